@@ -1,17 +1,36 @@
 use std::{fs, io};
+use std::path::Path;
 use std::thread::sleep;
 use std::time::{Duration, SystemTime};
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 use tui::backend::CrosstermBackend;
 use tui::Terminal;
 
+use crate::rail_analysis::rail_cfg::RailCfg;
+use crate::rail_analysis::rail_equivalence::{EquivLocation, EquivSpec, RailEquivalence, RailEquivalenceTrait};
+use crate::rail_analysis::rail_linter::{RailLinter, RailLinterTrait};
+use crate::rail_analysis::rail_stack_analysis::{RailStackAnalysis, RailStackAnalysisTrait, STACK_SIZE};
 use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+use crate::rail_assembler::rasm_dictionary::RasmDictionary;
+use crate::rail_compiler::{RailCompiler, RailCompilerTrait};
+use crate::rail_linker::{RailObjectLinker, RailObjectLinkerTrait};
+use crate::rail_linker::rail_object::RailObject;
+use crate::rail_lsp::{RailLanguageServer, RailLanguageServerTrait};
+use crate::rail_optimizer::{RailOptimizer, RailOptimizerTrait};
+use crate::rail_optimizer::rail_superoptimizer::{RailSuperoptimizer, RailSuperoptimizerTrait, SuperoptSpec};
 use crate::rail_system::{RailSystem, RailSystemTrait};
+use crate::rail_system::rail_isa;
+use crate::rail_system::rail_isa::IsaExtension;
 use crate::ui::RailTerminalUI;
 
 mod rail_system;
 mod rail_assembler;
+mod rail_analysis;
+mod rail_compiler;
+mod rail_linker;
+mod rail_lsp;
+mod rail_optimizer;
 mod ui;
 
 fn main() {
@@ -21,7 +40,8 @@ fn main() {
             .long("input")
             .short('i')
             .takes_value(true)
-            .help("Input file to run or assemble."))
+            .multiple_occurrences(true)
+            .help("Input file to run or assemble. Repeat it to link several objects."))
         .arg(Arg::with_name("output")
             .long("output")
             .short('o')
@@ -39,10 +59,102 @@ fn main() {
             .long("assemble")
             .short('a')
             .help("Assemble a source AMS file."))
+        .arg(Arg::with_name("compile")
+            .long("compile")
+            .short('x')
+            .help("Compiles a source file in the C-like Rail language to a binary."))
+        .arg(Arg::with_name("brainfuck")
+            .long("brainfuck")
+            .help("When compiling, reads the input as Brainfuck."))
+        .arg(Arg::with_name("emit-asm")
+            .long("emit-asm")
+            .help("When compiling, writes the generated ASM instead of the binary."))
         .arg(Arg::with_name("run")
             .long("run")
             .short('r')
             .help("Runs an assembled binary file."))
+        .arg(Arg::with_name("unified")
+            .long("unified")
+            .help("When running, puts program and RAM in one address space, so RAM_W can change the code."))
+        .arg(Arg::with_name("irq")
+            .long("irq")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .help("When running, raises an interrupt line at a step, like 100:2 for line 2 before step 100. Needs --ext interrupts."))
+        .arg(Arg::with_name("shift-mask")
+            .long("shift-mask")
+            .help("When running, masks shift amounts to their low 3 bits like the reference architecture, instead of shifting everything out from 8 on."))
+        .arg(Arg::with_name("optimize")
+            .long("optimize")
+            .short('O')
+            .help("When assembling, runs the peephole optimizer over the assembled code."))
+        .arg(Arg::with_name("define")
+            .short('D')
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .help("Defines a constant as NAME=VALUE, or NAME for a value of 1, for IFDEF and IF in the source."))
+        .arg(Arg::with_name("ext")
+            .long("ext")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .help("Enables an ISA extension when assembling, compiling, running or serving --lsp, like muldiv. See --isa."))
+        .arg(Arg::with_name("object")
+            .long("object")
+            .short('c')
+            .help("When assembling, writes a relocatable object file instead of a binary."))
+        .arg(Arg::with_name("link")
+            .long("link")
+            .short('k')
+            .help("Links the object files given with -i into a binary."))
+        .arg(Arg::with_name("map")
+            .long("map")
+            .takes_value(true)
+            .help("When linking, the map file to write. Defaults to the output file with .map appended."))
+        .arg(Arg::with_name("lint")
+            .long("lint")
+            .short('l')
+            .help("Checks a source ASM file for likely bugs."))
+        .arg(Arg::with_name("cfg")
+            .long("cfg")
+            .short('g')
+            .help("Exports the control-flow graph of an ASM file as Graphviz DOT, to the output file or stdout."))
+        .arg(Arg::with_name("stack")
+            .long("stack")
+            .help("Bounds the call stack and gen stack depth and the steps to HALT of an ASM file, per routine."))
+        .arg(Arg::with_name("superopt")
+            .long("superopt")
+            .help("Searches for the shortest ALU instruction sequences that compute the same as an ASM file."))
+        .arg(Arg::with_name("regs")
+            .long("regs")
+            .takes_value(true)
+            .default_value("R1:R1")
+            .help("For --superopt, the input registers and the output register, like R1,R2:R3."))
+        .arg(Arg::with_name("spec")
+            .long("spec")
+            .takes_value(true)
+            .help("For --superopt without an input file, a built-in function: mulN (times N), neg, avg, lowbit or clearbit."))
+        .arg(Arg::with_name("max-length")
+            .long("max-length")
+            .takes_value(true)
+            .default_value("3")
+            .help("For --superopt, the longest sequence to try."))
+        .arg(Arg::with_name("equiv")
+            .long("equiv")
+            .short('e')
+            .help("Checks that the two programs given with -i end with the same registers and ram for every input."))
+        .arg(Arg::with_name("inputs")
+            .long("inputs")
+            .takes_value(true)
+            .help("For --equiv, the registers and ram cells to try every value of, like R1,R2,@0x10."))
+        .arg(Arg::with_name("binary")
+            .long("binary")
+            .help("Treats the input as an assembled binary instead of ASM source."))
+        .arg(Arg::with_name("isa")
+            .long("isa")
+            .help("Prints the instruction set reference as a markdown table."))
+        .arg(Arg::with_name("lsp")
+            .long("lsp")
+            .help("Runs a language server for Rail ASM over stdio."))
 
         .arg(Arg::with_name("ui")
             .long("with-ui")
@@ -53,7 +165,7 @@ fn main() {
             .short('s')
             .takes_value(true)
             .default_value("256")
-            .help("When running a file, sets the number of steps to run."))
+            .help("When running a file or checking equivalence, sets the number of steps to run."))
         .arg(Arg::with_name("delay")
             .long("delay")
             .short('w')
@@ -70,7 +182,16 @@ fn main() {
     let bench_flag = matches.is_present("bench");
     let debug_flag = matches.is_present("debug");
     let assemble_flag = matches.is_present("assemble");
+    let compile_flag = matches.is_present("compile");
     let run_flag = matches.is_present("run");
+    let link_flag = matches.is_present("link");
+    let lint_flag = matches.is_present("lint");
+    let lsp_flag = matches.is_present("lsp");
+    let isa_flag = matches.is_present("isa");
+    let cfg_flag = matches.is_present("cfg");
+    let stack_flag = matches.is_present("stack");
+    let superopt_flag = matches.is_present("superopt");
+    let equiv_flag = matches.is_present("equiv");
     let gui_flag = matches.is_present("ui");
     let print_hex = matches.is_present("print-hex");
 
@@ -78,21 +199,213 @@ fn main() {
         return
     }
 
-    if bench_flag {
+    if isa_flag {
+        print!("{}", rail_isa::to_markdown());
+    }
+    else if bench_flag {
         run_benchmark();
     }
     else if debug_flag {
         run_debug();
     }
+    else if compile_flag {
+        let input_path = matches.value_of("input").expect("Need an input file to compile!");
+        let input_text = fs::read_to_string(input_path).expect("Input file does not exist or is unreadable!");
+        let output_path = matches.value_of("output").expect("Need an output file for the compiled program!");
+
+        let mut rail_compiler = RailCompiler::new();
+        for extension in get_extensions(&matches) {
+            rail_compiler.enable_extension(extension);
+        }
+        let is_brainfuck = matches.is_present("brainfuck");
+        if matches.is_present("emit-asm") {
+            let asm = match is_brainfuck {
+                true => rail_compiler.compile_brainfuck(&input_text),
+                false => rail_compiler.compile(&input_text)
+            };
+            fs::write(output_path, asm.unwrap_or_else(|e| panic!("{}", e))).expect("Failed to write to output file!");
+            return
+        }
+        let program = match is_brainfuck {
+            true => rail_compiler.compile_brainfuck_program(&input_text),
+            false => rail_compiler.compile_program(&input_text)
+        };
+        let mut program = program.unwrap_or_else(|e| panic!("{}", e));
+        if matches.is_present("optimize") {
+            program = RailOptimizer::new().optimize(&program).0;
+        }
+        fs::write(output_path, program.binary).expect("Failed to write to output file!");
+    }
     else if assemble_flag {
         let input_path = matches.value_of("input").expect("Need an input file to assemble!");
         let input_text = fs::read_to_string(input_path).expect("Input file does not exist or is unreadable!");
         let output_path = matches.value_of("output").expect("Need an output file for assembled binary!");
 
-        let rail_assembler = RailAssembler::new();
-        let assembled = rail_assembler.assemble(&input_text);
+        let rail_assembler = create_assembler(&matches);
+        if matches.is_present("object") {
+            let program = rail_assembler.try_assemble_object(&input_text).unwrap_or_else(|e| panic!("{}", e));
+            let name = Path::new(input_path).file_stem().and_then(|stem| stem.to_str()).unwrap_or(input_path);
+            let object = RailObject::from_program(name, &program);
+            fs::write(output_path, object.to_text()).expect("Failed to write to output file!");
+            return
+        }
+        let mut program = rail_assembler.assemble_program(&input_text);
+        if matches.is_present("optimize") {
+            let (optimized, stats) = RailOptimizer::new().optimize(&program);
+            println!("Optimizer: {} instruction(s) removed ({} no-op moves, {} dead), {} constant(s) folded, {} jump(s) threaded.",
+                     stats.get_removed_instructions(), stats.removed_moves, stats.removed_dead_code,
+                     stats.folded_constants, stats.threaded_jumps);
+            program = optimized;
+        }
 
-        fs::write(output_path, assembled).expect("Failed to write to output file!");
+        fs::write(output_path, program.binary).expect("Failed to write to output file!");
+    }
+    else if link_flag {
+        let input_paths = matches.values_of("input").expect("Need object files to link!");
+        let output_path = matches.value_of("output").expect("Need an output file for the linked binary!");
+
+        let objects: Vec<RailObject> = input_paths
+            .map(|path| {
+                let text = fs::read_to_string(path).expect("Object file does not exist or is unreadable!");
+                RailObject::from_text(&text).unwrap_or_else(|e| panic!("{}: {}", path, e))
+            })
+            .collect();
+        let result = RailObjectLinker::new().link(&objects).unwrap_or_else(|e| panic!("Link failed: {}", e));
+
+        let map_path = match matches.value_of("map") {
+            Some(path) => path.to_string(),
+            None => format!("{}.map", output_path)
+        };
+        fs::write(output_path, result.binary).expect("Failed to write to output file!");
+        fs::write(map_path, result.map).expect("Failed to write the map file!");
+    }
+    else if lsp_flag {
+        let mut server = RailLanguageServer::new();
+        for extension in get_extensions(&matches) {
+            server.enable_extension(extension);
+        }
+        server.run(&mut io::stdin().lock(), &mut io::stdout()).expect("Language server IO error");
+        std::process::exit(if server.is_shutdown() { 0 } else { 1 });
+    }
+    else if cfg_flag {
+        let input_path = matches.value_of("input").expect("Need an input file to export!");
+        let cfg = if matches.is_present("binary") {
+            let input_bin = fs::read(input_path).expect("Input file does not exist or is unreadable!");
+            RailCfg::from_binary(&input_bin)
+        }
+        else {
+            let input_text = fs::read_to_string(input_path).expect("Input file does not exist or is unreadable!");
+            RailCfg::from_program(&create_assembler(&matches).assemble_program(&input_text))
+        };
+
+        for address in &cfg.computed_jumps {
+            let block_start = cfg.get_block_at(*address).map(|block| block.start).unwrap_or(*address);
+            eprintln!("Computed jump at 0x{:02X} in block {} can not be followed statically.",
+                      address, cfg.get_address_name(block_start));
+        }
+        match matches.value_of("output") {
+            Some(output_path) => fs::write(output_path, cfg.to_dot()).expect("Failed to write to output file!"),
+            None => print!("{}", cfg.to_dot())
+        }
+    }
+    else if stack_flag {
+        let input_path = matches.value_of("input").expect("Need an input file to analyze!");
+        let report = if matches.is_present("binary") {
+            let input_bin = fs::read(input_path).expect("Input file does not exist or is unreadable!");
+            RailStackAnalysis::new().analyze_binary(&input_bin)
+        }
+        else {
+            let input_text = fs::read_to_string(input_path).expect("Input file does not exist or is unreadable!");
+            RailStackAnalysis::new().analyze(&create_assembler(&matches).assemble_program(&input_text))
+        };
+
+        let bound = |value: Option<String>| value.unwrap_or_else(|| "unbounded".to_string());
+        println!("{:<7} {:<20} {:>10} {:>10} {:>10}", "Address", "Routine", "Call stack", "Gen stack", "Steps");
+        for proc in &report.procs {
+            println!("0x{:02X}    {:<20} {:>10} {:>10} {:>10}", proc.address, proc.name,
+                     bound(proc.call_depth.map(|depth| depth.to_string())),
+                     bound(proc.gen_depth.map(|depth| depth.to_string())),
+                     bound(proc.steps.map(|steps| steps.to_string())));
+        }
+        for proc in report.get_recursive() {
+            println!("\x1B[33m{} is recursive, its stack use has no static bound.\x1B[0m", proc.name);
+        }
+        for address in &report.computed_jumps {
+            println!("\x1B[33mComputed jump at 0x{:02X} can not be followed statically.\x1B[0m", address);
+        }
+        match report.fits() {
+            true => println!("Both stacks stay within {} entries.", STACK_SIZE),
+            false => println!("\x1B[33mCould not prove that both stacks stay within {} entries.\x1B[0m", STACK_SIZE)
+        }
+        std::process::exit(if report.fits() { 0 } else { 1 });
+    }
+    else if equiv_flag {
+        let input_paths: Vec<&str> = matches.values_of("input").expect("Need two programs to compare!").collect();
+        if input_paths.len() != 2 {
+            panic!("Need exactly two programs to compare, given with -i");
+        }
+        let programs: Vec<Vec<u8>> = input_paths.iter().map(|path| match matches.is_present("binary") {
+            true => fs::read(path).expect("Input file does not exist or is unreadable!"),
+            false => {
+                let text = fs::read_to_string(path).expect("Input file does not exist or is unreadable!");
+                create_assembler(&matches).assemble(&text)
+            }
+        }).collect();
+
+        let inputs: Vec<EquivLocation> = matches.value_of("inputs").unwrap_or("")
+            .split(',')
+            .filter(|location| !location.trim().is_empty())
+            .map(parse_equiv_location)
+            .collect();
+        let mut spec = EquivSpec::new(&inputs);
+        spec.max_steps = matches.value_of("steps")
+            .expect("Missing value for steps")
+            .parse::<u32>().expect("Value for steps must be an integer");
+        spec.extensions = get_extensions(&matches);
+        let report = RailEquivalence::new().check(&programs[0], &programs[1], &spec);
+        match &report.counterexample {
+            Some(counterexample) => println!("\x1B[33mNot equivalent. {}\x1B[0m", counterexample),
+            None => println!("Equivalent for {} {} input case(s).", if report.is_exhaustive { "all" } else { "a sample of" }, report.cases)
+        }
+        std::process::exit(if report.is_equivalent() { 0 } else { 1 });
+    }
+    else if superopt_flag {
+        let (inputs, output) = parse_superopt_regs(matches.value_of("regs").expect("Missing value for regs"));
+        let mut spec = match matches.value_of("input") {
+            Some(input_path) => {
+                let input_text = fs::read_to_string(input_path).expect("Input file does not exist or is unreadable!");
+                let reference = create_assembler(&matches).assemble(&input_text);
+                SuperoptSpec::new_reference(&inputs, output, &reference)
+            }
+            None => create_superopt_spec(matches.value_of("spec").expect("Need a reference ASM file or a --spec to superoptimize!"),
+                                         &inputs, output)
+        };
+        spec.max_length = matches.value_of("max-length")
+            .expect("Missing value for max-length")
+            .parse::<usize>().expect("Value for max-length must be an integer");
+        let result = RailSuperoptimizer::new().search(&spec);
+        match result.get_length() {
+            Some(length) => println!("{} sequence(s) of {} instruction(s), {} candidates tried{}:",
+                                     result.sequences.len(), length, result.candidates,
+                                     if result.is_exhaustive { "" } else { ", verified on a sample of inputs" }),
+            None => println!("No sequence of up to {} instruction(s) found, {} candidates tried.", spec.max_length, result.candidates)
+        }
+        for sequence in &result.sequences {
+            println!();
+            for instruction in sequence {
+                println!("{}", instruction.to_asm());
+            }
+        }
+    }
+    else if lint_flag {
+        let input_path = matches.value_of("input").expect("Need an input file to lint!");
+        let input_text = fs::read_to_string(input_path).expect("Input file does not exist or is unreadable!");
+
+        let warnings = RailLinter::new_with_assembler(create_assembler(&matches)).lint(&input_text);
+        for warning in &warnings {
+            println!("\x1B[33m{}\x1B[0m", warning);
+        }
+        println!("{} warning(s) found.", warnings.len());
     }
     else if run_flag {
         let input_path = matches.value_of("input").expect("Need an input file to assemble!");
@@ -107,6 +420,12 @@ fn main() {
 
         let mut rail_system = RailSystem::new();
         rail_system.load_program(&input_bin[..]);
+        for extension in get_extensions(&matches) {
+            rail_system.enable_extension(extension);
+        }
+        rail_system.set_shift_mask(matches.is_present("shift-mask"));
+        rail_system.set_unified_memory(matches.is_present("unified"));
+        let interrupts = get_interrupts(&matches);
         if gui_flag {
             rail_system.set_io_print(false);
             let backend = CrosstermBackend::new(io::stdout());
@@ -116,7 +435,8 @@ fn main() {
             terminal.clear().expect("Error clearing terminal.");
             sleep(Duration::from_millis(100));
 
-            for _i in 0..steps {
+            for i in 0..steps {
+                raise_interrupts(&mut ui.rail_system, &interrupts, i);
                 ui.rail_system.step();
                 if ui.rail_system.is_halted() { break }
                 terminal.draw(|f| {
@@ -129,7 +449,8 @@ fn main() {
         }
         else {  // no ui
             rail_system.set_io_print(true);
-            for _i in 0..steps {
+            for i in 0..steps {
+                raise_interrupts(&mut rail_system, &interrupts, i);
                 rail_system.step();
                 if rail_system.is_halted() { break }
                 sleep(Duration::from_millis(delay));
@@ -147,6 +468,80 @@ fn main() {
 
 }
 
+fn create_assembler(matches: &ArgMatches) -> RailAssembler {
+    let mut rail_assembler = RailAssembler::new();
+    for define in matches.values_of("define").into_iter().flatten() {
+        match define.split_once('=') {
+            Some((name, value)) => rail_assembler.set_define(name, value),
+            None => rail_assembler.set_define(define, "1")
+        }
+    }
+    for extension in get_extensions(matches) {
+        rail_assembler.enable_extension(extension);
+    }
+    rail_assembler
+}
+
+fn get_extensions(matches: &ArgMatches) -> Vec<IsaExtension> {
+    matches.values_of("ext").into_iter().flatten()
+        .map(|name| IsaExtension::from_name(name).unwrap_or_else(|| panic!("{} is not an ISA extension", name)))
+        .collect()
+}
+
+    // the steps and lines of --irq, like 100:2
+fn get_interrupts(matches: &ArgMatches) -> Vec<(u32, u8)> {
+    matches.values_of("irq").into_iter().flatten()
+        .map(|irq| irq.split_once(':')
+            .and_then(|(step, line)| Some((step.trim().parse().ok()?, line.trim().parse().ok()?)))
+            .unwrap_or_else(|| panic!("{} is not STEP:LINE", irq)))
+        .collect()
+}
+
+fn raise_interrupts(rail_system: &mut RailSystem, interrupts: &[(u32, u8)], step: u32) {
+    for (_, line) in interrupts.iter().filter(|(at, _)| *at == step) {
+        rail_system.raise_interrupt(*line);
+    }
+}
+
+    // a register name, or @ and an address for a ram cell
+fn parse_equiv_location(text: &str) -> EquivLocation {
+    let text = text.trim().to_uppercase();
+    match text.strip_prefix('@') {
+        Some(address) => EquivLocation::Ram(RailAssembler::decode_num(address)
+            .unwrap_or_else(|_| panic!("{} is not a ram address", address))),
+        None => EquivLocation::Register(RasmDictionary::translate(&text).ok()
+            .filter(|reg| *reg < 16)
+            .unwrap_or_else(|| panic!("{} is not a register", text)))
+    }
+}
+
+    // "R1,R2:R3" to the input registers and the output register
+fn parse_superopt_regs(text: &str) -> (Vec<u8>, u8) {
+    let register = |name: &str| name.trim().trim_start_matches(['R', 'r']).parse::<u8>().ok()
+        .filter(|register| *register < 8)
+        .unwrap_or_else(|| panic!("{} is not a register from R0 to R7", name));
+    let (inputs, output) = text.split_once(':').expect("Registers must be given as INPUTS:OUTPUT, like R1,R2:R3");
+    (inputs.split(',').map(register).collect(), register(output))
+}
+
+fn create_superopt_spec(name: &str, inputs: &[u8], output: u8) -> SuperoptSpec {
+    let input_count = if name == "avg" { 2 } else { 1 };
+    if inputs.len() != input_count {
+        panic!("{} takes {} input register(s), set them with --regs", name, input_count);
+    }
+    match name {
+        "neg" => SuperoptSpec::new_function(inputs, output, |x| x[0].wrapping_neg()),
+        "avg" => SuperoptSpec::new_function(inputs, output, |x| ((x[0] as u16 + x[1] as u16) / 2) as u8),
+        "lowbit" => SuperoptSpec::new_function(inputs, output, |x| x[0] & x[0].wrapping_neg()),
+        "clearbit" => SuperoptSpec::new_function(inputs, output, |x| x[0] & x[0].wrapping_sub(1)),
+        _ => {
+            let factor = name.strip_prefix("mul").and_then(|factor| factor.parse::<u8>().ok())
+                .unwrap_or_else(|| panic!("Unknown spec {}, expected mulN, neg, avg, lowbit or clearbit", name));
+            SuperoptSpec::new_function(inputs, output, move |x| x[0].wrapping_mul(factor))
+        }
+    }
+}
+
 fn run_benchmark() {
     let steps = 100000000;
     let mut system = RailSystem::new_with_program(&FIBONACCI_ASM);
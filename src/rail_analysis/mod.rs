@@ -0,0 +1,5 @@
+pub mod rail_cfg;
+pub mod rail_equivalence;
+pub mod rail_flow;
+pub mod rail_linter;
+pub mod rail_stack_analysis;
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use crate::rail_analysis::rail_flow::{decode_program, FlowInstruction, FlowKind};
+use crate::rail_assembler::rasm_program::RasmProgram;
+use crate::rail_system::rail_instruction::RailInstruction;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CfgEdgeKind {
+    Fallthrough,
+    Jump,
+    Taken,
+    NotTaken,
+    Call,
+    Return,     // from a CALL to the instruction after it
+    Computed,   // CNT written from a value, target unknown
+    Bank        // FAR_CALL or FAR_JMP into another program bank
+}
+
+pub struct CfgEdge {
+    pub from: u8,
+    pub to: Option<u8>,     // None leaves the program, is in another bank, or is unknown for computed edges
+    pub kind: CfgEdgeKind,
+    pub label: String
+}
+
+pub struct CfgBlock {
+    pub start: u8,
+    pub end: u8     // address of the last instruction
+}
+
+pub struct RailCfg {
+    pub instructions: Vec<FlowInstruction>,
+    pub blocks: Vec<CfgBlock>,
+    pub edges: Vec<CfgEdge>,
+    pub computed_jumps: Vec<u8>,
+    labels: HashMap<u8, String>
+}
+
+impl RailCfg {
+
+    pub fn from_binary(binary: &[u8]) -> Self {
+        Self::build(decode_program(binary), HashMap::new())
+    }
+
+    pub fn from_program(program: &RasmProgram) -> Self {
+        let mut labels = HashMap::new();
+        for label in &program.labels {
+            labels.entry(label.address).or_insert_with(|| label.name.to_string());
+        }
+        Self::build(decode_program(&program.binary), labels)
+    }
+
+    fn build(instructions: Vec<FlowInstruction>, labels: HashMap<u8, String>) -> Self {
+        let count = instructions.len();
+        let in_program = |address: u8| address.is_multiple_of(4) && (address as usize / 4) < count;
+
+        let mut leaders = vec![false; count];
+        if count > 0 {
+            leaders[0] = true;
+        }
+        for instruction in &instructions {
+            let index = instruction.address as usize / 4;
+            match instruction.kind {
+                FlowKind::Next => continue,
+                FlowKind::Jump(target) | FlowKind::Branch(target) | FlowKind::Call(target) if in_program(target) => {
+                    leaders[target as usize / 4] = true;
+                }
+                _ => { }
+            }
+            if index + 1 < count {
+                leaders[index + 1] = true;
+            }
+        }
+        for address in labels.keys() {
+            if in_program(*address) {
+                leaders[*address as usize / 4] = true;
+            }
+        }
+
+        let mut blocks: Vec<CfgBlock> = Vec::new();
+        for (index, is_leader) in leaders.iter().enumerate() {
+            let address = (index * 4) as u8;
+            match blocks.last_mut() {
+                Some(block) if !is_leader => block.end = address,
+                _ => blocks.push(CfgBlock { start: address, end: address })
+            }
+        }
+
+        let mut cfg = Self { instructions, blocks, edges: Vec::new(), computed_jumps: Vec::new(), labels };
+        cfg.build_edges();
+        cfg
+    }
+
+    fn build_edges(&mut self) {
+        let count = self.instructions.len();
+        let check = |address: u16| if address.is_multiple_of(4) && (address as usize / 4) < count { Some(address as u8) } else { None };
+
+        let mut edges = Vec::new();
+        let mut computed_jumps = Vec::new();
+        for block in &self.blocks {
+            let last = &self.instructions[block.end as usize / 4];
+            let next = check(last.next_address());
+            let mut add = |to: Option<u8>, kind: CfgEdgeKind, label: String| {
+                edges.push(CfgEdge { from: block.start, to, kind, label });
+            };
+            match last.kind {
+                FlowKind::Next => add(next, CfgEdgeKind::Fallthrough, String::new()),
+                FlowKind::Jump(target) => add(check(target as u16), CfgEdgeKind::Jump, String::new()),
+                FlowKind::Branch(target) => {
+                    add(check(target as u16), CfgEdgeKind::Taken, self.get_condition(last));
+                    add(next, CfgEdgeKind::NotTaken, "else".to_string());
+                }
+                FlowKind::Call(target) => {
+                    let label = if last.instruction == RailInstruction::InterruptVector { "interrupt" } else { "call" };
+                    add(check(target as u16), CfgEdgeKind::Call, label.to_string());
+                    add(next, CfgEdgeKind::Return, "return".to_string());
+                }
+                FlowKind::IndirectCall => {
+                    let label = match last.instruction {
+                        RailInstruction::FarCall => format!("call {}", Self::get_far_target(last)),
+                        _ => format!("call {}", FlowInstruction::register_name(last.block.arg1))
+                    };
+                    add(None, CfgEdgeKind::Computed, label);
+                    add(next, CfgEdgeKind::Return, "return".to_string());
+                    computed_jumps.push(last.address);
+                }
+                FlowKind::IndirectJump => {
+                    let label = match last.instruction {
+                        RailInstruction::FarJump => Self::get_far_target(last),
+                        _ => "CNT = ?".to_string()
+                    };
+                    add(None, CfgEdgeKind::Computed, label);
+                    computed_jumps.push(last.address);
+                }
+                FlowKind::FarCall(_, _) => {
+                    add(None, CfgEdgeKind::Bank, format!("call {}", Self::get_far_target(last)));
+                    add(next, CfgEdgeKind::Return, "return".to_string());
+                }
+                FlowKind::FarJump(_, _) => add(None, CfgEdgeKind::Bank, Self::get_far_target(last)),
+                FlowKind::Return | FlowKind::Halt => { }
+            }
+        }
+        self.edges = edges;
+        self.computed_jumps = computed_jumps;
+    }
+
+        // like bank 1 at 0x10, with register names for operands without the immediate flag
+    fn get_far_target(instruction: &FlowInstruction) -> String {
+        let block = &instruction.block;
+        let bank = if block.is_arg1_immediate() { block.arg1.to_string() } else { FlowInstruction::register_name(block.arg1) };
+        let address = if block.is_arg2_immediate() { format!("0x{:02X}", block.arg2) } else { FlowInstruction::register_name(block.arg2) };
+        format!("bank {} at {}", bank, address)
+    }
+
+    fn get_condition(&self, instruction: &FlowInstruction) -> String {
+        match instruction.instruction {
+            RailInstruction::Carry => return "C".to_string(),
+            RailInstruction::NotCarry => return "!C".to_string(),
+            RailInstruction::Zero => return "Z".to_string(),
+            RailInstruction::Overflow => return "V".to_string(),
+            _ => { }
+        }
+        let block = &instruction.block;
+        let value = |arg: u8, immediate: bool| if immediate { arg.to_string() } else { FlowInstruction::register_name(arg) };
+        if matches!(instruction.instruction, RailInstruction::BitSet | RailInstruction::BitClear) {
+            let negation = if instruction.instruction == RailInstruction::BitClear { "!" } else { "" };
+            return format!("{}bit {} of {}", negation, value(block.arg2, block.is_arg2_immediate()),
+                           value(block.arg1, block.is_arg1_immediate()));
+        }
+        let operator = match instruction.instruction {
+            RailInstruction::Equals => "==",
+            RailInstruction::NotEquals => "!=",
+            RailInstruction::LessThan => "<",
+            RailInstruction::LessEqualThan => "<=",
+            RailInstruction::MoreThan => ">",
+            RailInstruction::MoreEqualThan => ">=",
+            RailInstruction::LessThanSigned => "<s",
+            RailInstruction::LessEqualThanSigned => "<=s",
+            RailInstruction::MoreThanSigned => ">s",
+            RailInstruction::MoreEqualThanSigned => ">=s",
+            _ => "?"
+        };
+        format!("{} {} {}", value(block.arg1, block.is_arg1_immediate()), operator,
+                value(block.arg2, block.is_arg2_immediate()))
+    }
+
+    pub fn get_block_at(&self, address: u8) -> Option<&CfgBlock> {
+        self.blocks.iter().find(|block| block.start <= address && address <= block.end)
+    }
+
+    pub fn get_address_name(&self, address: u8) -> String {
+        match self.labels.get(&address) {
+            Some(name) => name.to_string(),
+            None => format!("0x{:02X}", address)
+        }
+    }
+
+    fn node_name(address: u8) -> String {
+        format!("b_{:02X}", address)
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    pub fn to_dot(&self) -> String {
+        let address_name = |address: u8| self.get_address_name(address);
+        let mut res = String::from("digraph rail {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for block in &self.blocks {
+            let mut label = String::new();
+            if let Some(name) = self.labels.get(&block.start) {
+                label.push_str(&format!("{}:\\l", Self::escape(name)));
+            }
+            let mut ends_in_halt = false;
+            for instruction in &self.instructions[block.start as usize / 4..=block.end as usize / 4] {
+                label.push_str(&format!("0x{:02X}  {}\\l", instruction.address,
+                                        Self::escape(&instruction.to_asm(&address_name))));
+                ends_in_halt = instruction.kind == FlowKind::Halt;
+            }
+            let style = if ends_in_halt { ", peripheries=2" } else { "" };
+            res.push_str(&format!("    {} [label=\"{}\"{}];\n", Self::node_name(block.start), label, style));
+        }
+
+        let mut has_outside = false;
+        let mut has_computed = false;
+        let mut has_bank = false;
+        for edge in &self.edges {
+            let target = match (edge.to, edge.kind) {
+                (Some(to), _) => Self::node_name(to),
+                (None, CfgEdgeKind::Computed) => { has_computed = true; "computed".to_string() }
+                (None, CfgEdgeKind::Bank) => { has_bank = true; "bank".to_string() }
+                (None, _) => { has_outside = true; "outside".to_string() }
+            };
+            let style = match edge.kind {
+                CfgEdgeKind::Call | CfgEdgeKind::Bank => ", style=dashed",
+                CfgEdgeKind::Return => ", style=dotted",
+                CfgEdgeKind::Computed => ", color=red",
+                _ => ""
+            };
+            res.push_str(&format!("    {} -> {} [label=\"{}\"{}];\n", Self::node_name(edge.from), target,
+                                  Self::escape(&edge.label), style));
+        }
+        if has_outside {
+            res.push_str("    outside [label=\"zeroed memory\", shape=plaintext];\n");
+        }
+        if has_computed {
+            res.push_str("    computed [label=\"computed jump\", shape=diamond, color=red];\n");
+        }
+        if has_bank {
+            res.push_str("    bank [label=\"other bank\", shape=plaintext];\n");
+        }
+
+        res.push_str("}\n");
+        res
+    }
+
+}
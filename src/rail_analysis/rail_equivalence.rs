@@ -0,0 +1,185 @@
+use std::fmt::{Display, Formatter};
+
+use crate::rail_analysis::rail_flow::{FlowInstruction, CNT_REGISTER};
+use crate::rail_system::{RailSystem, RailSystemTrait};
+use crate::rail_system::rail_isa::IsaExtension;
+
+const SAMPLED_CASES: u64 = 65536;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EquivLocation {
+    Register(u8),
+    Ram(u8)
+}
+
+impl EquivLocation {
+    fn read(&self, system: &RailSystem) -> u8 {
+        match self {
+            EquivLocation::Register(reg) => system.get_register_value(*reg),
+            EquivLocation::Ram(address) => system.get_ram_slice(*address, *address)[0]
+        }
+    }
+
+    fn write(&self, system: &mut RailSystem, value: u8) {
+        match self {
+            EquivLocation::Register(reg) => system.set_register_value(*reg, value),
+            EquivLocation::Ram(address) => system.set_ram_value(*address, value)
+        }
+    }
+}
+
+impl Display for EquivLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EquivLocation::Register(reg) => write!(f, "{}", FlowInstruction::register_name(*reg)),
+            EquivLocation::Ram(address) => write!(f, "ram[0x{:02X}]", address)
+        }
+    }
+}
+
+pub struct EquivSpec {
+    pub inputs: Vec<EquivLocation>,     // enumerated, everything else starts at 0
+    pub outputs: Vec<EquivLocation>,    // compared once both programs stop
+    pub max_steps: u32,
+    pub max_cases: u64,     // above this many input combinations, a fixed sample is checked instead
+    pub extensions: Vec<IsaExtension>   // enabled on the machines running both programs
+}
+
+impl EquivSpec {
+
+        // compares every register but CNT, which differs with the program length, and all of ram
+    pub fn new(inputs: &[EquivLocation]) -> Self {
+        let registers = (0..16u8).filter(|reg| *reg != CNT_REGISTER).map(EquivLocation::Register);
+        let ram = (0..=255u8).map(EquivLocation::Ram);
+        Self {
+            inputs: inputs.to_vec(),
+            outputs: registers.chain(ram).collect(),
+            max_steps: 256,
+            max_cases: 1 << 16,
+            extensions: Vec::new()
+        }
+    }
+
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EquivDifference {
+    Halting { first: bool, second: bool },
+    Value { location: EquivLocation, first: u8, second: u8 }
+}
+
+pub struct EquivCounterexample {
+    pub inputs: Vec<(EquivLocation, u8)>,
+    pub difference: EquivDifference
+}
+
+impl Display for EquivCounterexample {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let inputs: Vec<String> = self.inputs.iter().map(|(location, value)| format!("{} = {}", location, value)).collect();
+        let inputs = if inputs.is_empty() { "no inputs".to_string() } else { inputs.join(", ") };
+        match self.difference {
+            EquivDifference::Halting { first, second } => {
+                let state = |halted: bool| if halted { "halts" } else { "does not halt" };
+                write!(f, "With {}: the first program {}, the second {}.", inputs, state(first), state(second))
+            }
+            EquivDifference::Value { location, first, second } =>
+                write!(f, "With {}: {} is {} after the first program and {} after the second.", inputs, location, first, second)
+        }
+    }
+}
+
+pub struct EquivReport {
+    pub cases: u64,
+    pub is_exhaustive: bool,
+    pub counterexample: Option<EquivCounterexample>
+}
+
+impl EquivReport {
+    pub fn is_equivalent(&self) -> bool {
+        self.counterexample.is_none()
+    }
+}
+
+pub trait RailEquivalenceTrait {
+    fn check(&self, first: &[u8], second: &[u8], spec: &EquivSpec) -> EquivReport;
+}
+
+    // Runs two binaries side by side for every value of the inputs and compares where they end up.
+    // Programs that don't halt within the step budget are compared as they are at the end of it.
+pub struct RailEquivalence { }
+
+impl RailEquivalenceTrait for RailEquivalence {
+
+    fn check(&self, first: &[u8], second: &[u8], spec: &EquivSpec) -> EquivReport {
+        let combinations = 256u64.checked_pow(spec.inputs.len() as u32).unwrap_or(u64::MAX);
+        let is_exhaustive = combinations <= spec.max_cases;
+        let cases = if is_exhaustive { combinations } else { SAMPLED_CASES.min(spec.max_cases) };
+        let mut seed: u32 = 0x2545F491;
+
+        for case in 0..cases {
+            let values: Vec<u8> = (0..spec.inputs.len()).map(|i| match is_exhaustive {
+                true => (case >> (8 * i)) as u8,
+                false => {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    seed as u8
+                }
+            }).collect();
+            let first_system = Self::run(first, spec, &values);
+            let second_system = Self::run(second, spec, &values);
+            if let Some(difference) = Self::compare(&first_system, &second_system, spec) {
+                return EquivReport {
+                    cases: case + 1,
+                    is_exhaustive,
+                    counterexample: Some(EquivCounterexample {
+                        inputs: spec.inputs.iter().copied().zip(values).collect(),
+                        difference
+                    })
+                };
+            }
+        }
+        EquivReport { cases, is_exhaustive, counterexample: None }
+    }
+
+}
+
+impl Default for RailEquivalence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailEquivalence {
+
+    pub fn new() -> Self {
+        Self { }
+    }
+
+    fn run(program: &[u8], spec: &EquivSpec, values: &[u8]) -> RailSystem {
+        let mut system = RailSystem::new_with_program(program);
+        system.set_io_print(false);
+        for extension in &spec.extensions {
+            system.enable_extension(*extension);
+        }
+        for (location, value) in spec.inputs.iter().zip(values) {
+            location.write(&mut system, *value);
+        }
+        for _i in 0..spec.max_steps {
+            system.step();
+            if system.is_halted() { break }
+        }
+        system
+    }
+
+    fn compare(first: &RailSystem, second: &RailSystem, spec: &EquivSpec) -> Option<EquivDifference> {
+        if first.is_halted() != second.is_halted() {
+            return Some(EquivDifference::Halting { first: first.is_halted(), second: second.is_halted() });
+        }
+        spec.outputs.iter()
+            .map(|location| (*location, location.read(first), location.read(second)))
+            .find(|(_, a, b)| a != b)
+            .map(|(location, first, second)| EquivDifference::Value { location, first, second })
+    }
+
+}
@@ -0,0 +1,225 @@
+use crate::rail_assembler::rasm_dictionary::RasmDictionary;
+use crate::rail_system::rail_instruction::RailInstruction;
+use crate::rail_system::rail_instruction_block::RailInstructionBlock;
+use crate::rail_system::rail_isa;
+use crate::rail_system::rail_isa::{IsaEntry, IsaOperand};
+use crate::rail_system::rail_subsystem::RailSubSystem;
+
+pub const CNT_REGISTER: u8 = 14;
+pub const IO_REGISTER: u8 = 15;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FlowKind {
+    Next,               // falls through to the next instruction
+    Jump(u8),           // always jumps
+    Branch(u8),         // jumps or falls through
+    Call(u8),
+    IndirectCall,       // call target comes from a register
+    IndirectJump,       // writes CNT, target unknown statically
+    FarCall(u8, u8),    // call into program bank .0 at address .1, which returns after it
+    FarJump(u8, u8),    // continues in program bank .0 at address .1
+    Return,
+    Halt
+}
+
+pub struct FlowInstruction {
+    pub address: u8,
+    pub block: RailInstructionBlock,
+    pub instruction: RailInstruction,
+    pub kind: FlowKind
+}
+
+impl FlowInstruction {
+
+    pub fn new(address: u8, block: RailInstructionBlock) -> Self {
+        let instruction = block.get_instruction();
+        let mut res = Self { address, block, instruction, kind: FlowKind::Next };
+        res.kind = res.get_flow_kind();
+        res
+    }
+
+    pub fn get_subsystem(&self) -> RailSubSystem {
+        self.block.get_subsystem()
+    }
+
+    pub fn next_address(&self) -> u16 {
+        self.address as u16 + 4
+    }
+
+        // addresses control can reach from here, without following RET.
+        // None stands for falling through past the end of the program space.
+    pub fn get_successors(&self) -> Vec<Option<u8>> {
+        let next = if self.next_address() > 0xFF { None } else { Some(self.next_address() as u8) };
+        match self.kind {
+            FlowKind::Next => vec![next],
+            FlowKind::Jump(target) => vec![Some(target)],
+            FlowKind::Branch(target) => vec![Some(target), next],
+            FlowKind::Call(target) => vec![Some(target), next],
+            FlowKind::IndirectCall | FlowKind::FarCall(_, _) => vec![next],
+            FlowKind::IndirectJump | FlowKind::FarJump(_, _) | FlowKind::Return | FlowKind::Halt => vec![]
+        }
+    }
+
+    pub fn get_isa_entry(&self) -> Option<&'static IsaEntry> {
+        rail_isa::get_by_opcode(self.block.op)
+    }
+
+        // registers whose value is consumed by the instruction
+    pub fn get_read_registers(&self) -> Vec<u8> {
+        let block = &self.block;
+        let operands = match self.get_isa_entry() {
+            Some(entry) => entry.operands,
+            None => return Vec::new()
+        };
+        let mut res = Vec::new();
+        for (operand, arg, immediate) in [(operands[0], block.arg1, block.is_arg1_immediate()),
+                                          (operands[1], block.arg2, block.is_arg2_immediate())] {
+            match operand {
+                IsaOperand::Register => res.push(arg),
+                IsaOperand::Value | IsaOperand::Address if !immediate => res.push(arg),
+                _ => { }
+            }
+        }
+        res.retain(|reg| *reg < 16);
+        res
+    }
+
+    pub fn get_written_register(&self) -> Option<u8> {
+        match self.get_isa_entry()?.operands[2] {
+            IsaOperand::Target => Some(self.block.get_result()),
+            _ => None
+        }
+    }
+
+        // change of the gen stack depth, None when it can't be known without running the program
+    pub fn get_stack_delta(&self) -> Option<i32> {
+        match self.instruction {
+            RailInstruction::SPush => Some(1),
+            RailInstruction::SPop => Some(-1),
+            RailInstruction::SDrop if self.block.is_arg2_immediate() => Some(-(self.block.arg2 as i32)),
+            RailInstruction::SDrop | RailInstruction::SPWrite => None,
+            _ => Some(0)
+        }
+    }
+
+    pub fn get_mnemonic(&self) -> String {
+        let op = self.block.op;
+        let entry = match self.get_isa_entry() {
+            Some(entry) if op & entry.get_fixed_flags() == entry.get_fixed_flags() => entry,
+                // no mnemonic encodes these, so the raw opcode is used
+            _ => return format!("0x{:02X}", op)
+        };
+        let mut res = entry.mnemonic.to_string();
+        let flags = op & !entry.get_fixed_flags();
+        if flags & rail_isa::IM1 != 0 {
+            res.push_str("+IM1");
+        }
+        if flags & rail_isa::IM2 != 0 {
+            res.push_str("+IM2");
+        }
+        res
+    }
+
+        // renders the instruction as v1 assembly; `address_name` names jump and call targets
+    pub fn to_asm(&self, address_name: &dyn Fn(u8) -> String) -> String {
+        let block = &self.block;
+        let entry = match self.get_isa_entry() {
+            Some(entry) => entry,
+            None => return format!("0x{:02X} 0x{:02X} 0x{:02X} 0x{:02X}", block.op,
+                                   block.arg1, block.arg2, block.result)
+        };
+        let render = |operand: IsaOperand, arg: u8, immediate: bool| match operand {
+            IsaOperand::Register | IsaOperand::Target => Self::register_name(arg),
+            IsaOperand::Address if immediate => address_name(arg),
+            _ if immediate => arg.to_string(),
+            _ => Self::register_name(arg)
+        };
+        let arg1 = render(entry.operands[0], block.arg1, block.is_arg1_immediate());
+        let arg2 = render(entry.operands[1], block.arg2, block.is_arg2_immediate());
+            // RES has no immediate flag: jump targets are always addresses
+        let result = match entry.operands[2] {
+            IsaOperand::Unused => block.result.to_string(),
+            operand => render(operand, block.result, true)
+        };
+        format!("{} {} {} {}", self.get_mnemonic(), arg1, arg2, result)
+    }
+
+    pub fn register_name(reg: u8) -> String {
+        match RasmDictionary::REGISTERS.get(reg as usize) {
+            Some(name) => name.to_string(),
+            None => reg.to_string()
+        }
+    }
+
+    pub fn writes_cnt(&self) -> bool {
+        self.get_written_register() == Some(CNT_REGISTER)
+    }
+
+    fn get_flow_kind(&self) -> FlowKind {
+        let block = &self.block;
+        let instruction = self.instruction;
+        match block.get_subsystem() {
+            RailSubSystem::CU => {
+                let target = block.get_cu_addr();
+                if instruction == RailInstruction::True {
+                    return FlowKind::Jump(target)
+                }
+                if instruction == RailInstruction::False || instruction == RailInstruction::None
+                    || instruction == RailInstruction::InterruptEnable {
+                    return FlowKind::Next
+                }
+                    // the handler can run from here on, between any two instructions, much like a call
+                if instruction == RailInstruction::InterruptVector {
+                    return FlowKind::Call(target)
+                }
+                if block.is_arg1_immediate() && block.is_arg2_immediate() {
+                    match instruction.compare(block.arg1, block.arg2) {
+                        Some(true) => return FlowKind::Jump(target),
+                        Some(false) => return FlowKind::Next,
+                        None => { }
+                    }
+                }
+                FlowKind::Branch(target)
+            }
+            RailSubSystem::RamStack => match instruction {
+                RailInstruction::Ret | RailInstruction::InterruptReturn => FlowKind::Return,
+                RailInstruction::Call | RailInstruction::CallIndirect if block.is_arg1_immediate() => FlowKind::Call(block.arg1),
+                RailInstruction::Call | RailInstruction::CallIndirect => FlowKind::IndirectCall,
+                RailInstruction::JumpIndirect if block.is_arg1_immediate() => FlowKind::Jump(block.arg1),
+                RailInstruction::JumpIndirect => FlowKind::IndirectJump,
+                    // the other bank is not part of the program, a bank held in a register may not be either
+                RailInstruction::FarCall if block.is_arg1_immediate() && block.is_arg2_immediate() => FlowKind::FarCall(block.arg1, block.arg2),
+                RailInstruction::FarCall => FlowKind::IndirectCall,
+                RailInstruction::FarJump if block.is_arg1_immediate() && block.is_arg2_immediate() => FlowKind::FarJump(block.arg1, block.arg2),
+                RailInstruction::FarJump => FlowKind::IndirectJump,
+                _ if self.writes_cnt() => FlowKind::IndirectJump,
+                _ => FlowKind::Next
+            },
+            RailSubSystem::Alu => match instruction {
+                RailInstruction::Halt => FlowKind::Halt,
+                _ if self.writes_cnt() => FlowKind::IndirectJump,
+                _ => FlowKind::Next
+            },
+            RailSubSystem::Peripheral => match instruction {
+                RailInstruction::PortRead if block.get_result() == CNT_REGISTER => FlowKind::IndirectJump,
+                _ => FlowKind::Next
+            },
+            _ => FlowKind::Next
+        }
+    }
+
+}
+
+    // decodes every 4 byte instruction of the binary; a trailing partial block is zero padded
+pub fn decode_program(binary: &[u8]) -> Vec<FlowInstruction> {
+    binary.chunks(4)
+        .take(64)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut bytes = [0u8; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            FlowInstruction::new((i * 4) as u8,
+                                 RailInstructionBlock::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+        })
+        .collect()
+}
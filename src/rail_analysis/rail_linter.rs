@@ -0,0 +1,387 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use crate::rail_analysis::rail_flow::{decode_program, FlowInstruction, FlowKind, CNT_REGISTER, IO_REGISTER};
+use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+use crate::rail_assembler::rasm_program::RasmProgram;
+use crate::rail_system::rail_instruction::RailInstruction;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LintKind {
+    UnreachableCode,
+    UnusedLabel,
+    UnusedConst,
+    CntWrite,
+    UninitializedRead,
+    UnmatchedRet,
+    UnbalancedStack,
+    MissingHalt,
+    ProcConvention
+}
+
+impl LintKind {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            LintKind::UnreachableCode => "unreachable-code",
+            LintKind::UnusedLabel => "unused-label",
+            LintKind::UnusedConst => "unused-const",
+            LintKind::CntWrite => "cnt-write",
+            LintKind::UninitializedRead => "uninitialized-read",
+            LintKind::UnmatchedRet => "unmatched-ret",
+            LintKind::UnbalancedStack => "unbalanced-stack",
+            LintKind::MissingHalt => "missing-halt",
+            LintKind::ProcConvention => "proc-convention"
+        }
+    }
+}
+
+pub struct LintWarning {
+    pub kind: LintKind,
+    pub line_number: Option<u32>,
+    pub original_line: String,
+    pub message: String
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.line_number {
+            Some(line_number) => write!(f, "Warning [{}] on line {}: {}\n Cause: {}",
+                                        self.kind.get_name(), line_number, self.original_line, self.message),
+            None => write!(f, "Warning [{}]: {}", self.kind.get_name(), self.message)
+        }
+    }
+}
+
+pub trait RailLinterTrait {
+    fn lint(&self, code: &str) -> Vec<LintWarning>;
+    fn lint_program(&self, program: &RasmProgram) -> Vec<LintWarning>;
+}
+
+pub struct RailLinter {
+    assembler: RailAssembler
+}
+
+impl RailLinterTrait for RailLinter {
+
+    fn lint(&self, code: &str) -> Vec<LintWarning> {
+        let program = self.assembler.assemble_program(code);
+        self.lint_program(&program)
+    }
+
+    fn lint_program(&self, program: &RasmProgram) -> Vec<LintWarning> {
+        let instructions = decode_program(&program.binary);
+        let mut warnings = Vec::new();
+
+        self.check_symbols(program, &mut warnings);
+        self.check_cnt_writes(program, &instructions, &mut warnings);
+        self.check_reachability(program, &instructions, &mut warnings);
+        self.check_unmatched_ret(program, &instructions, &mut warnings);
+        self.check_uninitialized_reads(program, &instructions, &mut warnings);
+        self.check_stack_balance(program, &instructions, &mut warnings);
+        self.check_proc_convention(program, &instructions, &mut warnings);
+
+        warnings.sort_by_key(|warning| warning.line_number.unwrap_or(0));
+        warnings
+    }
+
+}
+
+impl Default for RailLinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailLinter {
+
+    pub fn new() -> Self {
+        Self::new_with_assembler(RailAssembler::new())
+    }
+
+        // lints source with an assembler that has defines set
+    pub fn new_with_assembler(assembler: RailAssembler) -> Self {
+        Self { assembler }
+    }
+
+    fn warn_at(program: &RasmProgram, address: u8, kind: LintKind, message: String) -> LintWarning {
+        match program.get_code_line(address) {
+            Some(line) => LintWarning {
+                kind,
+                line_number: Some(line.line_number),
+                original_line: line.original_line.to_string(),
+                message
+            },
+            None => LintWarning { kind, line_number: None, original_line: String::new(), message }
+        }
+    }
+
+    fn check_symbols(&self, program: &RasmProgram, warnings: &mut Vec<LintWarning>) {
+        for label in &program.labels {
+            if label.references == 0 && !label.is_included {     // a module routine the program does not call
+                warnings.push(LintWarning {
+                    kind: LintKind::UnusedLabel,
+                    line_number: Some(label.line_number),
+                    original_line: format!("LABEL {}", label.name),
+                    message: format!("Label {} is never used", label.name)
+                });
+            }
+        }
+        for constant in &program.consts {
+            if constant.references == 0 {
+                warnings.push(LintWarning {
+                    kind: LintKind::UnusedConst,
+                    line_number: Some(constant.line_number),
+                    original_line: format!("CONST {} {}", constant.name, constant.value),
+                    message: format!("Const {} is never used", constant.name)
+                });
+            }
+        }
+    }
+
+    fn check_cnt_writes(&self, program: &RasmProgram, instructions: &[FlowInstruction],
+                        warnings: &mut Vec<LintWarning>) {
+        for instruction in instructions {
+            if instruction.writes_cnt() && instruction.kind != FlowKind::Halt {
+                warnings.push(Self::warn_at(program, instruction.address, LintKind::CntWrite,
+                    "Writes to CNT, which is a hidden jump that cannot be followed statically".to_string()));
+            }
+        }
+    }
+
+    fn has_indirect_jumps(instructions: &[FlowInstruction]) -> bool {
+        instructions.iter().any(|instr| instr.kind == FlowKind::IndirectJump)
+    }
+
+        // breadth first walk from address 0. Calls can be followed into the callee or stepped over.
+    fn get_reachable(instructions: &[FlowInstruction], enter_calls: bool) -> Vec<bool> {
+        let mut reachable = vec![false; instructions.len()];
+        let mut pending = vec![0u8];
+        while let Some(address) = pending.pop() {
+            let index = address as usize / 4;
+            if address % 4 != 0 || index >= instructions.len() || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            let instruction = &instructions[index];
+            let successors = match instruction.kind {
+                FlowKind::Call(_) if !enter_calls => vec![instruction.get_successors()[1]],
+                _ => instruction.get_successors()
+            };
+            pending.extend(successors.into_iter().flatten());
+        }
+
+        reachable
+    }
+
+    fn check_reachability(&self, program: &RasmProgram, instructions: &[FlowInstruction],
+                          warnings: &mut Vec<LintWarning>) {
+        let reachable = Self::get_reachable(instructions, true);
+        let note = if Self::has_indirect_jumps(instructions) {
+            " (the program writes CNT, so a hidden jump might still reach it)"
+        } else { "" };
+
+        let mut in_unreachable_run = false;
+        for (index, instruction) in instructions.iter().enumerate() {
+            if !reachable[index] {
+                let is_included = program.get_code_line(instruction.address).is_some_and(|line| line.is_included);
+                if !in_unreachable_run && !is_included {     // one warning per run of dead code
+                    warnings.push(Self::warn_at(program, instruction.address, LintKind::UnreachableCode,
+                        format!("Unreachable code{}", note)));
+                }
+                in_unreachable_run = true;
+                continue;
+            }
+            in_unreachable_run = false;
+
+            for successor in instruction.get_successors() {
+                let falls_out = match successor {
+                    Some(target) => target as usize / 4 >= instructions.len(),
+                    None => true
+                };
+                if falls_out {
+                    let message = if successor.is_some() && successor != Some(instruction.next_address() as u8) {
+                        "Jumps past the end of the program into zeroed memory, which runs as ADD R0 R0 R0"
+                    } else {
+                        "Missing HALT: execution falls into zeroed memory, which runs as ADD R0 R0 R0"
+                    };
+                    warnings.push(Self::warn_at(program, instruction.address, LintKind::MissingHalt,
+                                                message.to_string()));
+                }
+            }
+        }
+    }
+
+    fn check_unmatched_ret(&self, program: &RasmProgram, instructions: &[FlowInstruction],
+                           warnings: &mut Vec<LintWarning>) {
+        let main_body = Self::get_reachable(instructions, false);
+        for (index, instruction) in instructions.iter().enumerate() {
+            if main_body[index] && instruction.kind == FlowKind::Return {
+                warnings.push(Self::warn_at(program, instruction.address, LintKind::UnmatchedRet,
+                    "RET is reachable without a matching CALL".to_string()));
+            }
+        }
+    }
+
+        // forward "may have been written" analysis. Calls flow into the callee, and every RET flows
+        // back to every return site, since the analysis does not track call contexts.
+    fn check_uninitialized_reads(&self, program: &RasmProgram, instructions: &[FlowInstruction],
+                                 warnings: &mut Vec<LintWarning>) {
+        if instructions.is_empty() {
+            return;
+        }
+        let always_set: u16 = (1 << CNT_REGISTER) | (1 << IO_REGISTER);
+        let return_sites: Vec<u8> = instructions.iter()
+            .filter(|instr| matches!(instr.kind, FlowKind::Call(_) | FlowKind::IndirectCall | FlowKind::FarCall(_, _)))
+            .filter(|instr| instr.next_address() <= 0xFF)
+            .map(|instr| instr.next_address() as u8)
+            .collect();
+        let has_indirect = Self::has_indirect_jumps(instructions);
+
+        let mut states: Vec<Option<u16>> = vec![None; instructions.len()];
+        states[0] = Some(always_set);
+        let mut pending = vec![0usize];
+        while let Some(index) = pending.pop() {
+            let instruction = &instructions[index];
+            let mut out = states[index].unwrap();
+            if let Some(reg) = instruction.get_written_register() {
+                out |= 1 << (reg & 0x0F);
+            }
+
+            let mut successors: Vec<u8> = instruction.get_successors().into_iter().flatten().collect();
+            match instruction.kind {
+                FlowKind::Return => successors.extend(&return_sites),
+                FlowKind::IndirectJump if has_indirect =>
+                    successors.extend(instructions.iter().map(|instr| instr.address)),
+                _ => { }
+            }
+            for target in successors {
+                let target_index = target as usize / 4;
+                if target % 4 != 0 || target_index >= instructions.len() {
+                    continue;
+                }
+                let merged = states[target_index].unwrap_or(0) | out;
+                if states[target_index] != Some(merged) {
+                    states[target_index] = Some(merged);
+                    pending.push(target_index);
+                }
+            }
+        }
+
+        for (index, instruction) in instructions.iter().enumerate() {
+            let state = match states[index] {
+                Some(state) => state,
+                None => continue    // unreachable, reported elsewhere
+            };
+            let mut reported = HashSet::new();
+                // saving a USES register for the caller is not a use of its value
+            let saved = match program.get_proc_at(instruction.address) {
+                Some(proc) if instruction.instruction == RailInstruction::SPush => proc.uses.clone(),
+                _ => Vec::new()
+            };
+            for reg in instruction.get_read_registers().into_iter().filter(|reg| !saved.contains(reg)) {
+                if state & (1 << reg) == 0 && reported.insert(reg) {
+                    warnings.push(Self::warn_at(program, instruction.address, LintKind::UninitializedRead,
+                        format!("Reads {}, which is never written on any path before this point (reads as 0)",
+                                FlowInstruction::register_name(reg))));
+                }
+            }
+        }
+    }
+
+        // gen stack change of a call: PROCs pop their stack arguments, other routines are balanced
+    fn get_call_stack_delta(program: &RasmProgram, target: u8) -> i32 {
+        match program.procs.iter().find(|proc| proc.start == target) {
+            Some(proc) => -(proc.args.iter().filter(|arg| arg.on_stack).count() as i32),
+            None => 0
+        }
+    }
+
+        // walks every routine (the entry point and each CALL target) tracking the gen stack depth
+        // relative to the routine entry. Callees are assumed to leave the stack as they found it,
+        // less the stack arguments of a PROC.
+    fn check_stack_balance(&self, program: &RasmProgram, instructions: &[FlowInstruction],
+                           warnings: &mut Vec<LintWarning>) {
+        let mut entries = vec![0u8];
+        for instruction in instructions {
+            if let FlowKind::Call(target) = instruction.kind {
+                if !entries.contains(&target) {
+                    entries.push(target);
+                }
+            }
+        }
+
+        let mut reported: HashSet<u8> = HashSet::new();
+        for entry in entries {
+            let is_main = entry == 0;
+            let expected_return = Self::get_call_stack_delta(program, entry);
+            let mut depths: Vec<Option<i32>> = vec![None; instructions.len()];
+            let mut pending = vec![(entry, 0i32)];
+            while let Some((address, depth)) = pending.pop() {
+                let index = address as usize / 4;
+                if address % 4 != 0 || index >= instructions.len() {
+                    continue;
+                }
+                match depths[index] {
+                    Some(known) if known == depth => continue,
+                    Some(known) => {
+                        if reported.insert(address) {
+                            warnings.push(Self::warn_at(program, address, LintKind::UnbalancedStack,
+                                format!("Unbalanced S_PUSH/S_POP: reached with gen stack depth {} and {}",
+                                        known, depth)));
+                        }
+                        continue;
+                    }
+                    None => depths[index] = Some(depth)
+                }
+
+                let instruction = &instructions[index];
+                let delta = match instruction.get_stack_delta() {
+                    Some(delta) => delta,
+                    None => continue        // the depth is unknown from here on
+                };
+                let mut new_depth = depth + delta;
+                if let FlowKind::Call(target) = instruction.kind {
+                    new_depth += Self::get_call_stack_delta(program, target);
+                }
+                if is_main && delta < 0 && new_depth < 0 && reported.insert(address) {
+                    warnings.push(Self::warn_at(program, address, LintKind::UnbalancedStack,
+                        format!("{} on an empty gen stack", instruction.get_isa_entry().map_or("S_POP", |entry| entry.mnemonic))));
+                }
+                if !is_main && instruction.kind == FlowKind::Return && new_depth != expected_return
+                    && reported.insert(address) {
+                    warnings.push(Self::warn_at(program, address, LintKind::UnbalancedStack,
+                        format!("RET with gen stack depth {} relative to routine entry", new_depth)));
+                }
+
+                let successors = match instruction.kind {
+                    FlowKind::Call(_) => vec![instruction.get_successors()[1]],
+                    _ => instruction.get_successors()
+                };
+                for successor in successors.into_iter().flatten() {
+                    pending.push((successor, new_depth));
+                }
+            }
+        }
+    }
+
+        // a PROC may only write its arguments and the registers it saves with USES
+    fn check_proc_convention(&self, program: &RasmProgram, instructions: &[FlowInstruction],
+                             warnings: &mut Vec<LintWarning>) {
+        for instruction in instructions {
+            let proc = match program.get_proc_at(instruction.address) {
+                Some(proc) => proc,
+                None => continue
+            };
+            let reg = match instruction.get_written_register() {
+                Some(reg) if reg < 8 => reg,    // outputs, CNT and IO are not saved
+                _ => continue
+            };
+            if !proc.uses.contains(&reg) && !proc.args.iter().any(|arg| arg.register == reg) {
+                warnings.push(Self::warn_at(program, instruction.address, LintKind::ProcConvention,
+                    format!("PROC {} writes {}, which is neither an argument nor in its USES",
+                            proc.name, FlowInstruction::register_name(reg))));
+            }
+        }
+    }
+
+}
@@ -0,0 +1,610 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::rail_analysis::rail_cfg::RailCfg;
+use crate::rail_analysis::rail_flow::{FlowInstruction, FlowKind, CNT_REGISTER, IO_REGISTER};
+use crate::rail_assembler::rasm_program::RasmProgram;
+use crate::rail_system::rail_instruction::RailInstruction;
+use crate::rail_system::rail_subsystem::RailSubSystem;
+
+pub const STACK_SIZE: u32 = 128;     // entries in both the call stack and the gen stack
+const MAX_ITERATIONS: u32 = 256;     // an 8 bit counter that hasn't left the loop by now never will
+
+    // None in a depth or a step count means no bound could be proven
+pub struct StackProcReport {
+    pub name: String,
+    pub address: u8,
+    pub call_depth: Option<u32>,    // call stack entries used below this routine
+    pub gen_depth: Option<i32>,     // deepest gen stack relative to the routine entry, callees included
+    pub steps: Option<u64>,         // worst case steps until it returns or halts, callees included
+    pub is_recursive: bool
+}
+
+pub struct StackReport {
+    pub procs: Vec<StackProcReport>,    // the entry point first, then each CALL target by address
+    pub computed_jumps: Vec<u8>
+}
+
+impl StackReport {
+
+    pub fn get_entry(&self) -> &StackProcReport {
+        &self.procs[0]
+    }
+
+        // true when neither stack can overflow, from the entry point on
+    pub fn fits(&self) -> bool {
+        let entry = self.get_entry();
+        matches!(entry.call_depth, Some(depth) if depth <= STACK_SIZE)
+            && matches!(entry.gen_depth, Some(depth) if depth <= STACK_SIZE as i32)
+    }
+
+    pub fn get_recursive(&self) -> Vec<&StackProcReport> {
+        self.procs.iter().filter(|proc| proc.is_recursive).collect()
+    }
+
+}
+
+pub trait RailStackAnalysisTrait {
+    fn analyze(&self, program: &RasmProgram) -> StackReport;
+    fn analyze_binary(&self, binary: &[u8]) -> StackReport;
+}
+
+    // Bounds the stack use and running time of a program from its CFG. Every routine (the entry point
+    // and each CALL target) is walked on its own; callees are summarized and added at their call sites.
+    // Loops are bounded when one of their exits compares a register against a constant and the loop
+    // changes that register by a constant once per iteration, starting from a known value.
+pub struct RailStackAnalysis { }
+
+impl RailStackAnalysisTrait for RailStackAnalysis {
+
+    fn analyze(&self, program: &RasmProgram) -> StackReport {
+        Self::run(&RailCfg::from_program(program), Some(program))
+    }
+
+    fn analyze_binary(&self, binary: &[u8]) -> StackReport {
+        Self::run(&RailCfg::from_binary(binary), None)
+    }
+
+}
+
+impl Default for RailStackAnalysis {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailStackAnalysis {
+
+    pub fn new() -> Self {
+        Self { }
+    }
+
+    fn run(cfg: &RailCfg, program: Option<&RasmProgram>) -> StackReport {
+        let mut context = StackContext::new(&cfg.instructions, program);
+        let mut entries: Vec<u8> = context.routines.keys().copied().filter(|entry| *entry != 0).collect();
+        entries.sort();
+        entries.insert(0, 0);
+
+        let procs = entries.iter().map(|entry| StackProcReport {
+            name: cfg.get_address_name(*entry),
+            address: *entry,
+            call_depth: context.get_call_depth(*entry),
+            gen_depth: context.get_gen_depth(*entry),
+            steps: context.get_steps(*entry),
+            is_recursive: context.recursive.contains(entry)
+        }).collect();
+        StackReport { procs, computed_jumps: cfg.computed_jumps.clone() }
+    }
+
+}
+
+struct Routine {
+    nodes: Vec<usize>,          // instruction indices reached without entering calls
+    callees: Vec<u8>,
+    is_computed: bool,          // holds an indirect call or jump, or one into another bank
+    falls_off: bool             // runs past the end of the program
+}
+
+struct Loop {
+    header: usize,
+    body: HashSet<usize>,
+    latches: Vec<usize>         // sources of the edges back to the header
+}
+
+struct StackContext<'a> {
+    instructions: &'a [FlowInstruction],
+    program: Option<&'a RasmProgram>,
+    routines: HashMap<u8, Routine>,
+    recursive: HashSet<u8>,
+    writes: HashMap<u8, u16>,   // registers a routine or its callees may write, as a bit set
+    call_depths: HashMap<u8, Option<u32>>,
+    gen_depths: HashMap<u8, Option<i32>>,
+    steps: HashMap<u8, Option<u64>>
+}
+
+impl<'a> StackContext<'a> {
+
+    fn new(instructions: &'a [FlowInstruction], program: Option<&'a RasmProgram>) -> Self {
+        let mut context = Self {
+            instructions, program,
+            routines: HashMap::new(),
+            recursive: HashSet::new(),
+            writes: HashMap::new(),
+            call_depths: HashMap::new(),
+            gen_depths: HashMap::new(),
+            steps: HashMap::new()
+        };
+        if instructions.is_empty() {
+            context.routines.insert(0, Routine { nodes: Vec::new(), callees: Vec::new(), is_computed: false, falls_off: true });
+            return context;
+        }
+
+        let mut pending = vec![0u8];
+        while let Some(entry) = pending.pop() {
+            if context.routines.contains_key(&entry) {
+                continue;
+            }
+            let routine = context.walk_routine(entry);
+            pending.extend(routine.callees.iter().copied());
+            context.routines.insert(entry, routine);
+        }
+        context.find_recursion();
+        context.find_writes();
+        context
+    }
+
+    fn get_index(&self, address: Option<u8>) -> Option<usize> {
+        address.filter(|address| address % 4 == 0)
+            .map(|address| address as usize / 4)
+            .filter(|index| *index < self.instructions.len())
+    }
+
+        // successors inside the routine: a CALL continues after itself once the callee returns
+    fn get_successors(&self, index: usize) -> Vec<Option<usize>> {
+        let instruction = &self.instructions[index];
+        let successors = match instruction.kind {
+            FlowKind::Call(_) => vec![instruction.get_successors()[1]],
+            _ => instruction.get_successors()
+        };
+        successors.into_iter().map(|address| self.get_index(address)).collect()
+    }
+
+    fn walk_routine(&self, entry: u8) -> Routine {
+        let mut routine = Routine { nodes: Vec::new(), callees: Vec::new(), is_computed: false, falls_off: false };
+        let mut seen = vec![false; self.instructions.len()];
+        let mut pending = match self.get_index(Some(entry)) {
+            Some(index) => vec![index],
+            None => { routine.falls_off = true; vec![] }
+        };
+        while let Some(index) = pending.pop() {
+            if seen[index] {
+                continue;
+            }
+            seen[index] = true;
+            routine.nodes.push(index);
+            match self.instructions[index].kind {
+                FlowKind::Call(target) if !routine.callees.contains(&target) => routine.callees.push(target),
+                FlowKind::IndirectCall | FlowKind::IndirectJump | FlowKind::FarCall(_, _) | FlowKind::FarJump(_, _) =>
+                    routine.is_computed = true,
+                _ => { }
+            }
+            for successor in self.get_successors(index) {
+                match successor {
+                    Some(successor) => pending.push(successor),
+                    None => routine.falls_off = true
+                }
+            }
+        }
+        routine.nodes.sort();
+        routine
+    }
+
+        // a routine is recursive when it can reach itself through the call graph
+    fn find_recursion(&mut self) {
+        for entry in self.routines.keys() {
+            let mut seen: HashSet<u8> = HashSet::new();
+            let mut pending = self.routines[entry].callees.clone();
+            while let Some(callee) = pending.pop() {
+                if callee == *entry {
+                    self.recursive.insert(*entry);
+                    break;
+                }
+                if seen.insert(callee) {
+                    pending.extend(self.routines[&callee].callees.iter().copied());
+                }
+            }
+        }
+    }
+
+    fn find_writes(&mut self) {
+        for (entry, routine) in &self.routines {
+            let mut writes = 0u16;
+            for index in &routine.nodes {
+                if let Some(reg) = self.instructions[*index].get_written_register() {
+                    writes |= 1 << reg;
+                }
+            }
+            self.writes.insert(*entry, writes);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (entry, routine) in &self.routines {
+                let writes = routine.callees.iter().fold(self.writes[entry], |writes, callee| writes | self.writes[callee]);
+                if writes != self.writes[entry] {
+                    self.writes.insert(*entry, writes);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+        // gen stack change of a call: PROCs pop their stack arguments, other routines are balanced
+    fn get_call_stack_delta(&self, target: u8) -> i32 {
+        match self.program.and_then(|program| program.procs.iter().find(|proc| proc.start == target)) {
+            Some(proc) => -(proc.args.iter().filter(|arg| arg.on_stack).count() as i32),
+            None => 0
+        }
+    }
+
+    fn get_call_depth(&mut self, entry: u8) -> Option<u32> {
+        if let Some(depth) = self.call_depths.get(&entry) {
+            return *depth;
+        }
+        let routine = &self.routines[&entry];
+        let depth = if routine.is_computed || self.recursive.contains(&entry) {
+            None
+        }
+        else {
+            let mut depth = Some(0);
+            for callee in routine.callees.clone() {
+                depth = depth.zip(self.get_call_depth(callee)).map(|(depth, callee_depth)| depth.max(callee_depth + 1));
+            }
+            depth
+        };
+        self.call_depths.insert(entry, depth);
+        depth
+    }
+
+        // longest path over the gen stack depth; a node raised more often than there are nodes sits
+        // on a loop that keeps pushing
+    fn get_gen_depth(&mut self, entry: u8) -> Option<i32> {
+        if let Some(depth) = self.gen_depths.get(&entry) {
+            return *depth;
+        }
+        let depth = self.find_gen_depth(entry);
+        self.gen_depths.insert(entry, depth);
+        depth
+    }
+
+    fn find_gen_depth(&mut self, entry: u8) -> Option<i32> {
+        let routine = &self.routines[&entry];
+        if routine.is_computed || self.recursive.contains(&entry) {
+            return None;
+        }
+        let start = self.get_index(Some(entry))?;
+        let mut callee_depths: HashMap<u8, i32> = HashMap::new();
+        for callee in routine.callees.clone() {
+            callee_depths.insert(callee, self.get_gen_depth(callee)?);
+        }
+
+        let mut depths: Vec<Option<i32>> = vec![None; self.instructions.len()];
+        let mut raised = vec![0usize; self.instructions.len()];
+        let mut max_depth = 0;
+        let mut pending = vec![(start, 0i32)];
+        while let Some((index, depth)) = pending.pop() {
+            if matches!(depths[index], Some(known) if known >= depth) {
+                continue;
+            }
+            depths[index] = Some(depth);
+            raised[index] += 1;
+            if raised[index] > self.instructions.len() {
+                return None;
+            }
+
+            let instruction = &self.instructions[index];
+            let mut new_depth = depth + instruction.get_stack_delta()?;
+            if let FlowKind::Call(target) = instruction.kind {
+                max_depth = max_depth.max(depth + callee_depths[&target]);
+                new_depth += self.get_call_stack_delta(target);
+            }
+            max_depth = max_depth.max(new_depth);
+            for successor in self.get_successors(index).into_iter().flatten() {
+                pending.push((successor, new_depth));
+            }
+        }
+        Some(max_depth)
+    }
+
+    fn get_steps(&mut self, entry: u8) -> Option<u64> {
+        if let Some(steps) = self.steps.get(&entry) {
+            return *steps;
+        }
+        let steps = self.find_steps(entry);
+        self.steps.insert(entry, steps);
+        steps
+    }
+
+        // longest path to RET or HALT, with each loop collapsed into a single node costing its body
+        // times its number of iterations, innermost loops first
+    fn find_steps(&mut self, entry: u8) -> Option<u64> {
+        let routine = &self.routines[&entry];
+        if routine.is_computed || routine.falls_off || self.recursive.contains(&entry) {
+            return None;
+        }
+        let nodes = routine.nodes.clone();
+        let mut costs: HashMap<usize, u64> = HashMap::new();
+        for index in &nodes {
+            let cost = match self.instructions[*index].kind {
+                FlowKind::Call(target) => 1 + self.get_steps(target)?,
+                _ => 1
+            };
+            costs.insert(*index, cost);
+        }
+
+        let start = self.get_index(Some(entry))?;
+        let mut loops = self.find_loops(start, &nodes);
+        loops.sort_by_key(|l| l.body.len());
+        let mut representative: HashMap<usize, usize> = nodes.iter().map(|index| (*index, *index)).collect();
+        for l in &loops {
+            let body: Vec<usize> = nodes.iter().copied().filter(|index| l.body.contains(index)).collect();
+            let body_cost = self.get_longest_path(representative[&l.header], &body, &representative, &costs, Some(l.header))?;
+            let iterations = self.get_loop_iterations(entry, l)?;
+            for index in &body {
+                representative.insert(*index, l.header);
+            }
+            costs.insert(l.header, body_cost * (iterations as u64 + 1));
+        }
+        self.get_longest_path(representative[&start], &nodes, &representative, &costs, None)
+    }
+
+        // back edges found by a depth first walk, with the loop bodies they close
+    fn find_loops(&self, start: usize, nodes: &[usize]) -> Vec<Loop> {
+        let mut on_stack = vec![false; self.instructions.len()];
+        let mut seen = vec![false; self.instructions.len()];
+        let mut loops: Vec<Loop> = Vec::new();
+        let mut stack: Vec<(usize, Vec<usize>)> = vec![(start, self.get_successors(start).into_iter().flatten().collect())];
+        seen[start] = true;
+        on_stack[start] = true;
+        while let Some((index, successors)) = stack.last_mut() {
+            let index = *index;
+            match successors.pop() {
+                Some(successor) if on_stack[successor] => {
+                    match loops.iter_mut().find(|l| l.header == successor) {
+                        Some(l) => l.latches.push(index),
+                        None => loops.push(Loop { header: successor, body: HashSet::new(), latches: vec![index] })
+                    }
+                }
+                Some(successor) if !seen[successor] => {
+                    seen[successor] = true;
+                    on_stack[successor] = true;
+                    stack.push((successor, self.get_successors(successor).into_iter().flatten().collect()));
+                }
+                Some(_) => { }
+                None => {
+                    on_stack[index] = false;
+                    stack.pop();
+                }
+            }
+        }
+
+        for l in &mut loops {
+            l.body.insert(l.header);
+            let mut pending = l.latches.clone();
+            while let Some(index) = pending.pop() {
+                if !l.body.insert(index) {
+                    continue;
+                }
+                for predecessor in nodes {
+                    if self.get_successors(*predecessor).contains(&Some(index)) {
+                        pending.push(*predecessor);
+                    }
+                }
+            }
+        }
+        loops
+    }
+
+        // longest path from start over the collapsed nodes, leaving out the edges back to the header
+        // of the loop being measured. A cycle left over means the loop structure was not understood.
+    fn get_longest_path(&self, start: usize, nodes: &[usize], representative: &HashMap<usize, usize>,
+                        costs: &HashMap<usize, u64>, header: Option<usize>) -> Option<u64> {
+        let mut edges: HashMap<usize, Vec<usize>> = HashMap::new();
+        for index in nodes {
+            for successor in self.get_successors(*index).into_iter().flatten() {
+                let (from, to) = (representative[index], representative[&successor]);
+                if from != to && Some(successor) != header && nodes.contains(&successor) {
+                    edges.entry(from).or_default().push(to);
+                }
+            }
+        }
+
+        fn longest(node: usize, edges: &HashMap<usize, Vec<usize>>, costs: &HashMap<usize, u64>,
+                   memo: &mut HashMap<usize, Option<u64>>, visiting: &mut HashSet<usize>) -> Option<u64> {
+            if let Some(length) = memo.get(&node) {
+                return *length;
+            }
+            if !visiting.insert(node) {
+                return None;
+            }
+            let mut length = Some(0);
+            for successor in edges.get(&node).map(|successors| successors.as_slice()).unwrap_or(&[]) {
+                length = length.zip(longest(*successor, edges, costs, memo, visiting)).map(|(a, b)| a.max(b));
+            }
+            visiting.remove(&node);
+            let length = length.map(|length| length + costs[&node]);
+            memo.insert(node, length);
+            length
+        }
+        longest(start, &edges, costs, &mut HashMap::new(), &mut HashSet::new())
+    }
+
+        // complete iterations of a loop, from the first exit whose counter can be followed
+    fn get_loop_iterations(&self, entry: u8, l: &Loop) -> Option<u32> {
+        l.body.iter().filter_map(|index| self.get_exit_iterations(entry, l, *index)).min()
+    }
+
+    fn get_exit_iterations(&self, entry: u8, l: &Loop, exit: usize) -> Option<u32> {
+        let instruction = &self.instructions[exit];
+        let target = match instruction.kind {
+            FlowKind::Branch(target) => self.get_index(Some(target)),
+            _ => return None
+        };
+        let next = self.get_index(Some(instruction.next_address() as u8)).filter(|_| instruction.next_address() <= 0xFF);
+        let stays_when_taken = target.is_some_and(|target| l.body.contains(&target));
+        if stays_when_taken == next.is_some_and(|next| l.body.contains(&next)) {
+            return None;
+        }
+
+        let block = &instruction.block;
+        if !instruction.instruction.is_comparison() {
+            return None;
+        }
+        let (counter, counter_first) = match (block.is_arg1_immediate(), block.is_arg2_immediate()) {
+            (false, true) => (block.arg1, true),
+            (true, false) => (block.arg2, false),
+            _ => return None
+        };
+        if counter == CNT_REGISTER || counter == IO_REGISTER {
+            return None;
+        }
+        let limit = if counter_first { block.arg2 } else { block.arg1 };
+
+        let mut updates = Vec::new();
+        for index in &l.body {
+            let writes = match self.instructions[*index].kind {
+                FlowKind::Call(target) => self.writes[&target] & (1 << counter) != 0,
+                _ => self.instructions[*index].get_written_register() == Some(counter)
+            };
+            if writes {
+                updates.push(*index);
+            }
+        }
+        let update = match updates.as_slice() {
+            [update] => *update,
+            _ => return None
+        };
+        let (is_add, amount) = Self::get_counter_step(&self.instructions[update], counter)?;
+        let step = |value: u8| if is_add { value.wrapping_add(amount) } else { value.wrapping_sub(amount) };
+        if !self.is_on_every_iteration(l, exit) || !self.is_on_every_iteration(l, update) {
+            return None;
+        }
+        let update_first = update == l.header || (exit != l.header && !self.reaches(l, l.header, exit, update));
+
+        let mut value = self.get_entry_value(entry, l, counter)?;
+        for iterations in 0..MAX_ITERATIONS {
+            if update_first {
+                value = step(value);
+            }
+            let (arg1, arg2) = if counter_first { (value, limit) } else { (limit, value) };
+            if instruction.instruction.compare(arg1, arg2) != Some(stays_when_taken) {
+                return Some(iterations);
+            }
+            if !update_first {
+                value = step(value);
+            }
+        }
+        None
+    }
+
+        // ADD or SUB of a constant into the counter itself, as whether it adds and the constant
+    fn get_counter_step(instruction: &FlowInstruction, counter: u8) -> Option<(bool, u8)> {
+        let block = &instruction.block;
+        if instruction.get_subsystem() != RailSubSystem::Alu || block.get_result() != counter {
+            return None;
+        }
+        match (instruction.instruction, block.is_arg1_immediate(), block.is_arg2_immediate()) {
+            (RailInstruction::Add, false, true) if block.arg1 == counter => Some((true, block.arg2)),
+            (RailInstruction::Add, true, false) if block.arg2 == counter => Some((true, block.arg1)),
+            (RailInstruction::Sub, false, true) if block.arg1 == counter => Some((false, block.arg2)),
+            _ => None
+        }
+    }
+
+        // whether every path from the header around the loop passes through the node
+    fn is_on_every_iteration(&self, l: &Loop, node: usize) -> bool {
+        node == l.header || !l.latches.iter().any(|latch| *latch != node && self.reaches(l, l.header, *latch, node))
+    }
+
+        // whether target can be reached from start inside the loop body without passing avoid
+    fn reaches(&self, l: &Loop, start: usize, target: usize, avoid: usize) -> bool {
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut pending = vec![start];
+        while let Some(index) = pending.pop() {
+            if index == target {
+                return true;
+            }
+            if !seen.insert(index) {
+                continue;
+            }
+            for successor in self.get_successors(index).into_iter().flatten() {
+                if successor != avoid && successor != l.header && l.body.contains(&successor) {
+                    pending.push(successor);
+                }
+            }
+        }
+        false
+    }
+
+        // the counter value on entering the loop, followed back along the single path leading to it.
+        // Registers start at 0 when the program does.
+    fn get_entry_value(&self, entry: u8, l: &Loop, counter: u8) -> Option<u8> {
+        let nodes = &self.routines[&entry].nodes;
+        let predecessors = |index: usize| -> Vec<usize> {
+            nodes.iter().copied().filter(|node| self.get_successors(*node).contains(&Some(index))).collect()
+        };
+        let start = self.get_index(Some(entry))?;
+
+        let mut index = l.header;
+        let mut seen: HashSet<usize> = HashSet::new();
+        loop {
+            let incoming: Vec<usize> = predecessors(index).into_iter()
+                .filter(|node| index != l.header || !l.body.contains(node))
+                .collect();
+            if index == start {
+                return if entry == 0 && incoming.is_empty() { Some(0) } else { None };
+            }
+            let previous = match incoming.as_slice() {
+                [previous] => *previous,
+                _ => return None
+            };
+            if !seen.insert(previous) {
+                return None;
+            }
+            let instruction = &self.instructions[previous];
+            match instruction.kind {
+                FlowKind::Call(target) if self.writes[&target] & (1 << counter) != 0 => return None,
+                _ if instruction.get_written_register() == Some(counter) => return Self::get_constant(instruction),
+                _ => { }
+            }
+            index = previous;
+        }
+    }
+
+        // the value an ALU instruction writes when it doesn't depend on any register
+    fn get_constant(instruction: &FlowInstruction) -> Option<u8> {
+        let block = &instruction.block;
+        if instruction.get_subsystem() != RailSubSystem::Alu {
+            return None;
+        }
+        if !block.is_arg1_immediate() && !block.is_arg2_immediate() && block.arg1 == block.arg2 {
+            return match instruction.instruction {
+                RailInstruction::Sub | RailInstruction::Xor => Some(0),
+                _ => None
+            };
+        }
+        if !block.is_arg1_immediate() || !block.is_arg2_immediate() {
+            return None;
+        }
+        let (a, b) = (block.arg1, block.arg2);
+        match instruction.instruction {
+            RailInstruction::Add => Some(a.wrapping_add(b)),
+            RailInstruction::Sub => Some(a.wrapping_sub(b)),
+            RailInstruction::And => Some(a & b),
+            RailInstruction::Or => Some(a | b),
+            RailInstruction::Not => Some(!a),
+            RailInstruction::Xor => Some(a ^ b),
+            _ => None
+        }
+    }
+
+}
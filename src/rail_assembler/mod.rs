@@ -1,38 +1,128 @@
 
 use std::collections::HashMap;
+use crate::rail_assembler::rasm_conditional::{RasmCondition, RasmConditional};
 use crate::rail_assembler::rasm_dictionary::RasmDictionary;
 
+use crate::rail_assembler::rasm_error::RasmError;
 use crate::rail_assembler::rasm_line::{LineType, RasmLine, RasmTag};
+use crate::rail_assembler::rasm_proc::RasmProcs;
+use crate::rail_assembler::rasm_program::{RasmCodeLine, RasmConst, RasmExtern, RasmLabel, RasmProgram, RasmRelocation};
+use crate::rail_assembler::rasm_stdlib::RasmStdlib;
+use crate::rail_assembler::rasm_structured::{RasmLowered, RasmStructured};
+use crate::rail_system::rail_isa;
+use crate::rail_system::rail_isa::{IsaExtension, IsaOperand};
 
 mod rasm_line;
 pub mod rasm_dictionary;
+pub mod rasm_error;
+pub mod rasm_program;
+pub mod rasm_stdlib;
+mod rasm_structured;
+mod rasm_proc;
+mod rasm_conditional;
 
 
 const EMPTY: &[&str] = &[];
 const EMPTY_VEC: Vec<String> = Vec::new();
 const LABEL: &str = "LABEL";
 const CONST: &str = "CONST";
+const ORG: &str = "ORG";
+const BANK: &str = "BANK";
+const GLOBAL: &str = "GLOBAL";
+const EXTERN: &str = "EXTERN";
+const INCLUDE: &str = "INCLUDE";
+
+    // carried through INCLUDEs while parsing
+struct RasmParseState {
+    included: Vec<String>,      // modules already in the program, each is only included once
+    structured: RasmStructured,
+    procs: RasmProcs,
+    conditional: RasmConditional,
+    extensions: Vec<IsaExtension>       // enabled from outside, or in the source with # &rail-ext-NAME
+}
 
 pub trait RailAssemblerTrait {
     fn assemble(&self, code: &str) -> Vec<u8>;
+    fn assemble_program(&self, code: &str) -> RasmProgram;
+    fn try_assemble_program(&self, code: &str) -> Result<RasmProgram, RasmError>;
+    fn try_assemble_object(&self, code: &str) -> Result<RasmProgram, RasmError>;
 }
 
-pub struct RailAssembler { }
+pub struct RailAssembler {
+    defines: Vec<(String, String)>,     // constants given from outside the source, like -D on the command line
+    extensions: Vec<IsaExtension>
+}
 
 impl RailAssemblerTrait for RailAssembler {
     fn assemble(&self, code: &str) -> Vec<u8> {
-        let lines = self.parse_lines(code);
-        self.process_lines(&lines)
+        self.assemble_program(code).binary
+    }
+
+    fn assemble_program(&self, code: &str) -> RasmProgram {
+        match self.try_assemble_program(code) {
+            Ok(program) => program,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    fn try_assemble_program(&self, code: &str) -> Result<RasmProgram, RasmError> {
+        let mut program = self.try_assemble_object(code)?;
+        if let Some(ext) = program.externs.first() {
+            return Err(RasmError::new(ext.line_number, &format!("EXTERN {}", ext.name),
+                                      "External labels need the program to be assembled as an object and linked"));
+        }
+        Self::place_at_org(&mut program)?;
+        Ok(program)
+    }
+
+        // label addresses stay relative to the start of the code, and every byte holding
+        // one gets a relocation entry, so the linker can move it
+    fn try_assemble_object(&self, code: &str) -> Result<RasmProgram, RasmError> {
+        let mut state = RasmParseState {
+            included: Vec::new(), structured: RasmStructured::new(), procs: RasmProcs::new(),
+            conditional: RasmConditional::new(&self.defines),
+            extensions: self.extensions.clone()
+        };
+        let lines = self.parse_lines(code, &mut state)?;
+        for (open_line, message) in [(state.structured.get_open_line(), "Block is never closed"),
+                                     (state.procs.get_open_line(), "PROC has no ENDP"),
+                                     (state.conditional.get_open_line(), "Conditional block has no ENDIF")] {
+            if let Some(line_number) = open_line {
+                let line = code.split('\n').nth(line_number as usize - 1).unwrap_or("");
+                return Err(RasmError::new(line_number, line, message));
+            }
+        }
+        self.process_lines(&lines, state)
+    }
+}
+
+impl Default for RailAssembler {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl RailAssembler {
 
     pub fn new() -> Self {
-        Self {}
+        Self { defines: Vec::new(), extensions: Vec::new() }
+    }
+
+        // allows the instructions of an ISA extension, as # &rail-ext-NAME does in the source
+    pub fn enable_extension(&mut self, extension: IsaExtension) {
+        if !self.extensions.contains(&extension) {
+            self.extensions.push(extension);
+        }
     }
 
-    fn parse_lines(&self, text: &str) -> Vec<RasmLine> {
+        // defines a constant for IFDEF/IF, which overrides a CONST of the same name in the source
+    pub fn set_define(&mut self, name: &str, value: &str) {
+        let name = name.to_uppercase();
+        self.defines.retain(|(defined, _)| *defined != name);
+        self.defines.push((name, value.to_uppercase()));
+    }
+
+    fn parse_lines(&self, text: &str, state: &mut RasmParseState) -> Result<Vec<RasmLine>, RasmError> {
         let lines: Vec<&str> = text.split('\n').collect();
         let mut result: Vec<RasmLine> = Vec::new();
         let mut line_number: u32 = 0;
@@ -46,17 +136,31 @@ impl RailAssembler {
                 if comment.contains("&rail-asm-v2") {
                     is_v2 = true;
                 }
+                if let Some((_, name)) = comment.split_once("&rail-ext-") {
+                    let name = name.split_whitespace().next().unwrap_or("");
+                    match IsaExtension::from_name(name) {
+                        Some(extension) if !state.extensions.contains(&extension) => state.extensions.push(extension),
+                        Some(_) => { }
+                        None => return Err(RasmError::new(line_number, line, &format!("Unknown ISA extension {}", name)))
+                    }
+                }
                 result.push(RasmLine::new(comment.to_string(), RasmTag::None,
                                           LineType::Empty, EMPTY, EMPTY_VEC,
                                           line_number, line));
             }
             else {
+                let condition = state.conditional.process(&Self::get_parts(&code), state.structured.get_depth(), line_number)
+                    .map_err(|e| RasmError::new(line_number, line, &e))?;
+                if !matches!(condition, RasmCondition::Assemble) {
+                    continue;
+                }
                     // check for tags
                 if code.starts_with(LABEL) {
                     let parts = Self::get_parts(&code);
                     if parts.len() < 2 {
-                        println!("Label has no value");
+                        return Err(RasmError::new(line_number, line, "Label has no value"));
                     }
+                    state.procs.clear_ret();
                     result.push(RasmLine::new(comment.to_string(), RasmTag::Label,
                                               LineType::Tag, &parts[1..2], EMPTY_VEC,
                                               line_number, line));
@@ -64,14 +168,79 @@ impl RailAssembler {
                 else if code.starts_with(CONST) {
                     let parts = Self::get_parts(&code);
                     if parts.len() < 3 {
-                        println!("Incomplete Const");
+                        return Err(RasmError::new(line_number, line, "Incomplete Const"));
+                    }
+                    if self.defines.iter().any(|(name, _)| name == parts[1]) {
+                        continue;
                     }
+                    state.conditional.add_const(parts[1], parts[2]);
                     result.push(RasmLine::new(comment.to_string(), RasmTag::Const,
                                               LineType::Tag, &parts[1..3], EMPTY_VEC,
                                               line_number, line));
                 }
-                else {
+                else if Self::get_parts(&code)[0] == INCLUDE {
                     let parts = Self::get_parts(&code);
+                    if parts.len() < 2 {
+                        return Err(RasmError::new(line_number, line, "INCLUDE has no module name"));
+                    }
+                    let source = match RasmStdlib::get_source(parts[1]) {
+                        Some(source) => source,
+                        None => return Err(RasmError::new(line_number, line,
+                            &format!("Unknown module {}, available are {}", parts[1], RasmStdlib::MODULES.join(", "))))
+                    };
+                    if !state.included.iter().any(|name| name == parts[1]) {
+                        state.included.push(parts[1].to_string());
+                        let depth = (state.structured.get_depth(), state.conditional.get_depth());
+                        let open_proc = state.procs.get_open_line();
+                        let module_lines = self.parse_lines(source, state)
+                            .map_err(|e| RasmError::new(line_number, line,
+                                &format!("{} line {}: {}", parts[1], e.line_number, e.message)))?;
+                        if (state.structured.get_depth(), state.conditional.get_depth()) != depth
+                            || state.procs.get_open_line() != open_proc {
+                            return Err(RasmError::new(line_number, line,
+                                                      &format!("{} leaves a block open", parts[1])));
+                        }
+                            // errors and warnings in the module point at the INCLUDE line
+                        for mut module_line in module_lines {
+                            module_line.line_number = line_number;
+                            module_line.is_included = true;
+                            result.push(module_line);
+                        }
+                    }
+                }
+                else if RasmStructured::is_keyword(Self::get_parts(&code)[0]) {
+                    let parts = state.procs.substitute(Self::get_parts(&code));
+                    let conditional = &state.conditional;
+                    let lowered = state.structured.lower(&parts, line_number, &|name| conditional.is_constant(name))
+                        .map_err(|e| RasmError::new(line_number, line, &e))?;
+                    state.procs.clear_ret();
+                    Self::push_lowered(&mut result, lowered, &comment, line_number, line);
+                }
+                else if RasmProcs::is_keyword(Self::get_parts(&code)[0]) {
+                    let lowered = state.procs.lower(&Self::get_parts(&code), line_number)
+                        .map_err(|e| RasmError::new(line_number, line, &e))?;
+                    Self::push_lowered(&mut result, lowered, &comment, line_number, line);
+                }
+                else if let Some(lowered) = state.procs.lower_ret(Self::get_parts(&code)[0]) {
+                    Self::push_lowered(&mut result, lowered, &comment, line_number, line);
+                }
+                else if matches!(Self::get_parts(&code)[0], ORG | BANK | GLOBAL | EXTERN) {
+                    let parts = Self::get_parts(&code);
+                    let tag_type = match parts[0] {
+                        ORG => RasmTag::Org,
+                        BANK => RasmTag::Bank,
+                        GLOBAL => RasmTag::Global,
+                        _ => RasmTag::Extern
+                    };
+                    if parts.len() < 2 {
+                        return Err(RasmError::new(line_number, line, &format!("{} has no value", parts[0])));
+                    }
+                    result.push(RasmLine::new(comment.to_string(), tag_type,
+                                              LineType::Tag, &parts[1..2], EMPTY_VEC,
+                                              line_number, line));
+                }
+                else {
+                    let parts = state.procs.substitute(Self::get_parts(&code));
                     let (parts, add_parts) = if is_v2 {
                         Self::preprocess_parts(parts)
                     }
@@ -83,21 +252,43 @@ impl RailAssembler {
                                               LineType::Code, EMPTY, parts,
                                               line_number, line));
                     }
-                    match add_parts {
-                        Some(ext_parts) => {
-                            for ext_part in ext_parts {
-                                result.push(RasmLine::new(comment.to_string(), RasmTag::None,
-                                                      LineType::Code, EMPTY, ext_part,
-                                                      line_number, line));
-                            }
-                        },
-                        None => { },
+                    if let Some(ext_parts) = add_parts {
+                        for ext_part in ext_parts {
+                            result.push(RasmLine::new(comment.to_string(), RasmTag::None,
+                                                  LineType::Code, EMPTY, ext_part,
+                                                  line_number, line));
+                        }
                     }
                 }
             }
         }
 
-        result
+        Ok(result)
+    }
+
+    fn push_lowered(result: &mut Vec<RasmLine>, lowered: Vec<RasmLowered>, comment: &str,
+                    line_number: u32, line: &str) {
+        for part in lowered {
+            let (tag_type, tags, code_parts) = match part {
+                RasmLowered::Label(name) => (RasmTag::Label, vec![name], Vec::new()),
+                RasmLowered::Proc(name) => (RasmTag::Proc, vec![name], Vec::new()),
+                RasmLowered::EndProc(name) => (RasmTag::EndProc, vec![name], Vec::new()),
+                RasmLowered::Code(code_parts) if code_parts[0].starts_with("!ST") => {
+                        // PROC register saves reuse the stack expansions
+                    let (_, expansion) = Self::preprocess_expand(code_parts);
+                    for ext_part in expansion.unwrap_or_default() {
+                        result.push(RasmLine::new(comment.to_string(), RasmTag::None, LineType::Code,
+                                                  EMPTY, ext_part, line_number, line));
+                    }
+                    continue;
+                }
+                RasmLowered::Code(code_parts) => (RasmTag::None, Vec::new(), code_parts)
+            };
+            let line_type = if code_parts.is_empty() { LineType::Tag } else { LineType::Code };
+            let tags: Vec<&str> = tags.iter().map(|tag| tag.as_str()).collect();
+            result.push(RasmLine::new(comment.to_string(), tag_type, line_type, &tags, code_parts,
+                                      line_number, line));
+        }
     }
 
     fn get_parts(code: &str) -> Vec<&str> {
@@ -118,51 +309,68 @@ impl RailAssembler {
     }
 
     fn preprocess_parts(parts: Vec<&str>) -> (Vec<String>, Option<Vec<Vec<String>>>) {
-        if parts.len() == 0 {
+        if parts.is_empty() {
             return (Vec::new(), None);
         }
+        let parts = Self::fill_unused_operands(parts);
 
         let mut res = Vec::new();
         let mut op = String::from(parts[0]);
 
-        match parts.get(1) {
-            Some(p) => {
-                let part = if p.starts_with('*') {
-                    op.push_str("+IM1");
-                    p.replacen('*', "", 1)
-                }
-                else {
-                    p.to_string()
-                };
-                res.push(part);
-            },
-            None => { },
-        }
-
-        match parts.get(2) {
-            Some(p) => {
-                let part = if p.starts_with('*') {
-                    op.push_str("+IM2");
-                    p.replacen('*', "", 1)
-                }
-                else {
-                    p.to_string()
-                };
-                res.push(part);
-            },
-            None => { },
+        if let Some(p) = parts.get(1) {
+            let part = if p.starts_with('*') {
+                op.push_str("+IM1");
+                p.replacen('*', "", 1)
+            }
+            else {
+                p.to_string()
+            };
+            res.push(part);
+        }
+
+        if let Some(p) = parts.get(2) {
+            let part = if p.starts_with('*') {
+                op.push_str("+IM2");
+                p.replacen('*', "", 1)
+            }
+            else {
+                p.to_string()
+            };
+            res.push(part);
         }
 
-        for p in 3..parts.len() {
-            res.push(parts[p].to_string());
+        for p in parts.iter().skip(3) {
+            res.push(p.to_string());
         }
 
         res.insert(0, op);
         Self::preprocess_expand(res)
     }
 
+        // an instruction written with only the operands it uses, like IF_C label, gets 0 for the others
+    fn fill_unused_operands(parts: Vec<&str>) -> Vec<&str> {
+        let mnemonic = parts[0].split('+').next().unwrap_or("");
+        let operands = match rail_isa::get_by_mnemonic(mnemonic) {
+            Some(entry) => entry.operands,
+            None => return parts
+        };
+        let used = operands.iter().filter(|operand| **operand != IsaOperand::Unused).count();
+        if used == 3 || parts.len() != used + 1 {
+            return parts;
+        }
+        let mut given = parts[1..].iter();
+        let mut res = vec![parts[0]];
+        for operand in operands {
+            res.push(match operand {
+                IsaOperand::Unused => "0",
+                _ => given.next().unwrap_or(&"0")
+            });
+        }
+        res
+    }
+
     fn preprocess_expand(mut parts: Vec<String>) -> (Vec<String>, Option<Vec<Vec<String>>>) {
-        let op = parts.get(0).unwrap();
+        let op = parts.first().unwrap();
         let mut opt = None;
             // TODO: should be a better way to do this, without doing it manually. But can wait.
         if op.contains("JMP") {
@@ -221,8 +429,8 @@ impl RailAssembler {
         }
         else if op.starts_with("!ST<") {
             let mut expansion = Vec::new();
-            for i in 1..parts.len() {
-                expansion.push(vec![String::from("S_PUSH+IM1"), parts[i].to_string(),
+            for part in parts.iter().skip(1) {
+                expansion.push(vec![String::from("S_PUSH+IM1"), part.to_string(),
                                     String::from("0"), String::from("0")]);
             }
             parts.clear();
@@ -241,12 +449,21 @@ impl RailAssembler {
         (parts, opt)
     }
 
-    fn process_lines(&self, lines: &Vec<RasmLine>) -> Vec<u8> {
-        let mut const_map: HashMap<&str, &str> = HashMap::new();
+    fn process_lines(&self, lines: &Vec<RasmLine>, mut state: RasmParseState) -> Result<RasmProgram, RasmError> {
+        let mut procs = state.procs.take_procs();
+        let mut const_map: HashMap<&str, &str> = self.defines.iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
         let mut label_map: HashMap<&str, u8> = HashMap::new();
+        let mut extern_map: HashMap<&str, u32> = HashMap::new();
+        let mut references: HashMap<String, u32> = state.conditional.take_references();
+        let mut relocations: Vec<RasmRelocation> = Vec::new();
         let mut result: Vec<u8> = Vec::new();
         let mut code_lines: Vec<&RasmLine> = Vec::new();
-        let mut code_line = 0;
+        let mut tag_lines: Vec<&RasmLine> = Vec::new();
+        let mut code_line: u32 = 0;
+        let mut org: Option<u8> = None;
+        let mut bank: Option<u8> = None;
 
         for line in lines {
             match line.line_type {
@@ -257,12 +474,46 @@ impl RailAssembler {
                             const_map.insert(&line.tags[0], &line.tags[1]);
                         }
                         RasmTag::Label => {
-                            if label_map.insert(&line.tags[0], code_line * 4).is_some() {
-                                Self::do_panic(line, &format!("Label {} already exists", line.tags[0]));
+                            if code_line >= 64 {
+                                return Err(Self::line_error(line, "Label points past the 64 instruction program space"));
+                            }
+                            if label_map.insert(&line.tags[0], (code_line * 4) as u8).is_some() {
+                                return Err(Self::line_error(line, &format!("Label {} already exists", line.tags[0])));
                             }
                         }
-                        RasmTag::None => {} // noop
+                        RasmTag::Org => {
+                            if org.is_some() || code_line > 0 {
+                                return Err(Self::line_error(line, "ORG must come once, before any code"));
+                            }
+                            match Self::decode_num(&line.tags[0]) {
+                                Ok(value) if value % 4 == 0 => org = Some(value),
+                                Ok(_) => return Err(Self::line_error(line, "ORG must be a multiple of 4")),
+                                Err(e) => return Err(Self::line_error(line, &e))
+                            }
+                        }
+                        RasmTag::Bank => {
+                            if !state.extensions.contains(&IsaExtension::Banks) {
+                                return Err(Self::line_error(line, "BANK needs the banks extension, enable it with # &rail-ext-banks"));
+                            }
+                            if bank.is_some() || code_line > 0 {
+                                return Err(Self::line_error(line, "BANK must come once, before any code"));
+                            }
+                            bank = Some(Self::decode_num(&line.tags[0]).map_err(|e| Self::line_error(line, &e))?);
+                        }
+                        RasmTag::Extern => {
+                            extern_map.insert(&line.tags[0], line.line_number);
+                        }
+                        RasmTag::Proc | RasmTag::EndProc => {
+                            if let Some(proc) = procs.iter_mut().find(|proc| proc.name == line.tags[0]) {
+                                match line.tag_type {
+                                    RasmTag::Proc => proc.start = (code_line * 4) as u8,
+                                    _ => proc.end = (code_line * 4) as u16
+                                }
+                            }
+                        }
+                        RasmTag::Global | RasmTag::None => {} // checked once all labels are known
                     }
+                    tag_lines.push(line);
                 }
                 LineType::Code => {
                     code_lines.push(line);
@@ -270,21 +521,111 @@ impl RailAssembler {
                 }
             }
         }
-        for line in code_lines {
+        for line in &code_lines {
             for code in &line.code_parts {
-                match self.process_code(code, &const_map, &label_map) {
-                    Ok(res) => result.push(res),
-                    Err(e) => Self::do_panic(line, &e.to_string()),
+                let offset = result.len();
+                match self.process_code(code, &const_map, &label_map, &extern_map, &mut references, &state.extensions) {
+                    Ok((res, symbols)) => {
+                        result.push(res);
+                        relocations.extend(symbols.into_iter()
+                            .map(|relocation| RasmRelocation { offset, ..relocation }));
+                    }
+                    Err(e) => return Err(Self::line_error(line, &e)),
                 }
             }
         }
 
-        result
+        let mut labels = Vec::new();
+        let mut consts = Vec::new();
+        let mut externs = Vec::new();
+        let mut globals = Vec::new();
+        for line in tag_lines {
+            let name = &line.tags[0];
+            let refs = *references.get(name).unwrap_or(&0);
+            match line.tag_type {
+                RasmTag::Label => labels.push(RasmLabel {
+                    name: name.to_string(),
+                    address: label_map[name.as_str()],
+                    line_number: line.line_number,
+                    references: refs,
+                    is_global: false,
+                    is_included: line.is_included
+                }),
+                RasmTag::Const => consts.push(RasmConst {
+                    name: name.to_string(),
+                    value: line.tags[1].to_string(),
+                    line_number: line.line_number,
+                    references: refs
+                }),
+                RasmTag::Extern => {
+                    if label_map.contains_key(name.as_str()) {
+                        return Err(Self::line_error(line, &format!("{} is both a label and EXTERN", name)));
+                    }
+                    externs.push(RasmExtern { name: name.to_string(), line_number: line.line_number });
+                }
+                RasmTag::Global => globals.push(line),
+                RasmTag::Org | RasmTag::Bank | RasmTag::Proc | RasmTag::EndProc | RasmTag::None => {} // noop
+            }
+        }
+        for line in globals {
+            match labels.iter_mut().find(|label| label.name == line.tags[0]) {
+                Some(label) => label.is_global = true,
+                None => return Err(Self::line_error(line, &format!("GLOBAL {} is not a label", line.tags[0])))
+            }
+        }
+
+        Ok(RasmProgram {
+            binary: result,
+            org,
+            bank,
+            labels,
+            consts,
+            externs,
+            relocations,
+            procs,
+            code_lines: code_lines.iter()
+                .map(|line| RasmCodeLine {
+                    line_number: line.line_number,
+                    original_line: line.original_line.trim().to_string(),
+                    is_included: line.is_included
+                })
+                .collect()
+        })
+    }
+
+        // turns the relocatable result into the final image: code moves to ORG of its BANK, with
+        // zeroes before it
+    fn place_at_org(program: &mut RasmProgram) -> Result<(), RasmError> {
+        let org = program.org.unwrap_or(0);
+        let bank = program.bank.unwrap_or(0);
+        let relocations = std::mem::take(&mut program.relocations);
+        if org == 0 && bank == 0 {
+            return Ok(());
+        }
+        if org as usize + program.binary.len() > 256 {
+            let line = program.code_lines.last().map(|line| (line.line_number, line.original_line.as_str()))
+                .unwrap_or((0, ""));
+            return Err(RasmError::new(line.0, line.1, "Code placed at ORG does not fit in the program space"));
+        }
+        for relocation in relocations {
+            let byte = &mut program.binary[relocation.offset];
+            *byte = byte.wrapping_add(if relocation.is_bank { bank } else { org });
+        }
+        for label in &mut program.labels {
+            label.address = label.address.wrapping_add(org);
+        }
+        for proc in &mut program.procs {
+            proc.start = proc.start.wrapping_add(org);
+            proc.end += org as u16;
+        }
+        let mut binary = vec![0u8; bank as usize * 256 + org as usize];
+        binary.append(&mut program.binary);
+        program.binary = binary;
+        Ok(())
     }
 
-    fn do_panic(line: &RasmLine, error_msg: &str) {
-        panic!("{}", format!("\n\x00\x1B[33m Fatal error on line {}: {}\n Cause: {} \x00\x1B[0m\n",
-                             line.line_number, line.original_line, error_msg));
+    fn line_error(line: &RasmLine, error_msg: &str) -> RasmError {
+        RasmError::new(line.line_number, &line.original_line, error_msg)
     }
 
     fn extract_comment(&self, line: &str) -> (String, String) {
@@ -306,23 +647,64 @@ impl RailAssembler {
         (code.to_uppercase(), comment)
     }
 
-    fn process_code(&self, code: &str, const_map: &HashMap<&str, &str>, label_map: &HashMap<&str, u8>) -> Result<u8, String> {
+        // returns the byte, and a relocation for each label it was built from, still without an offset
+    fn process_code(&self, code: &str, const_map: &HashMap<&str, &str>, label_map: &HashMap<&str, u8>,
+                    extern_map: &HashMap<&str, u32>, references: &mut HashMap<String, u32>,
+                    extensions: &[IsaExtension]) -> Result<(u8, Vec<RasmRelocation>), String> {
                 // TODO add more arithmetic support
         let parts: Vec<&str> = code.split('+') .collect();
-        let mut result = 0;
+        let mut result: u8 = 0;
+        let mut flags = 0;      // immediate flags set so far, by the mnemonic or by IM1 and IM2
+        let mut symbols = Vec::new();
         for part in parts {
             let mut real_code: &str = part;
+            let mut resolved = Vec::new();      // names seen on the way, a repeat is a cycle
             while const_map.contains_key(real_code) {
+                if resolved.contains(&real_code) {
+                    return Err(format!("CONST {} is defined in terms of itself", real_code));
+                }
+                resolved.push(real_code);
+                *references.entry(real_code.to_string()).or_insert(0) += 1;
                 real_code = const_map[real_code];
             }
             let num_code: u8 = if label_map.contains_key(real_code) {
+                *references.entry(real_code.to_string()).or_insert(0) += 1;
+                symbols.push(RasmRelocation { offset: 0, symbol: None, is_bank: false });
                 label_map[real_code]
             }
+            else if extern_map.contains_key(real_code) {
+                symbols.push(RasmRelocation { offset: 0, symbol: Some(real_code.to_string()), is_bank: false });
+                0   // filled in by the linker
+            }
+            else if let Some(name) = real_code.strip_prefix('^') {
+                if label_map.contains_key(name) {
+                    *references.entry(name.to_string()).or_insert(0) += 1;
+                    symbols.push(RasmRelocation { offset: 0, symbol: None, is_bank: true });
+                }
+                else if extern_map.contains_key(name) {
+                    symbols.push(RasmRelocation { offset: 0, symbol: Some(name.to_string()), is_bank: true });
+                }
+                else {
+                    return Err(format!("{} is not a label", name));
+                }
+                0   // the bank is only known once the code is placed
+            }
             else {
                 match RasmDictionary::translate(real_code) {
-                    Ok(code) => code,
+                    Ok(code) => {
+                        let extension = rail_isa::get_by_mnemonic(real_code).and_then(|entry| entry.extension);
+                        if let Some(extension) = extension.filter(|extension| !extensions.contains(extension)) {
+                            return Err(format!("{} is part of the {} extension, enable it with # &rail-ext-{}",
+                                               real_code, extension.get_name(), extension.get_name()));
+                        }
+                        if code & flags != 0 {
+                            return Err(format!("{} sets an immediate flag that is already set", real_code));
+                        }
+                        flags |= code & (rail_isa::IM1 | rail_isa::IM2);
+                        code
+                    }
                     Err(_) => {
-                        match self.decode_num(real_code) {
+                        match Self::decode_num(real_code) {
                             Ok(num) => num,
                             Err(e) => {
                                 return Err(format!("Parsing error: {}", e));
@@ -332,13 +714,16 @@ impl RailAssembler {
                 }
             };
 
-            result += num_code
+            result = match result.checked_add(num_code) {
+                Some(sum) => sum,
+                None => return Err(format!("{} does not fit in a byte", code))
+            };
         }
 
-        Ok(result)
+        Ok((result, symbols))
     }
 
-    fn decode_num(&self, str: &str) -> Result<u8, String> {
+    pub fn decode_num(str: &str) -> Result<u8, String> {
         match
             if let Some(value) = str.strip_prefix("0X") {
                 u8::from_str_radix(value, 16)
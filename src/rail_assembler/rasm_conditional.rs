@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::rail_assembler::RailAssembler;
+
+const IF: &str = "IF";
+const IFDEF: &str = "IFDEF";
+const IFNDEF: &str = "IFNDEF";
+const ELSE: &str = "ELSE";
+const ENDIF: &str = "ENDIF";
+
+pub enum RasmCondition {
+    Assemble,       // not a conditional line, assemble it as usual
+    Skip,           // in a branch that is left out
+    Consumed        // a conditional directive, nothing to assemble
+}
+
+struct RasmCondBlock {
+    is_active: bool,
+    is_parent_active: bool,
+    has_else: bool,
+    structured_depth: usize,    // structured blocks open at the IF, to tell our ELSE/ENDIF from theirs
+    line_number: u32
+}
+
+    // IFDEF/IFNDEF/IF expr ... ELSE ... ENDIF over CONST values and command line defines.
+    // An IF whose operands are not all constants is a structured IF, which is left to RasmStructured.
+pub struct RasmConditional {
+    consts: HashMap<String, String>,
+    blocks: Vec<RasmCondBlock>,
+    references: HashMap<String, u32>
+}
+
+impl RasmConditional {
+
+    pub fn new(defines: &[(String, String)]) -> Self {
+        Self {
+            consts: defines.iter().cloned().collect(),
+            blocks: Vec::new(),
+            references: HashMap::new()
+        }
+    }
+
+    pub fn add_const(&mut self, name: &str, value: &str) {
+        self.consts.insert(name.to_string(), value.to_string());
+    }
+
+        // a CONST or define so far whose value is a number, which structured blocks use as an immediate
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.consts.contains_key(name) && self.get_value(name).is_some()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.blocks.last().map(|block| block.is_active).unwrap_or(true)
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn get_open_line(&self) -> Option<u32> {
+        self.blocks.last().map(|block| block.line_number)
+    }
+
+        // constants used by the directives, so they don't count as unused
+    pub fn take_references(&mut self) -> HashMap<String, u32> {
+        std::mem::take(&mut self.references)
+    }
+
+    pub fn process(&mut self, parts: &[&str], structured_depth: usize, line_number: u32) -> Result<RasmCondition, String> {
+        let is_ours = self.blocks.last().is_some_and(|block| block.structured_depth == structured_depth);
+        if !self.is_active() {
+            match parts[0] {
+                    // any nested IF, structured or not, needs its own ENDIF
+                IF | IFDEF | IFNDEF => self.open(false, false, structured_depth, line_number),
+                ELSE => self.flip()?,
+                ENDIF => { self.blocks.pop(); }
+                _ => return Ok(RasmCondition::Skip)
+            }
+            return Ok(RasmCondition::Consumed);
+        }
+
+        match parts[0] {
+            IFDEF | IFNDEF => {
+                let name = parts.get(1).ok_or(format!("{} has no name", parts[0]))?;
+                self.add_reference(name);
+                let is_defined = self.consts.contains_key(*name);
+                self.open(is_defined == (parts[0] == IFDEF), true, structured_depth, line_number);
+            }
+            IF => match self.evaluate(&parts[1..])? {
+                Some(value) => self.open(value, true, structured_depth, line_number),
+                None => return Ok(RasmCondition::Assemble)
+            },
+            ELSE if is_ours => self.flip()?,
+            ENDIF if is_ours => { self.blocks.pop(); }
+            _ => return Ok(RasmCondition::Assemble)
+        }
+        Ok(RasmCondition::Consumed)
+    }
+
+    fn open(&mut self, is_active: bool, is_parent_active: bool, structured_depth: usize, line_number: u32) {
+        self.blocks.push(RasmCondBlock { is_active, is_parent_active, has_else: false, structured_depth, line_number });
+    }
+
+    fn flip(&mut self) -> Result<(), String> {
+        let block = self.blocks.last_mut().ok_or("ELSE without a matching IF")?;
+        if block.has_else {
+            return Err("ELSE without a matching IF".to_string());
+        }
+        block.has_else = true;
+        block.is_active = block.is_parent_active && !block.is_active;
+        Ok(())
+    }
+
+    fn add_reference(&mut self, name: &str) {
+        if self.consts.contains_key(name) {
+            *self.references.entry(name.to_string()).or_insert(0) += 1;
+        }
+    }
+
+        // None when an operand is not a constant, which makes it a structured IF
+    fn evaluate(&mut self, expression: &[&str]) -> Result<Option<bool>, String> {
+        let values: Vec<Option<u8>> = expression.iter().step_by(2).map(|operand| self.get_value(operand)).collect();
+        let res = match (expression.len(), values.as_slice()) {
+            (1, [Some(value)]) => *value != 0,
+            (1, [None]) => return Err(format!("{} is not defined", expression[0])),
+            (3, [Some(a), Some(b)]) => match expression[1] {
+                "==" => a == b,
+                "!=" => a != b,
+                "<" => a < b,
+                "<=" => a <= b,
+                ">" => a > b,
+                ">=" => a >= b,
+                op => return Err(format!("Unknown comparison {}, expected one of == != < <= > >=", op))
+            },
+            _ => return Ok(None)
+        };
+        expression.iter().step_by(2).for_each(|operand| self.add_reference(operand));
+        Ok(Some(res))
+    }
+
+    fn get_value(&self, operand: &str) -> Option<u8> {
+        let mut value = operand.trim_start_matches('*');
+        for _ in 0..self.consts.len() {
+            match self.consts.get(value) {
+                Some(next) => value = next,
+                None => break
+            }
+        }
+        RailAssembler::decode_num(value).ok()
+    }
+
+}
@@ -1,9 +1,72 @@
+use crate::rail_system::rail_isa;
 
 pub struct RasmDictionary { }
 
 impl RasmDictionary {
 
+    pub const REGISTERS: [&'static str; 16] = ["R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7",
+                                               "BZ0", "LV0", "D0", "D1", "D2", "D3", "CNT", "IO"];
+
+    const ALIASES: [&'static str; 4] = ["MOV", "JMP", "IM1", "IM2"];
+
+        // every instruction of the ISA table, then the aliases and flags
+    pub const MNEMONICS: [&'static str; rail_isa::ISA.len() + 4] = Self::build_mnemonics();
+
+    const fn build_mnemonics() -> [&'static str; rail_isa::ISA.len() + 4] {
+        let mut res = [""; rail_isa::ISA.len() + 4];
+        let mut i = 0;
+        while i < rail_isa::ISA.len() {
+            res[i] = rail_isa::ISA[i].mnemonic;
+            i += 1;
+        }
+        while i < res.len() {
+            res[i] = Self::ALIASES[i - rail_isa::ISA.len()];
+            i += 1;
+        }
+        res
+    }
+
+    pub fn describe(token: &str) -> Option<&'static str> {
+        if let Some(entry) = rail_isa::get_by_mnemonic(token) {
+            return Some(entry.semantics);
+        }
+        let description = match token {
+                // REGISTERS //
+            "R0" | "R1" | "R2" | "R3" | "R4" | "R5" | "R6" | "R7" => "General purpose register.",
+            "BZ0" => "Buzzer output register.",
+            "LV0" => "Level output register, shown as 8 lights.",
+            "D0" | "D1" | "D2" | "D3" => "7 segment display register, shown in hex.",
+            "CNT" => "Program counter. Writing to it jumps.",
+            "IO" => "IO register. Written values are printed.",
+
+                // IMMEDIATE //
+            "IM1" => "Flag: A is an immediate value instead of a register.",
+            "IM2" => "Flag: B is an immediate value instead of a register.",
+
+                // ALIAS //
+            "MOV" => "Alias of ADD+IM2: RES = A + B.",
+            "JMP" => "Alias of IF_T: always jumps to RES.",
+
+                // STRUCTURED //
+            "IF" => "Structured: IF A op B ... ENDIF runs the body when the comparison holds. With only constants, it is conditional assembly.",
+            "ELSE" => "Structured: runs until ENDIF when the IF comparison does not hold.",
+            "ENDIF" => "Structured: ends an IF block.",
+            "WHILE" => "Structured: WHILE A op B ... ENDWHILE repeats the body while the comparison holds.",
+            "ENDWHILE" => "Structured: ends a WHILE loop.",
+            "FOR" => "Structured: FOR REG = START TO END ... ENDFOR counts REG up to END, inclusive.",
+            "ENDFOR" => "Structured: ends a FOR loop.",
+            "IFDEF" => "Conditional: assembles up to ELSE or ENDIF only if the CONST or -D name is defined.",
+            "IFNDEF" => "Conditional: assembles up to ELSE or ENDIF only if the CONST or -D name is not defined.",
+
+            _ => return None
+        };
+        Some(description)
+    }
+
     pub fn translate(token: &str) -> Result<u8, String> {
+        if let Some(entry) = rail_isa::get_by_mnemonic(token) {
+            return Ok(entry.opcode);
+        }
         let value = match token {
                 // REGISTERS //
             "R0" => 0x00,
@@ -24,50 +87,13 @@ impl RasmDictionary {
             "CNT" => 0x0E,
             "IO" => 0x0F,
 
-                // ALU //
-            "ADD" => 0x00,
-            "SUB" => 0x01,
-            "AND" => 0x02,
-            "OR" => 0x03,
-            "NOT" => 0x04,
-            "XOR" => 0x05,
-            "SHL" => 0x06,
-            "SHR" => 0x07,
-
-            "RAN_SS" => 0x0C,
-            "RAN_NEXT" => 0x0D,
-
-            "NOOP" => 0x0F,
-
-                // CU //
-            "IF_EQ" => 0x20,
-            "IF_N_EQ" => 0x21,
-            "IF_LT" => 0x22,
-            "IF_LTE" => 0x23,
-            "IF_MT" => 0x24,
-            "IF_MTE" => 0x25,
-            "IF_T" => 0x26,  // check exact meaning
-            "IF_F" => 0x27,  // check exact meaning
-
-                // RAM_STACK //
-            "RAM_R" => 0x10,
-            "RAM_W" => 0x11,
-
-            "S_POP" => 0x18,
-            "S_PUSH" => 0x19,
-            "RET" => 0x1A,
-            "CALL" => 0x9B,   //0x1B  IM included
-
                 // IMMEDIATE //
-            "IM2" => 0x40,
-            "IM1" => 0x80,
+            "IM2" => rail_isa::IM2,
+            "IM1" => rail_isa::IM1,
 
                 // ALIAS //
-            "MOV" => 0x40,   //0x00,
-            "JMP" => 0x26,
-
-                // V2 //
-            "HALT" => 0x0E,
+            "MOV" => rail_isa::IM2,     // ADD+IM2
+            "JMP" => 0x26,              // IF_T
 
             _ => 0xFF   // unnecessary error code
         };
@@ -0,0 +1,24 @@
+use std::fmt::{Display, Formatter};
+
+pub struct RasmError {
+    pub line_number: u32,
+    pub original_line: String,
+    pub message: String
+}
+
+impl RasmError {
+    pub fn new(line_number: u32, original_line: &str, message: &str) -> Self {
+        Self {
+            line_number,
+            original_line: original_line.to_string(),
+            message: message.to_string()
+        }
+    }
+}
+
+impl Display for RasmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\n\x00\x1B[33m Fatal error on line {}: {}\n Cause: {} \x00\x1B[0m\n",
+               self.line_number, self.original_line, self.message)
+    }
+}
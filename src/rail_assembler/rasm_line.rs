@@ -1,5 +1,6 @@
 
 pub struct RasmLine {
+    #[allow(dead_code)]
     pub comment: String,
     pub tag_type: RasmTag,
     pub line_type: LineType,
@@ -7,7 +8,8 @@ pub struct RasmLine {
     pub code_parts: Vec<String>,
 
     pub line_number: u32,
-    pub original_line: String
+    pub original_line: String,
+    pub is_included: bool       // comes from an INCLUDE module
 }
 
 impl RasmLine {
@@ -22,13 +24,14 @@ impl RasmLine {
             code_parts,
 
             line_number,
-            original_line: original_line.to_string()
+            original_line: original_line.to_string(),
+            is_included: false
         }
     }
 }
 
 pub enum RasmTag {
-    Const, Label, None
+    Const, Label, Org, Bank, Global, Extern, Proc, EndProc, None
 }
 
 pub enum LineType {
@@ -0,0 +1,149 @@
+use crate::rail_assembler::rasm_dictionary::RasmDictionary;
+use crate::rail_assembler::rasm_program::{RasmProc, RasmProcArg};
+use crate::rail_assembler::rasm_structured::RasmLowered;
+
+const PROC: &str = "PROC";
+const ENDP: &str = "ENDP";
+const USES: &str = "USES";
+const ARGS: &str = "ARGS";
+const RET: &str = "RET";
+const STACK: &str = "STACK:";
+
+    // PROC name [ARGS name=REG name=STACK:REG ...] [USES REG ...] ... ENDP
+    // saves the USES registers on entry and restores them before every RET
+pub struct RasmProcs {
+    current: Option<RasmProc>,
+    last_was_ret: bool,
+    procs: Vec<RasmProc>
+}
+
+impl Default for RasmProcs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RasmProcs {
+
+    pub fn new() -> Self {
+        Self { current: None, last_was_ret: false, procs: Vec::new() }
+    }
+
+    pub fn is_keyword(op: &str) -> bool {
+        op == PROC || op == ENDP
+    }
+
+    pub fn get_open_line(&self) -> Option<u32> {
+        self.current.as_ref().map(|proc| proc.line_number)
+    }
+
+    pub fn take_procs(&mut self) -> Vec<RasmProc> {
+        std::mem::take(&mut self.procs)
+    }
+
+    pub fn lower(&mut self, parts: &[&str], line_number: u32) -> Result<Vec<RasmLowered>, String> {
+        if parts[0] == ENDP {
+            let proc = self.current.take().ok_or("ENDP without a matching PROC")?;
+                // a body that does not end in RET returns at ENDP
+            let mut res = if self.last_was_ret { Vec::new() } else { Self::epilogue(&proc) };
+            res.push(RasmLowered::EndProc(proc.name.to_string()));
+            self.procs.push(proc);
+            return Ok(res);
+        }
+
+        if let Some(open) = &self.current {
+            return Err(format!("PROC inside PROC {}, which needs an ENDP first", open.name));
+        }
+        if parts.len() < 2 {
+            return Err("PROC has no name".to_string());
+        }
+        let proc = Self::parse_header(parts, line_number)?;
+
+        let mut res = vec![RasmLowered::Label(proc.name.to_string()), RasmLowered::Proc(proc.name.to_string())];
+            // stack arguments were pushed in order, so the last one is on top
+        for arg in proc.args.iter().rev().filter(|arg| arg.on_stack) {
+            res.push(RasmLowered::Code(vec!["S_POP".to_string(), "0".to_string(), "0".to_string(),
+                                            RasmDictionary::REGISTERS[arg.register as usize].to_string()]));
+        }
+        if !proc.uses.is_empty() {
+            res.push(RasmLowered::Code(Self::save_parts("!ST<", &proc)));
+        }
+        self.last_was_ret = false;
+        self.current = Some(proc);
+        Ok(res)
+    }
+
+        // inside a PROC, argument names stand for their registers
+    pub fn substitute<'a>(&self, parts: Vec<&'a str>) -> Vec<&'a str> {
+        let proc = match &self.current {
+            Some(proc) => proc,
+            None => return parts
+        };
+        parts.into_iter()
+            .map(|part| match proc.args.iter().find(|arg| arg.name == part) {
+                Some(arg) => RasmDictionary::REGISTERS[arg.register as usize],
+                None => part
+            })
+            .collect()
+    }
+
+        // inside a PROC, RET restores the saved registers first
+    pub fn lower_ret(&mut self, op: &str) -> Option<Vec<RasmLowered>> {
+        let proc = self.current.as_ref()?;
+        self.last_was_ret = op == RET;
+        if op == RET { Some(Self::epilogue(proc)) } else { None }
+    }
+
+        // a label or a structured block can be reached past the RET before it, so ENDP
+        // only leaves out the epilogue after an unconditional RET on the line right before it
+    pub fn clear_ret(&mut self) {
+        self.last_was_ret = false;
+    }
+
+    fn parse_header(parts: &[&str], line_number: u32) -> Result<RasmProc, String> {
+        let register = |name: &str| match RasmDictionary::translate(name) {
+            Ok(value) if RasmDictionary::REGISTERS.contains(&name) => Ok(value),
+            _ => Err(format!("{} is not a register", name))
+        };
+        let mut proc = RasmProc {
+            name: parts[1].to_string(), line_number, start: 0, end: 0, uses: Vec::new(), args: Vec::new()
+        };
+        let mut section = None;
+        for part in &parts[2..] {
+            match *part {
+                USES | ARGS => section = Some(*part),
+                _ if section == Some(USES) => proc.uses.push(register(part)?),
+                _ if section == Some(ARGS) => {
+                    let (name, target) = part.split_once('=')
+                        .ok_or(format!("Argument {} needs a register, like {}=R1", part, part))?;
+                    let (target, on_stack) = match target.strip_prefix(STACK) {
+                        Some(target) => (target, true),
+                        None => (target, false)
+                    };
+                    proc.args.push(RasmProcArg { name: name.to_string(), register: register(target)?, on_stack });
+                }
+                _ => return Err(format!("Expected ARGS or USES, found {}", part))
+            }
+        }
+        if let Some(arg) = proc.args.iter().find(|arg| arg.on_stack && proc.uses.contains(&arg.register)) {
+            return Err(format!("Stack argument {} is popped into a register the PROC saves", arg.name));
+        }
+        Ok(proc)
+    }
+
+    fn save_parts(op: &str, proc: &RasmProc) -> Vec<String> {
+        let mut parts = vec![op.to_string()];
+        parts.extend(proc.uses.iter().map(|reg| RasmDictionary::REGISTERS[*reg as usize].to_string()));
+        parts
+    }
+
+    fn epilogue(proc: &RasmProc) -> Vec<RasmLowered> {
+        let mut res = Vec::new();
+        if !proc.uses.is_empty() {
+            res.push(RasmLowered::Code(Self::save_parts("!ST>", proc)));
+        }
+        res.push(RasmLowered::Code(vec![RET.to_string(), "0".to_string(), "0".to_string(), "0".to_string()]));
+        res
+    }
+
+}
@@ -0,0 +1,84 @@
+#[derive(Clone)]
+pub struct RasmProgram {
+    pub binary: Vec<u8>,
+    pub org: Option<u8>,    // None places the code at 0, or anywhere when linking
+    pub bank: Option<u8>,   // the program bank of the code, None for bank 0 or the bank of the previous object
+    pub labels: Vec<RasmLabel>,
+    pub consts: Vec<RasmConst>,
+    pub externs: Vec<RasmExtern>,
+    pub relocations: Vec<RasmRelocation>,
+    pub procs: Vec<RasmProc>,
+    pub code_lines: Vec<RasmCodeLine>
+}
+
+#[derive(Clone)]
+pub struct RasmLabel {
+    pub name: String,
+    pub address: u8,
+    pub line_number: u32,
+    pub references: u32,
+    pub is_global: bool,
+    pub is_included: bool
+}
+
+#[derive(Clone)]
+pub struct RasmConst {
+    pub name: String,
+    pub value: String,
+    pub line_number: u32,
+    pub references: u32
+}
+
+#[derive(Clone)]
+pub struct RasmExtern {
+    pub name: String,
+    pub line_number: u32
+}
+
+    // a byte of the binary holding a label address, or with is_bank the bank of a label, written
+    // ^LABEL. Local labels are relative to the start of the code, and their bank is the code's own.
+#[derive(Clone)]
+pub struct RasmRelocation {
+    pub offset: usize,
+    pub symbol: Option<String>,     // None for local labels, the EXTERN name otherwise
+    pub is_bank: bool
+}
+
+    // a PROC and the registers it declares
+#[derive(Clone)]
+pub struct RasmProc {
+    pub name: String,
+    pub line_number: u32,
+    pub start: u8,
+    pub end: u16,           // address after its last instruction
+    pub uses: Vec<u8>,
+    pub args: Vec<RasmProcArg>
+}
+
+#[derive(Clone)]
+pub struct RasmProcArg {
+    pub name: String,
+    pub register: u8,
+    pub on_stack: bool      // pushed by the caller, popped into the register on entry
+}
+
+    // one per assembled instruction, in program order
+#[derive(Clone)]
+pub struct RasmCodeLine {
+    pub line_number: u32,
+    pub original_line: String,
+    pub is_included: bool
+}
+
+impl RasmProgram {
+
+    pub fn get_proc_at(&self, address: u8) -> Option<&RasmProc> {
+        self.procs.iter().find(|proc| proc.start <= address && (address as u16) < proc.end)
+    }
+
+    pub fn get_code_line(&self, address: u8) -> Option<&RasmCodeLine> {
+        let offset = address.checked_sub(self.org.unwrap_or(0))?;
+        self.code_lines.get(offset as usize / 4)
+    }
+
+}
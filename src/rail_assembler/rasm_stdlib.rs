@@ -0,0 +1,20 @@
+pub struct RasmStdlib { }
+
+impl RasmStdlib {
+
+        // include names, as written after INCLUDE
+    pub const MODULES: [&'static str; 5] = ["STD/MUL", "STD/DIVMOD", "STD/MATH16", "STD/BCD", "STD/MEM"];
+
+    pub fn get_source(name: &str) -> Option<&'static str> {
+        let source = match name {
+            "STD/MUL" => include_str!("../../stdlib/mul.asm"),
+            "STD/DIVMOD" => include_str!("../../stdlib/divmod.asm"),
+            "STD/MATH16" => include_str!("../../stdlib/math16.asm"),
+            "STD/BCD" => include_str!("../../stdlib/bcd.asm"),
+            "STD/MEM" => include_str!("../../stdlib/mem.asm"),
+            _ => return None
+        };
+        Some(source)
+    }
+
+}
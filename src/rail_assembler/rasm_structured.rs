@@ -0,0 +1,193 @@
+use crate::rail_assembler::rasm_dictionary::RasmDictionary;
+
+const IF: &str = "IF";
+const ELSE: &str = "ELSE";
+const ENDIF: &str = "ENDIF";
+const WHILE: &str = "WHILE";
+const ENDWHILE: &str = "ENDWHILE";
+const FOR: &str = "FOR";
+const ENDFOR: &str = "ENDFOR";
+const TO: &str = "TO";
+
+pub enum RasmLowered {
+    Label(String),
+    Code(Vec<String>),
+    Proc(String),       // start and end of a PROC, for its address range
+    EndProc(String)
+}
+
+enum RasmBlockKind {
+    If { has_else: bool },
+    While,
+    For { counter: String, end: String }
+}
+
+struct RasmBlock {
+    kind: RasmBlockKind,
+    id: u32,
+    line_number: u32
+}
+
+    // turns IF/ELSE/ENDIF, WHILE/ENDWHILE and FOR/ENDFOR into CU instructions and generated labels
+pub struct RasmStructured {
+    open_blocks: Vec<RasmBlock>,
+    next_id: u32
+}
+
+impl Default for RasmStructured {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RasmStructured {
+
+    pub fn new() -> Self {
+        Self { open_blocks: Vec::new(), next_id: 0 }
+    }
+
+    pub fn is_keyword(op: &str) -> bool {
+        matches!(op, IF | ELSE | ENDIF | WHILE | ENDWHILE | FOR | ENDFOR)
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.open_blocks.len()
+    }
+
+        // the line of the innermost block left open
+    pub fn get_open_line(&self) -> Option<u32> {
+        self.open_blocks.last().map(|block| block.line_number)
+    }
+
+    pub fn lower(&mut self, parts: &[&str], line_number: u32, is_constant: &dyn Fn(&str) -> bool) -> Result<Vec<RasmLowered>, String> {
+        let parts = Self::mark_constants(parts, is_constant)?;
+        let parts: Vec<&str> = parts.iter().map(|part| part.as_str()).collect();
+        match parts[0] {
+            IF => {
+                let id = self.open(RasmBlockKind::If { has_else: false }, line_number);
+                Ok(vec![Self::branch_unless(&parts[1..], &format!("__IF_{}_ELSE", id))?])
+            }
+            ELSE => {
+                let block = match self.open_blocks.last_mut() {
+                    Some(RasmBlock { kind: RasmBlockKind::If { has_else }, id, .. }) if !*has_else => {
+                        *has_else = true;
+                        *id
+                    }
+                    _ => return Err("ELSE without a matching IF".to_string())
+                };
+                Ok(vec![Self::jump(&format!("__IF_{}_END", block)),
+                        RasmLowered::Label(format!("__IF_{}_ELSE", block))])
+            }
+            ENDIF => match self.open_blocks.pop() {
+                Some(RasmBlock { kind: RasmBlockKind::If { has_else }, id, .. }) => {
+                    let name = if has_else { "END" } else { "ELSE" };
+                    Ok(vec![RasmLowered::Label(format!("__IF_{}_{}", id, name))])
+                }
+                _ => Err("ENDIF without a matching IF".to_string())
+            },
+            WHILE => {
+                let id = self.open(RasmBlockKind::While, line_number);
+                Ok(vec![RasmLowered::Label(format!("__WHILE_{}", id)),
+                        Self::branch_unless(&parts[1..], &format!("__WHILE_{}_END", id))?])
+            }
+            ENDWHILE => match self.open_blocks.pop() {
+                Some(RasmBlock { kind: RasmBlockKind::While, id, .. }) =>
+                    Ok(vec![Self::jump(&format!("__WHILE_{}", id)),
+                            RasmLowered::Label(format!("__WHILE_{}_END", id))]),
+                _ => Err("ENDWHILE without a matching WHILE".to_string())
+            },
+            FOR => {
+                    // FOR counter = start TO end
+                if parts.len() != 6 || parts[2] != "=" || parts[4] != TO || Self::is_immediate(parts[1]) {
+                    return Err("Expected FOR <register> = <start> TO <end>".to_string());
+                }
+                let (counter, start, end) = (parts[1], parts[3], parts[5]);
+                let id = self.open(RasmBlockKind::For { counter: counter.to_string(), end: end.to_string() }, line_number);
+                let end_label = format!("__FOR_{}_END", id);
+                Ok(vec![Self::instruction("ADD", start, "*0", counter),
+                        Self::instruction("IF_MT", counter, end, &end_label),     // empty range
+                        RasmLowered::Label(format!("__FOR_{}", id))])
+            }
+            ENDFOR => match self.open_blocks.pop() {
+                    // the last value is checked before incrementing, so a loop up to 255 still ends
+                Some(RasmBlock { kind: RasmBlockKind::For { counter, end }, id, .. }) =>
+                    Ok(vec![Self::instruction("IF_EQ", &counter, &end, &format!("__FOR_{}_END", id)),
+                            Self::instruction("ADD", &counter, "*1", &counter),
+                            Self::jump(&format!("__FOR_{}", id)),
+                            RasmLowered::Label(format!("__FOR_{}_END", id))]),
+                _ => Err("ENDFOR without a matching FOR".to_string())
+            },
+            _ => Err(format!("{} is not a structured keyword", parts[0]))
+        }
+    }
+
+    fn open(&mut self, kind: RasmBlockKind, line_number: u32) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.open_blocks.push(RasmBlock { kind, id, line_number });
+        id
+    }
+
+        // jumps to `target` when the condition `a op b` does not hold
+    fn branch_unless(condition: &[&str], target: &str) -> Result<RasmLowered, String> {
+        if condition.len() != 3 {
+            return Err("Expected a condition like R1 < R2".to_string());
+        }
+        let inverse = match condition[1] {
+            "==" => "IF_N_EQ",
+            "!=" => "IF_EQ",
+            "<" => "IF_MTE",
+            "<=" => "IF_MT",
+            ">" => "IF_LTE",
+            ">=" => "IF_LT",
+                // source is uppercased by now, these are written <s, <=s, >s and >=s
+            "<S" => "IF_MTE_S",
+            "<=S" => "IF_MT_S",
+            ">S" => "IF_LTE_S",
+            ">=S" => "IF_LT_S",
+            op => return Err(format!("Unknown comparison {}, expected one of == != < <= > >= <s <=s >s >=s", op))
+        };
+        Ok(Self::instruction(inverse, condition[0], condition[2], target))
+    }
+
+    fn jump(target: &str) -> RasmLowered {
+        RasmLowered::Code(vec!["IF_T".to_string(), "0".to_string(), "0".to_string(), target.to_string()])
+    }
+
+        // CONST names become immediates, as if written *name. Any other name has to be a register,
+        // instead of a CONST defined further down quietly assembling to a register index.
+    fn mark_constants(parts: &[&str], is_constant: &dyn Fn(&str) -> bool) -> Result<Vec<String>, String> {
+        let mut res = vec![parts[0].to_string()];
+        for part in &parts[1..] {
+            if !part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') || *part == TO
+                || RasmDictionary::REGISTERS.contains(part) {
+                res.push(part.to_string());
+            }
+            else if is_constant(part) {
+                res.push(format!("*{}", part));
+            }
+            else {
+                return Err(format!("{} is neither a register nor a CONST defined above", part));
+            }
+        }
+        Ok(res)
+    }
+
+        // numbers, and names marked with *, are immediate values; anything else is a register
+    fn is_immediate(operand: &str) -> bool {
+        operand.starts_with('*') || operand.starts_with(|c: char| c.is_ascii_digit())
+    }
+
+    fn instruction(op: &str, arg1: &str, arg2: &str, result: &str) -> RasmLowered {
+        let mut op = op.to_string();
+        if Self::is_immediate(arg1) {
+            op.push_str("+IM1");
+        }
+        if Self::is_immediate(arg2) {
+            op.push_str("+IM2");
+        }
+        RasmLowered::Code(vec![op, arg1.trim_start_matches('*').to_string(),
+                               arg2.trim_start_matches('*').to_string(), result.to_string()])
+    }
+
+}
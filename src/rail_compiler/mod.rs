@@ -0,0 +1,87 @@
+use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+use crate::rail_assembler::rasm_program::RasmProgram;
+use crate::rail_compiler::rc_brainfuck::RcBrainfuck;
+use crate::rail_compiler::rc_codegen::RcCodegen;
+use crate::rail_compiler::rc_error::RcError;
+use crate::rail_compiler::rc_lexer::tokenize;
+use crate::rail_compiler::rc_parser::RcParser;
+use crate::rail_system::rail_isa::IsaExtension;
+
+pub mod rc_error;
+mod rc_lexer;
+mod rc_parser;
+mod rc_codegen;
+mod rc_brainfuck;
+
+const PROGRAM_SIZE: usize = 256;
+
+pub trait RailCompilerTrait {
+    fn compile(&self, source: &str) -> Result<String, RcError>;
+    fn compile_program(&self, source: &str) -> Result<RasmProgram, RcError>;
+    fn compile_brainfuck(&self, source: &str) -> Result<String, RcError>;
+    fn compile_brainfuck_program(&self, source: &str) -> Result<RasmProgram, RcError>;
+}
+
+    // Compiles a small C-like language with u8 variables, or Brainfuck, to Rail assembly,
+    // which RailAssembler turns into the binary.
+pub struct RailCompiler {
+    assembler: RailAssembler,
+    extensions: Vec<IsaExtension>
+}
+
+impl RailCompilerTrait for RailCompiler {
+
+    fn compile(&self, source: &str) -> Result<String, RcError> {
+        let program = RcParser::new(tokenize(source)?).parse_program()?;
+        let mut codegen = RcCodegen::new();
+        for extension in &self.extensions {
+            codegen.enable_extension(*extension);
+        }
+        codegen.generate(&program)
+    }
+
+    fn compile_program(&self, source: &str) -> Result<RasmProgram, RcError> {
+        self.assemble(&self.compile(source)?)
+    }
+
+    fn compile_brainfuck(&self, source: &str) -> Result<String, RcError> {
+        RcBrainfuck::new().generate(source)
+    }
+
+    fn compile_brainfuck_program(&self, source: &str) -> Result<RasmProgram, RcError> {
+        self.assemble(&self.compile_brainfuck(source)?)
+    }
+
+}
+
+impl Default for RailCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailCompiler {
+
+    pub fn new() -> Self {
+        Self { assembler: RailAssembler::new(), extensions: Vec::new() }
+    }
+
+        // lets the generated code use the instructions of an ISA extension
+    pub fn enable_extension(&mut self, extension: IsaExtension) {
+        if !self.extensions.contains(&extension) {
+            self.extensions.push(extension);
+            self.assembler.enable_extension(extension);
+        }
+    }
+
+    fn assemble(&self, asm: &str) -> Result<RasmProgram, RcError> {
+        let program = self.assembler.try_assemble_program(asm)
+            .map_err(|e| RcError::new(0, &format!("The generated assembly does not assemble: {}", e.message)))?;
+        if program.binary.len() > PROGRAM_SIZE {
+            return Err(RcError::new(0, &format!("The program needs {} instructions, Rail has room for {}",
+                                                program.binary.len() / 4, PROGRAM_SIZE / 4)));
+        }
+        Ok(program)
+    }
+
+}
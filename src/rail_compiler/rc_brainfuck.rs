@@ -0,0 +1,101 @@
+use crate::rail_compiler::rc_error::RcError;
+
+const POINTER_REGISTER: &str = "R1";
+const CELL_REGISTER: &str = "R2";
+
+    // Translates Brainfuck to Rail assembly. The tape is `ram`, 256 cells that wrap around,
+    // the pointer lives in R1 and the current cell is kept in R2, written through to `ram`.
+    // `.` writes the cell to IO and `,` reads IO into the cell.
+pub struct RcBrainfuck {
+    lines: Vec<String>,
+    is_cell_loaded: bool,
+    next_loop: u32
+}
+
+impl RcBrainfuck {
+
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            is_cell_loaded: false,
+            next_loop: 0
+        }
+    }
+
+    pub fn generate(mut self, source: &str) -> Result<String, RcError> {
+        self.lines.push("# translated from Brainfuck".to_string());
+        let commands: Vec<(char, u32)> = source.lines().enumerate()
+            .flat_map(|(index, line)| line.chars().map(move |c| (c, index as u32 + 1)))
+            .filter(|(c, _)| "<>+-.,[]".contains(*c))
+            .collect();
+        let mut loops: Vec<(u32, u32)> = Vec::new();
+        let mut pos = 0;
+        while pos < commands.len() {
+            let (command, line_number) = commands[pos];
+                // runs of the same move or change become one instruction
+            let count = commands[pos..].iter().take_while(|(c, _)| *c == command).count();
+            let step = if "<>+-".contains(command) { count } else { 1 };
+            let amount = (step % 256) as u8;
+            match command {
+                '>' | '<' => {
+                    let op = if command == '>' { "ADD" } else { "SUB" };
+                    self.lines.push(format!("{}+IM2 {} {} {}", op, POINTER_REGISTER, amount, POINTER_REGISTER));
+                    self.is_cell_loaded = false;
+                }
+                '+' | '-' => {
+                    let op = if command == '+' { "ADD" } else { "SUB" };
+                    self.load_cell();
+                    self.lines.push(format!("{}+IM2 {} {} {}", op, CELL_REGISTER, amount, CELL_REGISTER));
+                    self.store_cell();
+                }
+                '.' => {
+                    self.load_cell();
+                    self.lines.push(format!("ADD+IM2 {} 0 IO", CELL_REGISTER));
+                }
+                ',' => {
+                    self.lines.push(format!("ADD+IM2 IO 0 {}", CELL_REGISTER));
+                    self.store_cell();
+                    self.is_cell_loaded = true;
+                }
+                '[' => {
+                    self.next_loop += 1;
+                    loops.push((self.next_loop, line_number));
+                    self.load_cell();
+                    self.lines.push(format!("IF_EQ+IM2 {} 0 BF_{}_END", CELL_REGISTER, self.next_loop));
+                    self.lines.push(format!("LABEL BF_{}", self.next_loop));
+                }
+                _ => {
+                    let (id, _) = loops.pop().ok_or_else(|| RcError::new(line_number, "] without a matching ["))?;
+                    self.load_cell();
+                    self.lines.push(format!("IF_N_EQ+IM2 {} 0 BF_{}", CELL_REGISTER, id));
+                    self.lines.push(format!("LABEL BF_{}_END", id));
+                }
+            }
+            pos += step;
+        }
+        if let Some((_, line_number)) = loops.last() {
+            return Err(RcError::new(*line_number, "[ is never closed"));
+        }
+        self.lines.push("HALT 0 0 R0".to_string());
+        Ok(self.lines.join("\n") + "\n")
+    }
+
+        // both ends of a loop leave the cell in R2, so it only needs loading after the pointer moved
+    fn load_cell(&mut self) {
+        if !self.is_cell_loaded {
+            self.lines.push(format!("RAM_R 0 {} {}", POINTER_REGISTER, CELL_REGISTER));
+            self.is_cell_loaded = true;
+        }
+    }
+
+    fn store_cell(&mut self) {
+        self.lines.push(format!("RAM_W+IM1 {} {} 0", CELL_REGISTER, POINTER_REGISTER));
+    }
+
+}
+
+impl Default for RcBrainfuck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,745 @@
+use std::collections::HashMap;
+
+use crate::rail_compiler::rc_error::RcError;
+use crate::rail_compiler::rc_parser::{RcBinaryOp, RcExpr, RcFunction, RcProgram, RcStmt, RcStmtLine, RcUnaryOp};
+use crate::rail_system::rail_isa::IsaExtension;
+
+const SPILL_REGISTER: u8 = 0;       // reloads spilled temporaries right before they are used
+const FIRST_REGISTER: u8 = 1;
+const LAST_VAR_REGISTER: u8 = 6;    // R7 stays free for temporaries, so an expression always has a register
+const LAST_REGISTER: u8 = 7;
+const RESULT_REGISTER: u8 = 1;
+const RAM_SIZE: u16 = 256;
+
+#[derive(Clone, Copy)]
+enum RcSymbol {
+    Register(u8),
+    Ram(u8),
+    Array(u8, u8)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RcOperand {
+    Imm(u8),
+    Reg(u8)
+}
+
+struct RcFunctionInfo {
+    params: usize,
+    returns_value: bool
+}
+
+    // Turns the syntax tree into Rail assembly.
+    // Parameters and locals live in R1-R6 (parameters first), locals past that are spilled to ram.
+    // Temporaries take the free registers and are spilled to the gen stack when none are left.
+    // Calls are caller saved: every register in use is pushed before the call and popped after it,
+    // arguments are passed in R1, R2, ... and the result comes back in R1.
+pub struct RcCodegen {
+    lines: Vec<String>,
+    next_label: u32,
+    ram_next: u16,
+    globals: HashMap<String, RcSymbol>,
+    functions: HashMap<String, RcFunctionInfo>,
+    locals: HashMap<String, RcSymbol>,
+    in_use: [bool; 8],
+    temps: [bool; 8],
+    loops: Vec<(String, String)>,
+    line_number: u32,
+    uses_mul: bool,
+    uses_divmod: bool,
+    has_muldiv: bool        // *, / and % are single instructions instead of library calls
+}
+
+impl RcCodegen {
+
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            next_label: 0,
+            ram_next: 0,
+            globals: HashMap::new(),
+            functions: HashMap::new(),
+            locals: HashMap::new(),
+            in_use: [false; 8],
+            temps: [false; 8],
+            loops: Vec::new(),
+            line_number: 0,
+            uses_mul: false,
+            uses_divmod: false,
+            has_muldiv: false
+        }
+    }
+
+    pub fn enable_extension(&mut self, extension: IsaExtension) {
+        match extension {
+            IsaExtension::MulDiv => self.has_muldiv = true,
+                // all values are unsigned u8: nothing carries into a next byte or shifts in a sign
+            IsaExtension::Flags | IsaExtension::Shifts => { }
+                // generated programs stay in one bank, and have no handlers
+            IsaExtension::Banks | IsaExtension::Interrupts => { }
+        }
+    }
+
+    pub fn generate(mut self, program: &RcProgram) -> Result<String, RcError> {
+        for global in &program.globals {
+            self.line_number = global.line_number;
+            if self.globals.contains_key(&global.name) {
+                return Err(self.error(&format!("{} is already defined", global.name)));
+            }
+            let symbol = self.reserve_ram(global.size)?;
+            self.globals.insert(global.name.clone(), symbol);
+        }
+        for function in &program.functions {
+            self.line_number = function.line_number;
+            if self.functions.contains_key(&function.name) || self.globals.contains_key(&function.name) {
+                return Err(self.error(&format!("{} is already defined", function.name)));
+            }
+            if function.params.len() > (LAST_VAR_REGISTER - FIRST_REGISTER + 1) as usize {
+                return Err(self.error(&format!("{} has more than {} parameters", function.name, LAST_VAR_REGISTER)));
+            }
+            self.functions.insert(function.name.clone(), RcFunctionInfo {
+                params: function.params.len(),
+                returns_value: function.returns_value
+            });
+        }
+        match self.functions.get("main") {
+            Some(info) if info.params == 0 => {}
+            Some(_) => return Err(RcError::new(0, "main can not take parameters")),
+            None => return Err(RcError::new(0, "No main function"))
+        }
+
+        self.emit("# compiled from the Rail C-like language".to_string());
+        if self.has_muldiv {
+            self.emit(format!("# &rail-ext-{}", IsaExtension::MulDiv.get_name()));
+        }
+        for global in program.globals.iter().filter(|global| global.value != 0) {
+            if let Some(RcSymbol::Ram(address)) = self.globals.get(&global.name).copied() {
+                self.emit(format!("ADD+IM1+IM2 {} 0 R{}", global.value, SPILL_REGISTER));
+                self.emit(format!("RAM_W+IM1+IM2 R{} {} 0", SPILL_REGISTER, address));
+            }
+        }
+        self.emit("CALL FN_MAIN 0 0".to_string());
+        self.emit("HALT 0 0 R0".to_string());
+        for function in &program.functions {
+            self.generate_function(function)?;
+        }
+        if self.uses_mul {
+            self.emit("INCLUDE std/mul".to_string());
+        }
+        if self.uses_divmod {
+            self.emit("INCLUDE std/divmod".to_string());
+        }
+        Ok(self.lines.join("\n") + "\n")
+    }
+
+    fn generate_function(&mut self, function: &RcFunction) -> Result<(), RcError> {
+        self.line_number = function.line_number;
+        self.locals.clear();
+        self.in_use = [false; 8];
+        self.temps = [false; 8];
+        self.emit(String::new());
+        self.emit(format!("LABEL FN_{}", function.name.to_uppercase()));
+
+        let mut next_register = FIRST_REGISTER;
+        for param in &function.params {
+            self.declare_local(param, RcSymbol::Register(next_register))?;
+            self.in_use[next_register as usize] = true;
+            next_register += 1;
+        }
+            // every local gets its home up front, they live for the whole function
+        let mut decls = Vec::new();
+        Self::collect_decls(&function.body, &mut decls);
+        for (name, size, line_number) in decls {
+            self.line_number = line_number;
+            let symbol = match size {
+                None if next_register <= LAST_VAR_REGISTER => {
+                    self.in_use[next_register as usize] = true;
+                    next_register += 1;
+                    RcSymbol::Register(next_register - 1)
+                }
+                _ => self.reserve_ram(size)?
+            };
+            self.declare_local(&name, symbol)?;
+        }
+
+        self.generate_block(&function.body)?;
+        let ends_in_return = matches!(function.body.last(), Some(RcStmtLine { stmt: RcStmt::Return(_), .. }));
+        if !ends_in_return {
+            self.emit("RET 0 0 0".to_string());
+        }
+        Ok(())
+    }
+
+    fn collect_decls(body: &[RcStmtLine], decls: &mut Vec<(String, Option<u8>, u32)>) {
+        for line in body {
+            match &line.stmt {
+                RcStmt::Decl { name, size, .. } => decls.push((name.clone(), *size, line.line_number)),
+                RcStmt::If { then_body, else_body, .. } => {
+                    Self::collect_decls(then_body, decls);
+                    Self::collect_decls(else_body, decls);
+                }
+                RcStmt::While { body, .. } => Self::collect_decls(body, decls),
+                _ => {}
+            }
+        }
+    }
+
+    fn declare_local(&mut self, name: &str, symbol: RcSymbol) -> Result<(), RcError> {
+        if self.locals.contains_key(name) || self.functions.contains_key(name) {
+            return Err(self.error(&format!("{} is already defined", name)));
+        }
+        self.locals.insert(name.to_string(), symbol);
+        Ok(())
+    }
+
+    fn reserve_ram(&mut self, size: Option<u8>) -> Result<RcSymbol, RcError> {
+        let address = self.ram_next;
+        self.ram_next += size.unwrap_or(1) as u16;
+        if self.ram_next > RAM_SIZE {
+            return Err(self.error("Out of ram for variables"));
+        }
+        Ok(match size {
+            Some(size) => RcSymbol::Array(address as u8, size),
+            None => RcSymbol::Ram(address as u8)
+        })
+    }
+
+    fn generate_block(&mut self, body: &[RcStmtLine]) -> Result<(), RcError> {
+        for line in body {
+            self.line_number = line.line_number;
+            self.generate_statement(&line.stmt)?;
+        }
+        Ok(())
+    }
+
+    fn generate_statement(&mut self, stmt: &RcStmt) -> Result<(), RcError> {
+        match stmt {
+            RcStmt::Decl { name, value: Some(value), .. } => self.generate_assign(name, None, value)?,
+            RcStmt::Decl { .. } => {}
+            RcStmt::Assign { name, index, value } => self.generate_assign(name, index.as_ref(), value)?,
+            RcStmt::If { cond, then_body, else_body } => {
+                let else_label = self.new_label();
+                self.generate_branch(cond, &else_label, false)?;
+                self.generate_block(then_body)?;
+                if else_body.is_empty() {
+                    self.emit(format!("LABEL {}", else_label));
+                }
+                else {
+                    let end_label = self.new_label();
+                    self.emit(format!("JMP 0 0 {}", end_label));
+                    self.emit(format!("LABEL {}", else_label));
+                    self.generate_block(else_body)?;
+                    self.emit(format!("LABEL {}", end_label));
+                }
+            }
+            RcStmt::While { cond, body } => {
+                let top_label = self.new_label();
+                let end_label = self.new_label();
+                self.emit(format!("LABEL {}", top_label));
+                self.generate_branch(cond, &end_label, false)?;
+                self.loops.push((top_label.clone(), end_label.clone()));
+                self.generate_block(body)?;
+                self.loops.pop();
+                self.emit(format!("JMP 0 0 {}", top_label));
+                self.emit(format!("LABEL {}", end_label));
+            }
+            RcStmt::Break | RcStmt::Continue => {
+                let (top_label, end_label) = self.loops.last().cloned()
+                    .ok_or_else(|| self.error("break or continue outside of a loop"))?;
+                let label = if *stmt == RcStmt::Break { end_label } else { top_label };
+                self.emit(format!("JMP 0 0 {}", label));
+            }
+            RcStmt::Return(value) => {
+                if let Some(value) = value {
+                    self.generate_into(value, RESULT_REGISTER)?;
+                }
+                self.emit("RET 0 0 0".to_string());
+            }
+            RcStmt::Expr(RcExpr::Call(name, args)) if name == "out" => {
+                if args.len() != 1 {
+                    return Err(self.error("out takes one argument"));
+                }
+                let value = self.generate_operand(&args[0])?;
+                self.emit_alu("ADD", value, RcOperand::Imm(0), "IO");
+                self.free_operand(value);
+            }
+            RcStmt::Expr(RcExpr::Call(name, args)) => {
+                let label = self.function_label(name, args.len())?;
+                if let Some(register) = self.generate_call(&label, args, None, LAST_REGISTER)? {
+                    self.free(register);
+                }
+            }
+            RcStmt::Expr(_) => return Err(self.error("Only a call can be used as a statement"))
+        }
+        Ok(())
+    }
+
+    fn generate_assign(&mut self, name: &str, index: Option<&RcExpr>, value: &RcExpr) -> Result<(), RcError> {
+        match (self.lookup(name)?, index) {
+            (RcSymbol::Register(register), None) => self.generate_into(value, register)?,
+            (RcSymbol::Ram(address), None) => {
+                let value = self.generate_register(value)?;
+                self.emit(format!("RAM_W+IM1+IM2 R{} {} 0", value, address));
+                self.free(value);
+            }
+            (RcSymbol::Array(base, size), Some(RcExpr::Number(offset))) => {
+                self.check_bounds(name, size, *offset)?;
+                let value = self.generate_register(value)?;
+                self.emit(format!("RAM_W+IM1+IM2 R{} {} 0", value, base + offset));
+                self.free(value);
+            }
+            (RcSymbol::Array(base, _), Some(index)) => {
+                let index = self.generate_register(index)?;
+                let (index, value) = self.generate_after(RcOperand::Reg(index), value)?;
+                let value = self.load_register(value)?;
+                let index = self.load_register(index)?;
+                self.emit(format!("RAM_WX R{} R{} {}", value, index, base));
+                self.free(index);
+                self.free(value);
+            }
+            (RcSymbol::Array(..), None) => return Err(self.error(&format!("{} is an array, assign to an element", name))),
+            (_, Some(_)) => return Err(self.error(&format!("{} is not an array", name)))
+        }
+        Ok(())
+    }
+
+        // evaluates into the given register, which saves a move for the common single operation
+    fn generate_into(&mut self, expr: &RcExpr, target: u8) -> Result<(), RcError> {
+        match expr {
+            RcExpr::Number(value) => self.emit_alu("ADD", RcOperand::Imm(*value), RcOperand::Imm(0), &format!("R{}", target)),
+            RcExpr::Binary(op, left, right) if !op.is_comparison() && !op.is_logic() && !self.is_libcall(*op) => {
+                self.generate_binary(*op, left, right, Some(target))?;
+            }
+            _ => {
+                let register = self.generate_register(expr)?;
+                self.emit_move(register, target);
+                self.free(register);
+            }
+        }
+        Ok(())
+    }
+
+    fn generate_register(&mut self, expr: &RcExpr) -> Result<u8, RcError> {
+        let operand = self.generate_operand(expr)?;
+        self.load_register(operand)
+    }
+
+    fn load_register(&mut self, operand: RcOperand) -> Result<u8, RcError> {
+        match operand {
+            RcOperand::Reg(register) => Ok(register),
+            RcOperand::Imm(value) => {
+                let register = self.alloc()?;
+                self.emit_alu("ADD", RcOperand::Imm(value), RcOperand::Imm(0), &format!("R{}", register));
+                Ok(register)
+            }
+        }
+    }
+
+        // numbers stay immediate, everything else ends up in a register
+    fn generate_operand(&mut self, expr: &RcExpr) -> Result<RcOperand, RcError> {
+        match expr {
+            RcExpr::Number(value) => Ok(RcOperand::Imm(*value)),
+            RcExpr::Var(name) => match self.lookup(name)? {
+                RcSymbol::Register(register) => Ok(RcOperand::Reg(register)),
+                RcSymbol::Ram(address) => {
+                    let register = self.alloc()?;
+                    self.emit(format!("RAM_R+IM2 0 {} R{}", address, register));
+                    Ok(RcOperand::Reg(register))
+                }
+                RcSymbol::Array(..) => Err(self.error(&format!("{} is an array, index it with []", name)))
+            },
+            RcExpr::Index(name, index) => match (self.lookup(name)?, index.as_ref()) {
+                (RcSymbol::Array(base, size), RcExpr::Number(offset)) => {
+                    self.check_bounds(name, size, *offset)?;
+                    let register = self.alloc()?;
+                    self.emit(format!("RAM_R+IM2 0 {} R{}", base + offset, register));
+                    Ok(RcOperand::Reg(register))
+                }
+                (RcSymbol::Array(base, _), index) => {
+                    let index = self.generate_register(index)?;
+                    let register = self.result_register(RcOperand::Reg(index), RcOperand::Imm(0))?;
+                    self.emit(format!("RAM_RX+IM2 R{} {} R{}", index, base, register));
+                    Ok(RcOperand::Reg(register))
+                }
+                _ => Err(self.error(&format!("{} is not an array", name)))
+            },
+            RcExpr::Call(name, args) => {
+                if name == "out" {
+                    return Err(self.error("out has no value"));
+                }
+                let label = self.function_label(name, args.len())?;
+                if !self.functions[name].returns_value {
+                    return Err(self.error(&format!("{} is void and has no value", name)));
+                }
+                let register = self.generate_call(&label, args, Some(RESULT_REGISTER), LAST_REGISTER)?;
+                Ok(RcOperand::Reg(register.unwrap_or(RESULT_REGISTER)))
+            }
+            RcExpr::Unary(RcUnaryOp::LogicNot, _) => self.generate_truth(expr),
+            RcExpr::Unary(op, inner) => {
+                let inner = self.generate_operand(inner)?;
+                if let RcOperand::Imm(value) = inner {
+                    return Ok(RcOperand::Imm(if *op == RcUnaryOp::Neg { value.wrapping_neg() } else { !value }));
+                }
+                let target = self.result_register(inner, RcOperand::Imm(0))?;
+                match op {
+                    RcUnaryOp::Neg => self.emit_alu("SUB", RcOperand::Imm(0), inner, &format!("R{}", target)),
+                    _ => self.emit_alu("NOT", inner, RcOperand::Imm(0), &format!("R{}", target))
+                }
+                Ok(RcOperand::Reg(target))
+            }
+            RcExpr::Binary(op, _, _) if op.is_comparison() || op.is_logic() => self.generate_truth(expr),
+            RcExpr::Binary(op, left, right) if self.is_libcall(*op) => {
+                    // the library routines only change the registers they document
+                let (label, result, clobbered) = match op {
+                    RcBinaryOp::Mul => {
+                        self.uses_mul = true;
+                        ("MUL8", 1, 4)
+                    }
+                    _ => {
+                        self.uses_divmod = true;
+                        ("DIVMOD", if *op == RcBinaryOp::Div { 1 } else { 2 }, 3)
+                    }
+                };
+                let args = [left.as_ref().clone(), right.as_ref().clone()];
+                let register = self.generate_call(label, &args, Some(result), clobbered)?;
+                Ok(RcOperand::Reg(register.unwrap_or(result)))
+            }
+            RcExpr::Binary(op, left, right) => self.generate_binary(*op, left, right, None)
+        }
+    }
+
+    fn generate_binary(&mut self, op: RcBinaryOp, left: &RcExpr, right: &RcExpr, target: Option<u8>) -> Result<RcOperand, RcError> {
+        let left = self.generate_operand(left)?;
+        let (left, right) = self.generate_after(left, right)?;
+        if let (RcOperand::Imm(a), RcOperand::Imm(b)) = (left, right) {
+            let value = Self::fold(op, a, b);
+            return match target {
+                Some(target) => {
+                    self.emit_alu("ADD", RcOperand::Imm(value), RcOperand::Imm(0), &format!("R{}", target));
+                    Ok(RcOperand::Reg(target))
+                }
+                None => Ok(RcOperand::Imm(value))
+            };
+        }
+        let target = match target {
+            Some(target) => {
+                self.free_operand(left);
+                self.free_operand(right);
+                target
+            }
+            None => self.result_register(left, right)?
+        };
+        let mnemonic = match op {
+            RcBinaryOp::Add => "ADD",
+            RcBinaryOp::Sub => "SUB",
+            RcBinaryOp::And => "AND",
+            RcBinaryOp::Or => "OR",
+            RcBinaryOp::Xor => "XOR",
+            RcBinaryOp::Shl => "SHL",
+            RcBinaryOp::Mul => "MUL",
+            RcBinaryOp::Div => "DIV",
+            RcBinaryOp::Mod => "MOD",
+            _ => "SHR"
+        };
+        self.emit_alu(mnemonic, left, right, &format!("R{}", target));
+        Ok(RcOperand::Reg(target))
+    }
+
+        // evaluates the right side while the left one is kept, pushing the left one to the gen stack
+        // when no register is left and popping it back into the spill register afterwards
+    fn generate_after(&mut self, left: RcOperand, right: &RcExpr) -> Result<(RcOperand, RcOperand), RcError> {
+        let spilled = match left {
+            RcOperand::Reg(register) if self.is_temp(register) && self.free_count() == 0 && !self.is_in_place(right) => {
+                self.emit(format!("S_PUSH+IM1 R{} 0 0", register));
+                self.free(register);
+                true
+            }
+            _ => false
+        };
+        let right = self.generate_operand(right)?;
+        if !spilled {
+            return Ok((left, right));
+        }
+        self.emit(format!("S_POP 0 0 R{}", SPILL_REGISTER));
+        Ok((RcOperand::Reg(SPILL_REGISTER), right))
+    }
+
+        // numbers and register variables are used where they are, without taking a register
+    fn is_in_place(&self, expr: &RcExpr) -> bool {
+        match expr {
+            RcExpr::Number(_) => true,
+            RcExpr::Var(name) => matches!(self.lookup(name), Ok(RcSymbol::Register(_))),
+            _ => false
+        }
+    }
+
+        // reuses a temporary operand for the result, or takes a new register
+    fn result_register(&mut self, left: RcOperand, right: RcOperand) -> Result<u8, RcError> {
+        match (left, right) {
+            (RcOperand::Reg(register), _) if self.is_temp(register) => {
+                self.free_operand(right);
+                Ok(register)
+            }
+            (_, RcOperand::Reg(register)) if self.is_temp(register) => {
+                self.free_operand(left);
+                Ok(register)
+            }
+            _ => self.alloc()
+        }
+    }
+
+        // comparisons and logic as a value, 1 or 0
+    fn generate_truth(&mut self, expr: &RcExpr) -> Result<RcOperand, RcError> {
+        let false_label = self.new_label();
+        let end_label = self.new_label();
+        self.generate_branch(expr, &false_label, false)?;
+        let register = self.alloc()?;
+        self.emit(format!("ADD+IM1+IM2 1 0 R{}", register));
+        self.emit(format!("JMP 0 0 {}", end_label));
+        self.emit(format!("LABEL {}", false_label));
+        self.emit(format!("ADD+IM1+IM2 0 0 R{}", register));
+        self.emit(format!("LABEL {}", end_label));
+        Ok(RcOperand::Reg(register))
+    }
+
+        // jumps to the label when the condition is jump_if, falls through otherwise
+    fn generate_branch(&mut self, cond: &RcExpr, label: &str, jump_if: bool) -> Result<(), RcError> {
+        match cond {
+            RcExpr::Unary(RcUnaryOp::LogicNot, inner) => self.generate_branch(inner, label, !jump_if),
+            RcExpr::Binary(op, left, right) if op.is_logic() => {
+                    // a && b jumps on false when either is false, a || b jumps on true when either is true
+                if (*op == RcBinaryOp::LogicAnd) != jump_if {
+                    self.generate_branch(left, label, jump_if)?;
+                    self.generate_branch(right, label, jump_if)
+                }
+                else {
+                    let skip_label = self.new_label();
+                    self.generate_branch(left, &skip_label, !jump_if)?;
+                    self.generate_branch(right, label, jump_if)?;
+                    self.emit(format!("LABEL {}", skip_label));
+                    Ok(())
+                }
+            }
+            RcExpr::Binary(op, left, right) if op.is_comparison() => {
+                let left = self.generate_operand(left)?;
+                let (left, right) = self.generate_after(left, right)?;
+                let op = if jump_if { *op } else { Self::invert(*op) };
+                self.emit_branch(op, left, right, label);
+                self.free_operand(left);
+                self.free_operand(right);
+                Ok(())
+            }
+            _ => {
+                let value = self.generate_operand(cond)?;
+                let op = if jump_if { RcBinaryOp::NotEq } else { RcBinaryOp::Eq };
+                self.emit_branch(op, value, RcOperand::Imm(0), label);
+                self.free_operand(value);
+                Ok(())
+            }
+        }
+    }
+
+        // saves the registers in use up to the last one the callee changes, passes the arguments
+        // through the gen stack into R1, R2, ..., and copies the result out before restoring
+    fn generate_call(&mut self, label: &str, args: &[RcExpr], result: Option<u8>, clobbered: u8) -> Result<Option<u8>, RcError> {
+        let saved: Vec<u8> = (FIRST_REGISTER..=clobbered).filter(|register| self.in_use[*register as usize]).collect();
+        for register in &saved {
+            self.emit(format!("S_PUSH+IM1 R{} 0 0", register));
+        }
+        let mut pushed = Vec::new();
+        let mut immediates = Vec::new();
+        for (target, arg) in (FIRST_REGISTER..).zip(args) {
+            match self.generate_operand(arg)? {
+                RcOperand::Imm(value) => immediates.push((target, value)),
+                RcOperand::Reg(register) => {
+                    self.emit(format!("S_PUSH+IM1 R{} 0 0", register));
+                    self.free(register);
+                    pushed.push((target, register));
+                }
+            }
+        }
+        match pushed.as_slice() {
+                // nothing ran after the only push, a move does the same
+            [(target, register)] if self.lines.last() == Some(&format!("S_PUSH+IM1 R{} 0 0", register)) => {
+                self.lines.pop();
+                self.emit_move(*register, *target);
+            }
+            _ => for (target, _) in pushed.iter().rev() {
+                self.emit(format!("S_POP 0 0 R{}", target));
+            }
+        }
+        for (target, value) in immediates {
+            self.emit_alu("ADD", RcOperand::Imm(value), RcOperand::Imm(0), &format!("R{}", target));
+        }
+        self.emit(format!("CALL {} 0 0", label));
+        let target = match result {
+            Some(result) => {
+                let target = self.alloc()?;
+                self.emit_move(result, target);
+                Some(target)
+            }
+            None => None
+        };
+        for register in saved.iter().rev() {
+            self.emit(format!("S_POP 0 0 R{}", register));
+        }
+        Ok(target)
+    }
+
+    fn function_label(&self, name: &str, arg_count: usize) -> Result<String, RcError> {
+        match self.functions.get(name) {
+            Some(info) if info.params == arg_count => Ok(format!("FN_{}", name.to_uppercase())),
+            Some(info) => Err(self.error(&format!("{} takes {} argument(s), found {}", name, info.params, arg_count))),
+            None => Err(self.error(&format!("{} is not a function", name)))
+        }
+    }
+
+    fn check_bounds(&self, name: &str, size: u8, offset: u8) -> Result<(), RcError> {
+        match offset < size {
+            true => Ok(()),
+            false => Err(self.error(&format!("Index {} is out of bounds of {}[{}]", offset, name, size)))
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<RcSymbol, RcError> {
+        self.locals.get(name).or_else(|| self.globals.get(name)).copied()
+            .ok_or_else(|| self.error(&format!("{} is not defined", name)))
+    }
+
+    fn emit_alu(&mut self, mnemonic: &str, a: RcOperand, b: RcOperand, result: &str) {
+        let (op, a, b) = Self::encode_operands(mnemonic, a, b);
+        self.emit(format!("{} {} {} {}", op, a, b, result));
+    }
+
+    fn emit_move(&mut self, source: u8, target: u8) {
+        if source != target {
+            self.emit(format!("ADD+IM2 R{} 0 R{}", source, target));
+        }
+    }
+
+    fn emit_branch(&mut self, op: RcBinaryOp, a: RcOperand, b: RcOperand, label: &str) {
+        if let (RcOperand::Imm(a), RcOperand::Imm(b)) = (a, b) {
+                // decided at compile time
+            if Self::fold(op, a, b) != 0 {
+                self.emit(format!("JMP 0 0 {}", label));
+            }
+            return;
+        }
+        let mnemonic = match op {
+            RcBinaryOp::Eq => "IF_EQ",
+            RcBinaryOp::NotEq => "IF_N_EQ",
+            RcBinaryOp::Lt => "IF_LT",
+            RcBinaryOp::LtEq => "IF_LTE",
+            RcBinaryOp::Gt => "IF_MT",
+            _ => "IF_MTE"
+        };
+        let (op, a, b) = Self::encode_operands(mnemonic, a, b);
+        self.emit(format!("{} {} {} {}", op, a, b, label));
+    }
+
+    fn encode_operands(mnemonic: &str, a: RcOperand, b: RcOperand) -> (String, String, String) {
+        let mut op = mnemonic.to_string();
+        let a = match a {
+            RcOperand::Imm(value) => {
+                op.push_str("+IM1");
+                value.to_string()
+            }
+            RcOperand::Reg(register) => format!("R{}", register)
+        };
+        let b = match b {
+            RcOperand::Imm(value) => {
+                op.push_str("+IM2");
+                value.to_string()
+            }
+            RcOperand::Reg(register) => format!("R{}", register)
+        };
+        (op, a, b)
+    }
+
+    fn fold(op: RcBinaryOp, a: u8, b: u8) -> u8 {
+        match op {
+            RcBinaryOp::Add => a.wrapping_add(b),
+            RcBinaryOp::Sub => a.wrapping_sub(b),
+            RcBinaryOp::And => a & b,
+            RcBinaryOp::Or => a | b,
+            RcBinaryOp::Xor => a ^ b,
+            RcBinaryOp::Shl => a.checked_shl(b as u32).unwrap_or(0),
+            RcBinaryOp::Shr => a.checked_shr(b as u32).unwrap_or(0),
+            RcBinaryOp::Mul => a.wrapping_mul(b),
+            RcBinaryOp::Div => a.checked_div(b).unwrap_or(255),
+            RcBinaryOp::Mod => a.checked_rem(b).unwrap_or(a),
+            RcBinaryOp::Eq => (a == b) as u8,
+            RcBinaryOp::NotEq => (a != b) as u8,
+            RcBinaryOp::Lt => (a < b) as u8,
+            RcBinaryOp::LtEq => (a <= b) as u8,
+            RcBinaryOp::Gt => (a > b) as u8,
+            RcBinaryOp::GtEq => (a >= b) as u8,
+            RcBinaryOp::LogicAnd => (a != 0 && b != 0) as u8,
+            RcBinaryOp::LogicOr => (a != 0 || b != 0) as u8
+        }
+    }
+
+    fn invert(op: RcBinaryOp) -> RcBinaryOp {
+        match op {
+            RcBinaryOp::Eq => RcBinaryOp::NotEq,
+            RcBinaryOp::NotEq => RcBinaryOp::Eq,
+            RcBinaryOp::Lt => RcBinaryOp::GtEq,
+            RcBinaryOp::LtEq => RcBinaryOp::Gt,
+            RcBinaryOp::Gt => RcBinaryOp::LtEq,
+            _ => RcBinaryOp::Lt
+        }
+    }
+
+    fn is_libcall(&self, op: RcBinaryOp) -> bool {
+        !self.has_muldiv && matches!(op, RcBinaryOp::Mul | RcBinaryOp::Div | RcBinaryOp::Mod)
+    }
+
+    fn alloc(&mut self) -> Result<u8, RcError> {
+        let register = (FIRST_REGISTER..=LAST_REGISTER).find(|register| !self.in_use[*register as usize])
+            .ok_or_else(|| self.error("Expression is too complex, out of registers"))?;
+        self.in_use[register as usize] = true;
+        self.temps[register as usize] = true;
+        Ok(register)
+    }
+
+    fn free(&mut self, register: u8) {
+        if self.is_temp(register) {
+            self.in_use[register as usize] = false;
+            self.temps[register as usize] = false;
+        }
+    }
+
+    fn free_operand(&mut self, operand: RcOperand) {
+        if let RcOperand::Reg(register) = operand {
+            self.free(register);
+        }
+    }
+
+    fn is_temp(&self, register: u8) -> bool {
+        self.temps[register as usize]
+    }
+
+    fn free_count(&self) -> usize {
+        (FIRST_REGISTER..=LAST_REGISTER).filter(|register| !self.in_use[*register as usize]).count()
+    }
+
+    fn new_label(&mut self) -> String {
+        self.next_label += 1;
+        format!("L_{}", self.next_label)
+    }
+
+    fn emit(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn error(&self, message: &str) -> RcError {
+        RcError::new(self.line_number, message)
+    }
+
+}
+
+impl Default for RcCodegen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
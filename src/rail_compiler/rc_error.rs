@@ -0,0 +1,21 @@
+use std::fmt::{Display, Formatter};
+
+pub struct RcError {
+    pub line_number: u32,
+    pub message: String
+}
+
+impl RcError {
+    pub fn new(line_number: u32, message: &str) -> Self {
+        Self {
+            line_number,
+            message: message.to_string()
+        }
+    }
+}
+
+impl Display for RcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\n\x00\x1B[33m Compile error on line {}: {} \x00\x1B[0m\n", self.line_number, self.message)
+    }
+}
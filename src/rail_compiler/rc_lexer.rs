@@ -0,0 +1,75 @@
+use crate::rail_compiler::rc_error::RcError;
+
+    // longest first, so "<=" is not read as "<" and "="
+const SYMBOLS: [&str; 29] = ["<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+                             "+", "-", "*", "/", "%", "&", "|", "^", "~", "!", "<", ">", "=",
+                             "(", ")", "{", "}", "[", "]", ";", ","];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RcToken {
+    Number(u8),
+    Ident(String),
+    Symbol(&'static str),
+    End
+}
+
+pub struct RcLexeme {
+    pub token: RcToken,
+    pub line_number: u32
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<RcLexeme>, RcError> {
+    let mut res = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index as u32 + 1;
+        let code = match line.find("//") {
+            Some(pos) => &line[..pos],
+            None => line
+        };
+        let chars: Vec<char> = code.chars().collect();
+        let mut pos = 0;
+        while pos < chars.len() {
+            let c = chars[pos];
+            if c.is_whitespace() {
+                pos += 1;
+                continue;
+            }
+            let start = pos;
+            let token = if c.is_ascii_digit() {
+                while pos < chars.len() && chars[pos].is_ascii_alphanumeric() {
+                    pos += 1;
+                }
+                let text: String = chars[start..pos].iter().collect();
+                let value = match text.strip_prefix("0x") {
+                    Some(hex) => u8::from_str_radix(hex, 16),
+                    None => text.parse::<u8>()
+                };
+                RcToken::Number(value.map_err(|_| RcError::new(line_number, &format!("{} is not a number from 0 to 255", text)))?)
+            }
+            else if c.is_alphabetic() || c == '_' {
+                while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                    pos += 1;
+                }
+                RcToken::Ident(chars[start..pos].iter().collect())
+            }
+            else if c == '\'' && pos + 2 < chars.len() && chars[pos + 2] == '\'' && chars[pos + 1].is_ascii() {
+                pos += 3;
+                RcToken::Number(chars[start + 1] as u8)
+            }
+            else {
+                let rest: String = chars[pos..].iter().collect();
+                match SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+                    Some(symbol) => {
+                        pos += symbol.len();
+                        RcToken::Symbol(symbol)
+                    }
+                    None => return Err(RcError::new(line_number, &format!("Unexpected character {}", c)))
+                }
+            };
+            res.push(RcLexeme { token, line_number });
+        }
+    }
+    let line_number = source.lines().count() as u32;
+    res.push(RcLexeme { token: RcToken::End, line_number });
+    Ok(res)
+}
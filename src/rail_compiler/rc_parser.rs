@@ -0,0 +1,376 @@
+use crate::rail_compiler::rc_error::RcError;
+use crate::rail_compiler::rc_lexer::{RcLexeme, RcToken};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RcBinaryOp {
+    Add, Sub, Mul, Div, Mod,
+    And, Or, Xor, Shl, Shr,
+    Eq, NotEq, Lt, LtEq, Gt, GtEq,
+    LogicAnd, LogicOr
+}
+
+impl RcBinaryOp {
+    pub fn is_comparison(&self) -> bool {
+        matches!(self, RcBinaryOp::Eq | RcBinaryOp::NotEq | RcBinaryOp::Lt | RcBinaryOp::LtEq | RcBinaryOp::Gt | RcBinaryOp::GtEq)
+    }
+
+    pub fn is_logic(&self) -> bool {
+        matches!(self, RcBinaryOp::LogicAnd | RcBinaryOp::LogicOr)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RcUnaryOp {
+    Neg,
+    Not,        // ~, bitwise
+    LogicNot    // !
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RcExpr {
+    Number(u8),
+    Var(String),
+    Index(String, Box<RcExpr>),
+    Call(String, Vec<RcExpr>),
+    Unary(RcUnaryOp, Box<RcExpr>),
+    Binary(RcBinaryOp, Box<RcExpr>, Box<RcExpr>)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RcStmt {
+    Decl { name: String, size: Option<u8>, value: Option<RcExpr> },
+    Assign { name: String, index: Option<RcExpr>, value: RcExpr },
+    If { cond: RcExpr, then_body: Vec<RcStmtLine>, else_body: Vec<RcStmtLine> },
+    While { cond: RcExpr, body: Vec<RcStmtLine> },
+    Return(Option<RcExpr>),
+    Expr(RcExpr),
+    Break,
+    Continue
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RcStmtLine {
+    pub stmt: RcStmt,
+    pub line_number: u32
+}
+
+pub struct RcGlobal {
+    pub name: String,
+    pub size: Option<u8>,       // Some for arrays
+    pub value: u8,
+    pub line_number: u32
+}
+
+pub struct RcFunction {
+    pub name: String,
+    pub params: Vec<String>,
+    pub returns_value: bool,
+    pub body: Vec<RcStmtLine>,
+    pub line_number: u32
+}
+
+pub struct RcProgram {
+    pub globals: Vec<RcGlobal>,
+    pub functions: Vec<RcFunction>
+}
+
+    // binary operators from the loosest to the tightest binding level, as in C
+const LEVELS: [&[(&str, RcBinaryOp)]; 10] = [
+    &[("||", RcBinaryOp::LogicOr)],
+    &[("&&", RcBinaryOp::LogicAnd)],
+    &[("|", RcBinaryOp::Or)],
+    &[("^", RcBinaryOp::Xor)],
+    &[("&", RcBinaryOp::And)],
+    &[("==", RcBinaryOp::Eq), ("!=", RcBinaryOp::NotEq)],
+    &[("<", RcBinaryOp::Lt), ("<=", RcBinaryOp::LtEq), (">", RcBinaryOp::Gt), (">=", RcBinaryOp::GtEq)],
+    &[("<<", RcBinaryOp::Shl), (">>", RcBinaryOp::Shr)],
+    &[("+", RcBinaryOp::Add), ("-", RcBinaryOp::Sub)],
+    &[("*", RcBinaryOp::Mul), ("/", RcBinaryOp::Div), ("%", RcBinaryOp::Mod)]
+];
+
+pub struct RcParser {
+    lexemes: Vec<RcLexeme>,
+    pos: usize
+}
+
+impl RcParser {
+
+    pub fn new(lexemes: Vec<RcLexeme>) -> Self {
+        Self {
+            lexemes,
+            pos: 0
+        }
+    }
+
+    pub fn parse_program(&mut self) -> Result<RcProgram, RcError> {
+        let mut program = RcProgram { globals: Vec::new(), functions: Vec::new() };
+        while self.peek() != &RcToken::End {
+            let line_number = self.line();
+            let is_void = match self.ident()?.as_str() {
+                "u8" => false,
+                "void" => true,
+                other => return Err(self.error(&format!("Expected u8 or void, found {}", other)))
+            };
+            let name = self.ident()?;
+            if self.accept("(") {
+                let params = self.parse_params()?;
+                let body = self.parse_block()?;
+                program.functions.push(RcFunction { name, params, returns_value: !is_void, body, line_number });
+                continue;
+            }
+            if is_void {
+                return Err(self.error(&format!("Variable {} can not be void", name)));
+            }
+            let size = self.parse_array_size()?;
+            let value = match self.accept("=") {
+                true => match self.advance() {
+                    RcToken::Number(value) if size.is_none() => value,
+                    _ => return Err(RcError::new(line_number, "A global can only be initialized with a number"))
+                },
+                false => 0
+            };
+            self.expect(";")?;
+            program.globals.push(RcGlobal { name, size, value, line_number });
+        }
+        Ok(program)
+    }
+
+    fn parse_params(&mut self) -> Result<Vec<String>, RcError> {
+        let mut params = Vec::new();
+        if self.accept(")") {
+            return Ok(params);
+        }
+        loop {
+            if self.ident()? != "u8" {
+                return Err(self.error("Parameters must be u8"));
+            }
+            params.push(self.ident()?);
+            if self.accept(")") {
+                return Ok(params);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn parse_array_size(&mut self) -> Result<Option<u8>, RcError> {
+        if !self.accept("[") {
+            return Ok(None);
+        }
+        let size = match self.advance() {
+            RcToken::Number(size) if size > 0 => size,
+            _ => return Err(self.error("Array size must be a number from 1 to 255"))
+        };
+        self.expect("]")?;
+        Ok(Some(size))
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<RcStmtLine>, RcError> {
+        self.expect("{")?;
+        let mut body = Vec::new();
+        while !self.accept("}") {
+            if self.peek() == &RcToken::End {
+                return Err(self.error("Block is never closed, expected }"));
+            }
+            body.push(self.parse_statement()?);
+        }
+        Ok(body)
+    }
+
+        // a { block } or a single statement, for the bodies of if, else and while
+    fn parse_body(&mut self) -> Result<Vec<RcStmtLine>, RcError> {
+        match self.peek() {
+            RcToken::Symbol("{") => self.parse_block(),
+            _ => Ok(vec![self.parse_statement()?])
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<RcStmtLine, RcError> {
+        let line_number = self.line();
+        let keyword = match self.peek() {
+            RcToken::Ident(name) => name.clone(),
+            _ => String::new()
+        };
+        let stmt = match keyword.as_str() {
+            "u8" => {
+                self.advance();
+                let name = self.ident()?;
+                let size = self.parse_array_size()?;
+                let value = match size.is_none() && self.accept("=") {
+                    true => Some(self.parse_expr()?),
+                    false => None
+                };
+                self.expect(";")?;
+                RcStmt::Decl { name, size, value }
+            }
+            "if" => {
+                self.advance();
+                let cond = self.parse_condition()?;
+                let then_body = self.parse_body()?;
+                let else_body = match self.peek() == &RcToken::Ident("else".to_string()) {
+                    true => {
+                        self.advance();
+                        self.parse_body()?
+                    }
+                    false => Vec::new()
+                };
+                RcStmt::If { cond, then_body, else_body }
+            }
+            "while" => {
+                self.advance();
+                let cond = self.parse_condition()?;
+                RcStmt::While { cond, body: self.parse_body()? }
+            }
+            "return" => {
+                self.advance();
+                let value = match self.accept(";") {
+                    true => None,
+                    false => {
+                        let value = self.parse_expr()?;
+                        self.expect(";")?;
+                        Some(value)
+                    }
+                };
+                RcStmt::Return(value)
+            }
+            "break" | "continue" => {
+                self.advance();
+                self.expect(";")?;
+                match keyword.as_str() {
+                    "break" => RcStmt::Break,
+                    _ => RcStmt::Continue
+                }
+            }
+            _ => {
+                let expr = self.parse_expr()?;
+                let stmt = match self.accept("=") {
+                    true => match expr {
+                        RcExpr::Var(name) => RcStmt::Assign { name, index: None, value: self.parse_expr()? },
+                        RcExpr::Index(name, index) => RcStmt::Assign { name, index: Some(*index), value: self.parse_expr()? },
+                        _ => return Err(RcError::new(line_number, "Can only assign to a variable or an array element"))
+                    },
+                    false => RcStmt::Expr(expr)
+                };
+                self.expect(";")?;
+                stmt
+            }
+        };
+        Ok(RcStmtLine { stmt, line_number })
+    }
+
+    fn parse_condition(&mut self) -> Result<RcExpr, RcError> {
+        self.expect("(")?;
+        let cond = self.parse_expr()?;
+        self.expect(")")?;
+        Ok(cond)
+    }
+
+    pub fn parse_expr(&mut self) -> Result<RcExpr, RcError> {
+        self.parse_level(0)
+    }
+
+    fn parse_level(&mut self, level: usize) -> Result<RcExpr, RcError> {
+        if level == LEVELS.len() {
+            return self.parse_unary();
+        }
+        let mut expr = self.parse_level(level + 1)?;
+        'outer: loop {
+            for (symbol, op) in LEVELS[level] {
+                if self.accept(symbol) {
+                    let right = self.parse_level(level + 1)?;
+                    expr = RcExpr::Binary(*op, Box::new(expr), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(expr);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<RcExpr, RcError> {
+        for (symbol, op) in [("-", RcUnaryOp::Neg), ("~", RcUnaryOp::Not), ("!", RcUnaryOp::LogicNot)] {
+            if self.accept(symbol) {
+                return Ok(RcExpr::Unary(op, Box::new(self.parse_unary()?)));
+            }
+        }
+        match self.advance() {
+            RcToken::Number(value) => Ok(RcExpr::Number(value)),
+            RcToken::Symbol("(") => {
+                let expr = self.parse_expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            RcToken::Ident(name) => {
+                if self.accept("[") {
+                    let index = self.parse_expr()?;
+                    self.expect("]")?;
+                    return Ok(RcExpr::Index(name, Box::new(index)));
+                }
+                if !self.accept("(") {
+                    return Ok(RcExpr::Var(name));
+                }
+                let mut args = Vec::new();
+                while !self.accept(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.parse_expr()?);
+                }
+                Ok(RcExpr::Call(name, args))
+            }
+            token => Err(self.error(&format!("Expected an expression, found {}", Self::describe(&token))))
+        }
+    }
+
+    fn peek(&self) -> &RcToken {
+        &self.lexemes[self.pos].token
+    }
+
+    fn line(&self) -> u32 {
+        self.lexemes[self.pos].line_number
+    }
+
+    fn advance(&mut self) -> RcToken {
+        let token = self.lexemes[self.pos].token.clone();
+        if token != RcToken::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn accept(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            RcToken::Symbol(s) if *s == symbol => {
+                self.pos += 1;
+                true
+            }
+            _ => false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), RcError> {
+        match self.accept(symbol) {
+            true => Ok(()),
+            false => Err(self.error(&format!("Expected {}, found {}", symbol, Self::describe(self.peek()))))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, RcError> {
+        match self.advance() {
+            RcToken::Ident(name) => Ok(name),
+            token => Err(self.error(&format!("Expected a name, found {}", Self::describe(&token))))
+        }
+    }
+
+    fn error(&self, message: &str) -> RcError {
+        RcError::new(self.line(), message)
+    }
+
+    fn describe(token: &RcToken) -> String {
+        match token {
+            RcToken::Number(value) => value.to_string(),
+            RcToken::Ident(name) => name.clone(),
+            RcToken::Symbol(symbol) => symbol.to_string(),
+            RcToken::End => "the end of the file".to_string()
+        }
+    }
+
+}
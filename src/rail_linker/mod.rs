@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::rail_linker::rail_object::RailObject;
+
+pub mod rail_object;
+
+pub struct LinkResult {
+    pub binary: Vec<u8>,
+    pub map: String
+}
+
+pub trait RailObjectLinkerTrait {
+    fn link(&self, objects: &[RailObject]) -> Result<LinkResult, String>;
+}
+
+pub struct RailObjectLinker { }
+
+impl RailObjectLinkerTrait for RailObjectLinker {
+
+        // objects with an ORG go there, the others follow the previous object. An object with a BANK
+        // goes into that program bank, the others into the bank of the previous object. The image
+        // holds the banks one after the other, 256 bytes each.
+    fn link(&self, objects: &[RailObject]) -> Result<LinkResult, String> {
+        let bases = Self::place(objects)?;
+
+        let mut globals: HashMap<&str, ((u8, u8), &str)> = HashMap::new();
+        for (object, (bank, base)) in objects.iter().zip(&bases) {
+            for symbol in object.symbols.iter().filter(|symbol| symbol.is_global) {
+                let address = (*bank, base.wrapping_add(symbol.offset));
+                if let Some((_, other)) = globals.insert(&symbol.name, (address, &object.name)) {
+                    return Err(format!("Global {} is defined in both {} and {}", symbol.name, other, object.name));
+                }
+            }
+        }
+
+        let size = objects.iter().zip(&bases)
+            .map(|(object, (bank, base))| Self::get_image_offset(*bank, *base) + object.code.len())
+            .max().unwrap_or(0);
+        let mut binary = vec![0u8; size];
+        for (object, (bank, base)) in objects.iter().zip(&bases) {
+            let mut code = object.code.clone();
+            for relocation in &object.relocations {
+                let (symbol_bank, address) = match &relocation.symbol {
+                    None => (*bank, *base),
+                    Some(name) => match globals.get(name.as_str()) {
+                        Some((address, _)) => *address,
+                        None => return Err(format!("Undefined external {} in {}", name, object.name))
+                    }
+                };
+                let value = if relocation.is_bank { symbol_bank } else { address };
+                let byte = code.get_mut(relocation.offset)
+                    .ok_or(format!("Relocation outside the code of {}", object.name))?;
+                *byte = byte.wrapping_add(value);
+            }
+            let start = Self::get_image_offset(*bank, *base);
+            binary[start..start + code.len()].copy_from_slice(&code);
+        }
+
+        Ok(LinkResult { binary, map: Self::build_map(objects, &bases) })
+    }
+
+}
+
+impl Default for RailObjectLinker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailObjectLinker {
+
+    pub fn new() -> Self {
+        Self {}
+    }
+
+        // the bank and the address in it of each object
+    fn place(objects: &[RailObject]) -> Result<Vec<(u8, u8)>, String> {
+        let mut bases: Vec<(u8, u8)> = Vec::new();
+        let mut next = (0u8, 0usize);
+        for (index, object) in objects.iter().enumerate() {
+            let bank = object.bank.unwrap_or(next.0);
+            if bank != next.0 {
+                next = (bank, 0);
+            }
+            let base = object.org.map(|org| org as usize).unwrap_or(next.1);
+            let end = base + object.code.len();
+            if end > 256 {
+                return Err(format!("{} does not fit in the program space, it ends at {}",
+                                   object.name, Self::format_address(bank, end)));
+            }
+            for (other, (other_bank, other_base)) in objects.iter().zip(&bases).take(index) {
+                let other_end = *other_base as usize + other.code.len();
+                if *other_bank == bank && base < other_end && (*other_base as usize) < end {
+                    return Err(format!("{} overlaps {} at {}", object.name, other.name,
+                                       Self::format_address(bank, base.max(*other_base as usize))));
+                }
+            }
+            bases.push((bank, base as u8));
+            next = (bank, end);
+        }
+        Ok(bases)
+    }
+
+    fn get_image_offset(bank: u8, base: u8) -> usize {
+        bank as usize * 256 + base as usize
+    }
+
+        // bank 0 addresses look like they did before there were banks
+    fn format_address(bank: u8, address: usize) -> String {
+        match bank {
+            0 => format!("0x{:02X}", address),
+            _ => format!("{}:0x{:02X}", bank, address)
+        }
+    }
+
+    fn build_map(objects: &[RailObject], bases: &[(u8, u8)]) -> String {
+        let mut res = String::from("Objects:\n");
+        for (object, (bank, base)) in objects.iter().zip(bases) {
+            let end = *base as usize + object.code.len();
+            res.push_str(&format!("  {}-0x{:02X}  {} bytes  {}\n", Self::format_address(*bank, *base as usize),
+                                  end.saturating_sub(1), object.code.len(), object.name));
+        }
+
+        let mut symbols: Vec<((u8, u8), &str, bool, &str)> = objects.iter().zip(bases)
+            .flat_map(|(object, (bank, base))| object.symbols.iter()
+                .map(move |symbol| ((*bank, base.wrapping_add(symbol.offset)), symbol.name.as_str(), symbol.is_global, object.name.as_str())))
+            .collect();
+        symbols.sort();
+        res.push_str("\nSymbols:\n");
+        for ((bank, address), name, is_global, object) in symbols {
+            let scope = if is_global { "global" } else { "local " };
+            res.push_str(&format!("  {}  {}  {}  ({})\n", Self::format_address(bank, address as usize), scope, name, object));
+        }
+        res
+    }
+
+}
@@ -0,0 +1,124 @@
+use crate::rail_assembler::rasm_program::{RasmProgram, RasmRelocation};
+
+const HEADER: &str = "RAILOBJ 1";
+
+#[derive(Clone)]
+pub struct RailSymbol {
+    pub name: String,
+    pub offset: u8,         // relative to the start of the object code
+    pub is_global: bool
+}
+
+    // assembled code that still has to be placed. Label bytes are relative to the start of the code,
+    // and the relocations list every byte the linker has to fix up.
+#[derive(Clone)]
+pub struct RailObject {
+    pub name: String,
+    pub org: Option<u8>,
+    pub bank: Option<u8>,
+    pub code: Vec<u8>,
+    pub symbols: Vec<RailSymbol>,
+    pub externs: Vec<String>,
+    pub relocations: Vec<RasmRelocation>
+}
+
+impl RailObject {
+
+    pub fn from_program(name: &str, program: &RasmProgram) -> Self {
+        Self {
+            name: name.to_string(),
+            org: program.org,
+            bank: program.bank,
+            code: program.binary.clone(),
+            symbols: program.labels.iter()
+                .map(|label| RailSymbol { name: label.name.to_string(), offset: label.address, is_global: label.is_global })
+                .collect(),
+            externs: program.externs.iter().map(|ext| ext.name.to_string()).collect(),
+            relocations: program.relocations.clone()
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut res = format!("{}\nNAME {}\n", HEADER, self.name);
+        if let Some(org) = self.org {
+            res.push_str(&format!("ORG 0x{:02X}\n", org));
+        }
+        if let Some(bank) = self.bank {
+            res.push_str(&format!("BANK 0x{:02X}\n", bank));
+        }
+        for chunk in self.code.chunks(4) {
+            let bytes: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            res.push_str(&format!("CODE {}\n", bytes.join(" ")));
+        }
+        for symbol in &self.symbols {
+            let kind = if symbol.is_global { "GLOBAL" } else { "LOCAL" };
+            res.push_str(&format!("{} {} 0x{:02X}\n", kind, symbol.name, symbol.offset));
+        }
+        for name in &self.externs {
+            res.push_str(&format!("EXTERN {}\n", name));
+        }
+        for relocation in &self.relocations {
+            let kind = if relocation.is_bank { "BANKREL" } else { "RELOC" };
+            match &relocation.symbol {
+                Some(name) => res.push_str(&format!("{} 0x{:02X} {}\n", kind, relocation.offset, name)),
+                None => res.push_str(&format!("{} 0x{:02X}\n", kind, relocation.offset))
+            }
+        }
+        res
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        match lines.next() {
+            Some((_, line)) if line.trim() == HEADER => { }
+            _ => return Err(format!("Not a Rail object file, expected a '{}' header", HEADER))
+        }
+
+        let mut object = Self {
+            name: String::new(), org: None, bank: None, code: Vec::new(),
+            symbols: Vec::new(), externs: Vec::new(), relocations: Vec::new()
+        };
+        for (index, line) in lines {
+            let error = |message: &str| format!("Object line {}: {}", index + 1, message);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match (parts[0], parts.len()) {
+                ("NAME", 2) => object.name = parts[1].to_string(),
+                ("ORG", 2) => object.org = Some(Self::parse_num(parts[1]).map_err(|e| error(&e))?),
+                ("BANK", 2) => object.bank = Some(Self::parse_num(parts[1]).map_err(|e| error(&e))?),
+                ("CODE", _) => {
+                    for byte in &parts[1..] {
+                        object.code.push(u8::from_str_radix(byte, 16).map_err(|_| error("Invalid code byte"))?);
+                    }
+                }
+                ("GLOBAL", 3) | ("LOCAL", 3) => object.symbols.push(RailSymbol {
+                    name: parts[1].to_string(),
+                    offset: Self::parse_num(parts[2]).map_err(|e| error(&e))?,
+                    is_global: parts[0] == "GLOBAL"
+                }),
+                ("EXTERN", 2) => object.externs.push(parts[1].to_string()),
+                ("RELOC", 2) | ("RELOC", 3) | ("BANKREL", 2) | ("BANKREL", 3) => {
+                    let offset = Self::parse_num(parts[1]).map_err(|e| error(&e))? as usize;
+                    if offset >= object.code.len() {
+                        return Err(error("Relocation points outside the code"));
+                    }
+                    object.relocations.push(RasmRelocation {
+                        offset,
+                        symbol: parts.get(2).map(|name| name.to_string()),
+                        is_bank: parts[0] == "BANKREL"
+                    });
+                }
+                _ => return Err(error(&format!("Unknown record '{}'", line.trim())))
+            }
+        }
+        Ok(object)
+    }
+
+    fn parse_num(text: &str) -> Result<u8, String> {
+        let res = match text.strip_prefix("0x") {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => text.parse::<u8>()
+        };
+        res.map_err(|_| format!("Invalid number {}", text))
+    }
+
+}
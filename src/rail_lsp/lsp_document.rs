@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LspSymbolKind {
+    Label, Const
+}
+
+    // a word of the source. Columns are in UTF-16 code units, as LSP positions are.
+#[derive(Clone)]
+pub struct LspToken {
+    pub line: u32,
+    pub start: u32,
+    pub end: u32,
+    pub text: String
+}
+
+pub struct LspDocument {
+    pub text: String,
+    tokens: Vec<LspToken>,
+    definitions: HashMap<String, (LspSymbolKind, LspToken)>
+}
+
+impl LspDocument {
+
+    pub fn new(text: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut definitions = HashMap::new();
+
+        for (line_number, line) in text.split('\n').enumerate() {
+            let code = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line
+            };
+            let line_tokens = Self::tokenize(line_number as u32, code);
+            if line_tokens.len() >= 2 {
+                let kind = match line_tokens[0].text.as_str() {
+                    "LABEL" | "PROC" => Some(LspSymbolKind::Label),
+                    "CONST" => Some(LspSymbolKind::Const),
+                    _ => None
+                };
+                if let Some(kind) = kind {
+                    definitions.entry(line_tokens[1].text.to_string())
+                        .or_insert((kind, line_tokens[1].clone()));
+                }
+            }
+            tokens.extend(line_tokens);
+        }
+
+        Self { text: text.to_string(), tokens, definitions }
+    }
+
+    fn is_separator(c: char) -> bool {
+        c.is_whitespace() || c == '+' || c == '*'
+    }
+
+    fn tokenize(line: u32, code: &str) -> Vec<LspToken> {
+        let mut tokens = Vec::new();
+        let mut column = 0;
+        let mut current: Option<(u32, String)> = None;
+        for c in code.chars() {
+            if Self::is_separator(c) {
+                if let Some((start, text)) = current.take() {
+                    tokens.push(LspToken { line, start, end: column, text: text.to_uppercase() });
+                }
+            }
+            else {
+                current.get_or_insert((column, String::new())).1.push(c);
+            }
+            column += c.len_utf16() as u32;
+        }
+        if let Some((start, text)) = current {
+            tokens.push(LspToken { line, start, end: column, text: text.to_uppercase() });
+        }
+
+        tokens
+    }
+
+    pub fn get_line_length(&self, line: u32) -> u32 {
+        self.text.split('\n').nth(line as usize)
+            .map(|l| l.trim_end_matches('\r').encode_utf16().count() as u32)
+            .unwrap_or(0)
+    }
+
+    pub fn get_token_at(&self, line: u32, character: u32) -> Option<&LspToken> {
+        self.tokens.iter()
+            .find(|token| token.line == line && token.start <= character && character <= token.end)
+    }
+
+    pub fn get_token_after(&self, token: &LspToken) -> Option<&LspToken> {
+        self.tokens.iter()
+            .find(|next| next.line == token.line && next.start >= token.end)
+    }
+
+    pub fn get_definition(&self, name: &str) -> Option<(LspSymbolKind, &LspToken)> {
+        self.definitions.get(name).map(|(kind, token)| (*kind, token))
+    }
+
+    pub fn get_definitions(&self) -> Vec<(&String, LspSymbolKind)> {
+        let mut res: Vec<(&String, LspSymbolKind)> = self.definitions.iter()
+            .map(|(name, (kind, _))| (name, *kind))
+            .collect();
+        res.sort();
+        res
+    }
+
+        // every occurrence of the name, its definition included
+    pub fn get_occurrences(&self, name: &str) -> Vec<&LspToken> {
+        self.tokens.iter().filter(|token| token.text == name).collect()
+    }
+
+}
@@ -0,0 +1,36 @@
+use std::io::{BufRead, Error, ErrorKind, Write};
+
+use serde_json::Value;
+
+    // reads one "Content-Length" framed JSON-RPC message. Returns None at end of input, and the
+    // parse error of a body that is not JSON, which leaves the reader at the next message.
+pub fn read_message<R: BufRead>(reader: &mut R) -> std::io::Result<Option<serde_json::Result<Value>>> {
+    let mut content_length: Option<usize> = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;   // stray blank line between messages
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            content_length = Some(value.trim().parse::<usize>()
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid Content-Length header"))?);
+        }
+    }
+
+    let mut body = vec![0u8; content_length.unwrap()];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::rail_analysis::rail_linter::{RailLinter, RailLinterTrait};
+use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+use crate::rail_assembler::rasm_dictionary::RasmDictionary;
+use crate::rail_lsp::lsp_document::{LspDocument, LspSymbolKind, LspToken};
+use crate::rail_lsp::lsp_transport::{read_message, write_message};
+use crate::rail_system::rail_isa::IsaExtension;
+
+pub mod lsp_document;
+pub mod lsp_transport;
+
+    // LSP constants
+const SYNC_FULL: u8 = 1;
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_KEYWORD: u8 = 14;
+const COMPLETION_REFERENCE: u8 = 18;
+const COMPLETION_CONSTANT: u8 = 21;
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+pub trait RailLanguageServerTrait {
+    fn run<R: BufRead, W: Write>(&mut self, reader: &mut R, writer: &mut W) -> std::io::Result<()>;
+    fn handle_message(&mut self, message: &Value) -> Vec<Value>;
+}
+
+pub struct RailLanguageServer {
+    documents: HashMap<String, LspDocument>,
+    extensions: Vec<IsaExtension>,     // enabled for every document, on top of its # &rail-ext- headers
+    is_shutdown: bool,
+    is_exited: bool
+}
+
+impl RailLanguageServerTrait for RailLanguageServer {
+
+    fn run<R: BufRead, W: Write>(&mut self, reader: &mut R, writer: &mut W) -> std::io::Result<()> {
+        while !self.is_exited {
+            let message = match read_message(reader)? {
+                Some(Ok(message)) => message,
+                Some(Err(e)) => {
+                        // a body that is not JSON has no id to answer to, the next message may still be fine
+                    write_message(writer, &json!({
+                        "jsonrpc": "2.0", "id": Value::Null,
+                        "error": { "code": PARSE_ERROR, "message": e.to_string() }
+                    }))?;
+                    continue;
+                }
+                None => break
+            };
+            for out in self.handle_message(&message) {
+                write_message(writer, &out)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_message(&mut self, message: &Value) -> Vec<Value> {
+        if message.get("method").is_none() {
+            return Vec::new();      // a response from the client, the server sends no requests to answer
+        }
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => Ok(Self::get_capabilities()),
+            "shutdown" => {
+                self.is_shutdown = true;
+                Ok(Value::Null)
+            }
+            "exit" => {
+                self.is_exited = true;
+                return Vec::new();
+            }
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                return self.update_document(uri, text);
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                    // full sync, so the last change holds the whole text
+                let text = params["contentChanges"].as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                    .unwrap_or("");
+                return self.update_document(uri, text);
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                self.documents.remove(uri);
+                return vec![Self::make_diagnostics_notification(uri, Vec::new())];
+            }
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/definition" => Ok(self.definition(params)),
+            "textDocument/references" => Ok(self.references(params)),
+            "textDocument/completion" => Ok(self.completion(params)),
+            "textDocument/rename" => self.rename(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Method {} not supported", method)))
+        };
+
+        match id {
+            None => Vec::new(),     // notifications get no answer
+            Some(id) => match result {
+                Ok(result) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+                Err((code, message)) => vec![json!({
+                    "jsonrpc": "2.0", "id": id,
+                    "error": { "code": code, "message": message }
+                })]
+            }
+        }
+    }
+
+}
+
+impl Default for RailLanguageServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailLanguageServer {
+
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            extensions: Vec::new(),
+            is_shutdown: false,
+            is_exited: false
+        }
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.is_shutdown
+    }
+
+        // like --ext when assembling, for documents without the # &rail-ext- header
+    pub fn enable_extension(&mut self, extension: IsaExtension) {
+        if !self.extensions.contains(&extension) {
+            self.extensions.push(extension);
+        }
+    }
+
+    fn create_assembler(&self) -> RailAssembler {
+        let mut rail_assembler = RailAssembler::new();
+        for extension in &self.extensions {
+            rail_assembler.enable_extension(*extension);
+        }
+        rail_assembler
+    }
+
+    fn get_capabilities() -> Value {
+        json!({
+            "capabilities": {
+                "textDocumentSync": SYNC_FULL,
+                "hoverProvider": true,
+                "definitionProvider": true,
+                "referencesProvider": true,
+                "completionProvider": { "triggerCharacters": ["+", " "] },
+                "renameProvider": true
+            },
+            "serverInfo": { "name": "rail-arch-rust" }
+        })
+    }
+
+    fn update_document(&mut self, uri: &str, text: &str) -> Vec<Value> {
+        let document = LspDocument::new(text);
+        let diagnostics = self.get_diagnostics(&document);
+        self.documents.insert(uri.to_string(), document);
+        vec![Self::make_diagnostics_notification(uri, diagnostics)]
+    }
+
+    fn make_diagnostics_notification(uri: &str, diagnostics: Vec<Value>) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics }
+        })
+    }
+
+    fn get_diagnostics(&self, document: &LspDocument) -> Vec<Value> {
+        let make = |line_number: u32, severity: u8, message: &str| {
+            let line = line_number.saturating_sub(1);
+            json!({
+                "range": Self::make_range(line, 0, document.get_line_length(line)),
+                "severity": severity,
+                "source": "rail",
+                "message": message
+            })
+        };
+
+        match self.create_assembler().try_assemble_program(&document.text) {
+            Err(e) => vec![make(e.line_number, SEVERITY_ERROR, &e.message)],
+            Ok(program) => RailLinter::new().lint_program(&program).iter()
+                .map(|warning| make(warning.line_number.unwrap_or(1), SEVERITY_WARNING,
+                                    &format!("{} [{}]", warning.message, warning.kind.get_name())))
+                .collect()
+        }
+    }
+
+    fn make_range(line: u32, start: u32, end: u32) -> Value {
+        json!({
+            "start": { "line": line, "character": start },
+            "end": { "line": line, "character": end }
+        })
+    }
+
+    fn make_location(uri: &str, token: &LspToken) -> Value {
+        json!({ "uri": uri, "range": Self::make_range(token.line, token.start, token.end) })
+    }
+
+    fn get_target<'a>(&'a self, params: &Value) -> Option<(&'a str, &'a LspDocument, &'a LspToken)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let (uri, document) = self.documents.get_key_value(uri)?;
+        let line = params["position"]["line"].as_u64()? as u32;
+        let character = params["position"]["character"].as_u64()? as u32;
+        let token = document.get_token_at(line, character)?;
+        Some((uri, document, token))
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let (_, document, token) = match self.get_target(params) {
+            Some(target) => target,
+            None => return Value::Null
+        };
+        let name = token.text.as_str();
+
+        let contents = if let Some((kind, _)) = document.get_definition(name) {
+            match kind {
+                LspSymbolKind::Label => {
+                    let address = self.create_assembler().try_assemble_program(&document.text).ok()
+                        .and_then(|program| program.labels.iter()
+                            .find(|label| label.name == name)
+                            .map(|label| label.address));
+                    match address {
+                        Some(address) => format!("**LABEL {}**\n\nInstruction address `0x{:02X}`", name, address),
+                        None => format!("**LABEL {}**", name)
+                    }
+                }
+                LspSymbolKind::Const => {
+                    let value = document.get_definition(name)
+                        .and_then(|(_, def)| document.get_token_after(def))
+                        .map(|value| value.text.to_string())
+                        .unwrap_or_default();
+                    format!("**CONST {}** = `{}`", name, value)
+                }
+            }
+        }
+        else if let (Ok(code), Some(description)) = (RasmDictionary::translate(name), RasmDictionary::describe(name)) {
+            format!("**{}** `0x{:02X}`\n\n{}", name, code, description)
+        }
+        else {
+            return Value::Null
+        };
+
+        json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": Self::make_range(token.line, token.start, token.end)
+        })
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        match self.get_target(params) {
+            Some((uri, document, token)) => match document.get_definition(&token.text) {
+                Some((_, def)) => Self::make_location(uri, def),
+                None => Value::Null
+            },
+            None => Value::Null
+        }
+    }
+
+    fn references(&self, params: &Value) -> Value {
+        let (uri, document, token) = match self.get_target(params) {
+            Some(target) => target,
+            None => return Value::Null
+        };
+        let definition = match document.get_definition(&token.text) {
+            Some((_, def)) => def,
+            None => return Value::Null
+        };
+        let include_declaration = params["context"]["includeDeclaration"].as_bool().unwrap_or(true);
+
+        let locations: Vec<Value> = document.get_occurrences(&token.text).into_iter()
+            .filter(|occurrence| include_declaration
+                || occurrence.line != definition.line || occurrence.start != definition.start)
+            .map(|occurrence| Self::make_location(uri, occurrence))
+            .collect();
+        Value::Array(locations)
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let mut items = Vec::new();
+        for mnemonic in RasmDictionary::MNEMONICS.iter().chain(["LABEL", "CONST"].iter()) {
+            items.push(json!({
+                "label": mnemonic,
+                "kind": COMPLETION_KEYWORD,
+                "detail": RasmDictionary::describe(mnemonic).unwrap_or("Assembler directive.")
+            }));
+        }
+        for register in RasmDictionary::REGISTERS {
+            items.push(json!({
+                "label": register,
+                "kind": COMPLETION_VARIABLE,
+                "detail": RasmDictionary::describe(register).unwrap_or("")
+            }));
+        }
+
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        if let Some(document) = self.documents.get(uri) {
+            for (name, kind) in document.get_definitions() {
+                let (kind, detail) = match kind {
+                    LspSymbolKind::Label => (COMPLETION_REFERENCE, "Label"),
+                    LspSymbolKind::Const => (COMPLETION_CONSTANT, "Const")
+                };
+                items.push(json!({ "label": name, "kind": kind, "detail": detail }));
+            }
+        }
+
+        Value::Array(items)
+    }
+
+    fn rename(&self, params: &Value) -> Result<Value, (i32, String)> {
+        let (uri, document, token) = match self.get_target(params) {
+            Some(target) => target,
+            None => return Ok(Value::Null)
+        };
+        if document.get_definition(&token.text).is_none() {
+            return Err((INVALID_PARAMS, format!("{} is not a label or const", token.text)));
+        }
+        let new_name = params["newName"].as_str().unwrap_or("").to_uppercase();
+        let is_valid = !new_name.is_empty()
+            && new_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && RasmDictionary::translate(&new_name).is_err()
+            && document.get_definition(&new_name).is_none();
+        if !is_valid {
+            return Err((INVALID_PARAMS, format!("{} can not be used as a name", new_name)));
+        }
+
+        let edits: Vec<Value> = document.get_occurrences(&token.text).into_iter()
+            .map(|occurrence| json!({
+                "range": Self::make_range(occurrence.line, occurrence.start, occurrence.end),
+                "newText": new_name
+            }))
+            .collect();
+        Ok(json!({ "changes": { uri: edits } }))
+    }
+
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+use crate::rail_analysis::rail_flow::{decode_program, FlowInstruction, FlowKind, CNT_REGISTER, IO_REGISTER};
+use crate::rail_assembler::rasm_program::RasmProgram;
+use crate::rail_system::rail_instruction::RailInstruction;
+use crate::rail_system::rail_isa::{IsaExtension, IsaOperand};
+use crate::rail_system::rail_subsystem::RailSubSystem;
+
+pub mod rail_superoptimizer;
+
+    // ADD+IM1+IM2, the encoding of MOV with an immediate value
+const MOV_IMMEDIATE: u8 = 0xC0;
+
+#[derive(Default)]
+pub struct OptimizerStats {
+    pub removed_moves: u32,
+    pub folded_constants: u32,
+    pub threaded_jumps: u32,
+    pub removed_dead_code: u32
+}
+
+impl OptimizerStats {
+    pub fn get_removed_instructions(&self) -> u32 {
+        self.removed_moves + self.removed_dead_code
+    }
+}
+
+pub trait RailOptimizerTrait {
+    fn optimize(&self, program: &RasmProgram) -> (RasmProgram, OptimizerStats);
+}
+
+pub struct RailOptimizer { }
+
+impl RailOptimizerTrait for RailOptimizer {
+
+    fn optimize(&self, program: &RasmProgram) -> (RasmProgram, OptimizerStats) {
+        let mut program = program.clone();
+        let mut stats = OptimizerStats::default();
+        if !program.binary.len().is_multiple_of(4) {
+            return (program, stats);     // not whole instructions, leave it alone
+        }
+
+        loop {
+            let mut changed = self.thread_jumps(&mut program, &mut stats);
+            if !Self::has_computed_flow(&program) && program.org.is_none() && program.bank.is_none() {
+                    // removing code moves addresses, which is only safe when every target is known
+                let removed = self.find_removable(&program, &mut stats);
+                if removed.iter().any(|r| *r) {
+                    Self::remove_instructions(&mut program, &removed);
+                    changed = true;
+                }
+            }
+            changed |= self.fold_constants(&mut program, &mut stats);
+            if !changed {
+                break;
+            }
+        }
+
+        (program, stats)
+    }
+
+}
+
+impl Default for RailOptimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailOptimizer {
+
+    pub fn new() -> Self {
+        Self {}
+    }
+
+        // code in another bank can also call or jump back to any address of this one
+    fn has_computed_flow(program: &RasmProgram) -> bool {
+        decode_program(&program.binary).iter()
+            .any(|instr| matches!(instr.kind, FlowKind::IndirectJump | FlowKind::IndirectCall
+                                              | FlowKind::FarCall(_, _) | FlowKind::FarJump(_, _)))
+    }
+
+        // with the flags extension every ALU instruction also sets the flags, so rewriting or
+        // removing one can change what a later ADC or IF_C sees. An interrupt handler can change
+        // registers between any two instructions, so no value is ever known.
+    fn uses_extension(instructions: &[FlowInstruction], extension: IsaExtension) -> bool {
+        instructions.iter().any(|instr| instr.get_isa_entry()
+            .is_some_and(|entry| entry.extension == Some(extension)))
+    }
+
+    fn get_target(instruction: &FlowInstruction) -> Option<u8> {
+        match instruction.kind {
+            FlowKind::Jump(target) | FlowKind::Branch(target) | FlowKind::Call(target) => Some(target),
+            _ => None
+        }
+    }
+
+    fn set_target(program: &mut RasmProgram, instruction: &FlowInstruction, target: u8) {
+            // the target sits in whichever operand the ISA calls the address
+        let position = instruction.get_isa_entry()
+            .and_then(|entry| entry.operands.iter().position(|operand| *operand == IsaOperand::Address))
+            .unwrap_or(2);
+        program.binary[instruction.address as usize + 1 + position] = target;
+    }
+
+        // a jump to an unconditional jump can go to its final destination directly
+    fn thread_jumps(&self, program: &mut RasmProgram, stats: &mut OptimizerStats) -> bool {
+        let instructions = decode_program(&program.binary);
+        let mut changed = false;
+        for instruction in &instructions {
+            let original = match Self::get_target(instruction) {
+                Some(target) => target,
+                None => continue
+            };
+            let mut target = original;
+            let mut hops = 0;
+            while let Some(FlowKind::Jump(next)) = Self::get_instruction_at(&instructions, target).map(|i| i.kind) {
+                if next == target || hops >= instructions.len() {
+                    break;      // jump to itself, or a cycle of jumps
+                }
+                target = next;
+                hops += 1;
+            }
+            if target != original {
+                Self::set_target(program, instruction, target);
+                stats.threaded_jumps += 1;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn get_instruction_at(instructions: &[FlowInstruction], address: u8) -> Option<&FlowInstruction> {
+        if !address.is_multiple_of(4) {
+            return None;
+        }
+        instructions.get(address as usize / 4)
+    }
+
+        // instructions that can be entered from somewhere else than the previous one
+    fn get_leaders(program: &RasmProgram, instructions: &[FlowInstruction]) -> Vec<bool> {
+        let mut leaders = vec![false; instructions.len()];
+        if let Some(first) = leaders.first_mut() {
+            *first = true;
+        }
+        let mut mark = |address: u8| {
+            if address.is_multiple_of(4) && (address as usize / 4) < leaders.len() {
+                leaders[address as usize / 4] = true;
+            }
+        };
+        for instruction in instructions {
+            if let Some(target) = Self::get_target(instruction) {
+                mark(target);
+            }
+            if matches!(instruction.kind, FlowKind::Call(_) | FlowKind::IndirectCall | FlowKind::FarCall(_, _))
+                && instruction.next_address() <= 0xFF {
+                mark(instruction.next_address() as u8);
+            }
+        }
+        for label in &program.labels {
+            if label.references > 0 {
+                mark(label.address);
+            }
+        }
+        leaders
+    }
+
+    fn evaluate(instruction: RailInstruction, arg1: u8, arg2: u8) -> Option<u8> {
+        let res = match instruction {
+            RailInstruction::Add => arg1.wrapping_add(arg2),
+            RailInstruction::Sub => arg1.wrapping_sub(arg2),
+            RailInstruction::And => arg1 & arg2,
+            RailInstruction::Or => arg1 | arg2,
+            RailInstruction::Not => !arg1,
+            RailInstruction::Xor => arg1 ^ arg2,
+            RailInstruction::Shl => arg1.checked_shl(arg2 as u32)?,
+            RailInstruction::Shr => arg1.checked_shr(arg2 as u32)?,
+            _ => return None
+        };
+        Some(res)
+    }
+
+        // tracks registers holding known values inside each basic block, and turns ALU operations
+        // on known values into a single MOV of the result
+    fn fold_constants(&self, program: &mut RasmProgram, stats: &mut OptimizerStats) -> bool {
+        let instructions = decode_program(&program.binary);
+        if Self::uses_extension(&instructions, IsaExtension::Flags) || Self::uses_extension(&instructions, IsaExtension::Interrupts) {
+            return false;
+        }
+        let mut leaders = Self::get_leaders(program, &instructions);
+        if Self::has_computed_flow(program) {
+            leaders.fill(true);     // a computed jump can land anywhere
+        }
+        let mut known: HashMap<u8, u8> = HashMap::new();
+        let mut changed = false;
+
+        for (index, instruction) in instructions.iter().enumerate() {
+            if leaders[index] {
+                known.clear();
+            }
+            let block = &instruction.block;
+            let value_of = |arg: u8, immediate: bool| if immediate { Some(arg) } else { known.get(&arg).copied() };
+
+            let mut written_value = None;
+            if instruction.get_subsystem() == RailSubSystem::Alu {
+                let arg1 = value_of(block.arg1, block.is_arg1_immediate());
+                let arg2 = if instruction.instruction == RailInstruction::Not { Some(0) }
+                           else { value_of(block.arg2, block.is_arg2_immediate()) };
+                if let (Some(arg1), Some(arg2)) = (arg1, arg2) {
+                    written_value = Self::evaluate(instruction.instruction, arg1, arg2);
+                }
+            }
+
+            match (instruction.get_written_register(), written_value) {
+                (Some(reg), Some(value)) if reg != CNT_REGISTER => {
+                    let folded = [MOV_IMMEDIATE, value, 0, reg];
+                    let base = instruction.address as usize;
+                    if program.binary[base..base + 4] != folded {
+                        program.binary[base..base + 4].copy_from_slice(&folded);
+                        stats.folded_constants += 1;
+                        changed = true;
+                    }
+                    if reg != IO_REGISTER {     // IO can change from outside
+                        known.insert(reg, value);
+                    }
+                }
+                (Some(reg), _) => { known.remove(&reg); }
+                (None, _) => { }
+            }
+            if matches!(instruction.kind, FlowKind::Call(_) | FlowKind::IndirectCall | FlowKind::FarCall(_, _)) {
+                known.clear();      // the callee can change anything
+            }
+        }
+        changed
+    }
+
+    fn is_noop_move(instruction: &FlowInstruction) -> bool {
+        let block = &instruction.block;
+        if instruction.get_subsystem() != RailSubSystem::Alu || block.is_arg1_immediate()
+            || block.arg1 != block.result || block.result == IO_REGISTER || block.result == CNT_REGISTER {
+            return false;
+        }
+        let identity = match instruction.instruction {
+            RailInstruction::Add | RailInstruction::Sub | RailInstruction::Or | RailInstruction::Xor |
+            RailInstruction::Shl | RailInstruction::Shr => 0,
+            RailInstruction::And => 0xFF,
+            _ => return false
+        };
+        block.is_arg2_immediate() && block.arg2 == identity
+    }
+
+    fn find_removable(&self, program: &RasmProgram, stats: &mut OptimizerStats) -> Vec<bool> {
+        let instructions = decode_program(&program.binary);
+        let leaders = Self::get_leaders(program, &instructions);
+        let uses_flags = Self::uses_extension(&instructions, IsaExtension::Flags);
+        let mut removed = vec![false; instructions.len()];
+        let mut is_dead = false;
+
+        for (index, instruction) in instructions.iter().enumerate() {
+            if leaders[index] {
+                is_dead = false;
+            }
+            if is_dead {
+                removed[index] = true;
+                stats.removed_dead_code += 1;
+                continue;
+            }
+            let jumps_to_next = instruction.kind == FlowKind::Jump(instruction.address.wrapping_add(4))
+                && instruction.get_subsystem() == RailSubSystem::CU;
+            if (Self::is_noop_move(instruction) && !uses_flags) || jumps_to_next {
+                removed[index] = true;
+                stats.removed_moves += 1;
+                continue;
+            }
+            is_dead = matches!(instruction.kind, FlowKind::Jump(_) | FlowKind::Return | FlowKind::Halt);
+        }
+        removed
+    }
+
+    fn remove_instructions(program: &mut RasmProgram, removed: &[bool]) {
+            // old address -> new address. Removed instructions map to the next kept one.
+        let mut new_address = vec![0u8; removed.len() + 1];
+        let mut kept = 0usize;
+        for (index, is_removed) in removed.iter().enumerate() {
+            new_address[index] = (kept * 4) as u8;
+            if !is_removed {
+                kept += 1;
+            }
+        }
+        new_address[removed.len()] = (kept * 4) as u8;
+        let remap = |address: u8| -> u8 {
+            if address.is_multiple_of(4) && (address as usize / 4) <= removed.len() {
+                new_address[address as usize / 4]
+            } else {
+                address
+            }
+        };
+
+        let instructions = decode_program(&program.binary);
+        for instruction in &instructions {
+            if let Some(target) = Self::get_target(instruction) {
+                Self::set_target(program, instruction, remap(target));
+            }
+        }
+
+        let mut binary = Vec::new();
+        let mut code_lines = Vec::new();
+        for (index, is_removed) in removed.iter().enumerate() {
+            if !is_removed {
+                binary.extend_from_slice(&program.binary[index * 4..index * 4 + 4]);
+                if let Some(line) = program.code_lines.get(index) {
+                    code_lines.push(line.clone());
+                }
+            }
+        }
+        program.binary = binary;
+        program.code_lines = code_lines;
+        for label in &mut program.labels {
+            label.address = remap(label.address);
+        }
+        let kept_before = |address: usize| removed.iter().take(address / 4).filter(|r| !**r).count();
+        for proc in &mut program.procs {
+            proc.start = (kept_before(proc.start as usize) * 4) as u8;
+            proc.end = (kept_before(proc.end as usize) * 4) as u16;
+        }
+    }
+
+}
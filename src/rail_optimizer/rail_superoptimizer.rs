@@ -0,0 +1,323 @@
+use crate::rail_system::{RailSystem, RailSystemTrait};
+use crate::rail_system::rail_isa::{self, IM1, IM2};
+
+const ADD: u8 = 0;
+const SUB: u8 = 1;
+const NOT: u8 = 4;
+const SHL: u8 = 6;
+const SHR: u8 = 7;
+const HALT: u8 = 0x0E;
+const FILTER_VECTORS: usize = 16;   // quick check every candidate has to pass before the full one
+const SAMPLED_VECTORS: usize = 4096;
+const EXHAUSTIVE_LIMIT: usize = 65536;
+const MAX_STEPS: u32 = 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SuperoptOperand {
+    Reg(u8),
+    Imm(u8)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SuperoptInstruction {
+    pub op: u8,         // ALU opcode 0-7
+    pub a: SuperoptOperand,
+    pub b: SuperoptOperand,
+    pub result: u8
+}
+
+impl SuperoptInstruction {
+
+    pub fn to_bytes(self) -> [u8; 4] {
+        let (im1, a) = Self::encode(self.a);
+        let (im2, b) = Self::encode(self.b);
+        [self.op | if im1 { IM1 } else { 0 } | if im2 { IM2 } else { 0 }, a, b, self.result]
+    }
+
+    pub fn to_asm(self) -> String {
+        let mut op = rail_isa::get_by_opcode(self.op).expect("Superoptimizer ops are ALU instructions").mnemonic.to_string();
+        let mut text = |operand: SuperoptOperand, flag: &str| match operand {
+            SuperoptOperand::Reg(register) => format!("R{}", register),
+            SuperoptOperand::Imm(value) => {
+                op.push_str(flag);
+                value.to_string()
+            }
+        };
+        let (a, b) = (text(self.a, "+IM1"), text(self.b, "+IM2"));
+        format!("{} {} {} R{}", op, a, b, self.result)
+    }
+
+    fn encode(operand: SuperoptOperand) -> (bool, u8) {
+        match operand {
+            SuperoptOperand::Reg(register) => (false, register),
+            SuperoptOperand::Imm(value) => (true, value)
+        }
+    }
+
+        // the same results as the ALU in RailSystem
+    fn apply(self, registers: &mut [u8; 16]) {
+        let value = |operand: SuperoptOperand| match operand {
+            SuperoptOperand::Reg(register) => registers[register as usize],
+            SuperoptOperand::Imm(value) => value
+        };
+        let (a, b) = (value(self.a), value(self.b));
+        registers[self.result as usize] = match self.op {
+            ADD => a.wrapping_add(b),
+            SUB => a.wrapping_sub(b),
+            2 => a & b,
+            3 => a | b,
+            NOT => !a,
+            5 => a ^ b,
+            SHL => a.checked_shl(b as u32).unwrap_or(0),
+            _ => a.checked_shr(b as u32).unwrap_or(0)
+        };
+    }
+}
+
+    // the output for the input values, in the order of the input registers
+pub type SuperoptFunction = Box<dyn Fn(&[u8]) -> u8>;
+
+pub enum SuperoptTarget {
+    Function(SuperoptFunction),
+    Reference(Vec<u8>)      // assembled instructions, run on RailSystem
+}
+
+pub struct SuperoptSpec {
+    pub inputs: Vec<u8>,        // registers holding the inputs, every other register starts at 0
+    pub output: u8,
+    pub scratch: Vec<u8>,       // registers a candidate may use for intermediate values
+    pub constants: Vec<u8>,     // immediates to try, shifts always try 1 to 7
+    pub max_length: usize,
+    pub max_results: usize,
+    target: SuperoptTarget
+}
+
+impl SuperoptSpec {
+
+    pub fn new_function(inputs: &[u8], output: u8, function: impl Fn(&[u8]) -> u8 + 'static) -> Self {
+        Self::new(inputs, output, SuperoptTarget::Function(Box::new(function)))
+    }
+
+    pub fn new_reference(inputs: &[u8], output: u8, code: &[u8]) -> Self {
+        Self::new(inputs, output, SuperoptTarget::Reference(code.to_vec()))
+    }
+
+    fn new(inputs: &[u8], output: u8, target: SuperoptTarget) -> Self {
+        let scratch = (1..8).find(|register| !inputs.contains(register) && *register != output);
+        Self {
+            inputs: inputs.to_vec(),
+            output,
+            scratch: scratch.into_iter().collect(),
+            constants: vec![1, 2, 3, 4, 8, 0x0F, 0x7F, 0x80, 0xFF],
+            max_length: 3,
+            max_results: 8,
+            target
+        }
+    }
+
+}
+
+pub struct SuperoptResult {
+    pub sequences: Vec<Vec<SuperoptInstruction>>,   // all of the same, shortest length
+    pub candidates: u64,
+    pub is_exhaustive: bool     // verified on every input, not on a sample
+}
+
+impl SuperoptResult {
+    pub fn get_length(&self) -> Option<usize> {
+        self.sequences.first().map(|sequence| sequence.len())
+    }
+}
+
+pub trait RailSuperoptimizerTrait {
+    fn search(&self, spec: &SuperoptSpec) -> SuperoptResult;
+}
+
+    // Tries every sequence of ALU instructions of increasing length and keeps the ones that
+    // compute the specification for all inputs, first in a fast model, then on RailSystem.
+pub struct RailSuperoptimizer { }
+
+struct SuperoptSearch<'a> {
+    spec: &'a SuperoptSpec,
+    vectors: Vec<Vec<u8>>,
+    expected: Vec<u8>,
+    filter: Vec<usize>,
+    sequence: Vec<SuperoptInstruction>,
+    result: SuperoptResult
+}
+
+impl RailSuperoptimizerTrait for RailSuperoptimizer {
+
+    fn search(&self, spec: &SuperoptSpec) -> SuperoptResult {
+        let (vectors, is_exhaustive) = Self::get_vectors(spec.inputs.len());
+        let expected: Vec<u8> = vectors.iter().map(|vector| match &spec.target {
+            SuperoptTarget::Function(function) => function(vector),
+            SuperoptTarget::Reference(code) => Self::run_on_system(spec, vector, code)
+        }).collect();
+            // the edges and a spread of the rest
+        let mut filter: Vec<usize> = (0..FILTER_VECTORS).map(|i| i * vectors.len() / FILTER_VECTORS + i % 7).collect();
+        filter.push(vectors.len() - 1);
+        filter.retain(|index| *index < vectors.len());
+
+        let mut search = SuperoptSearch {
+            spec, vectors, expected, filter,
+            sequence: Vec::new(),
+            result: SuperoptResult { sequences: Vec::new(), candidates: 0, is_exhaustive }
+        };
+        for length in 0..=spec.max_length {
+            let states: Vec<[u8; 16]> = search.filter.iter().map(|index| Self::initial_state(spec, &search.vectors[*index])).collect();
+            let readable = spec.inputs.iter().fold(0u16, |mask, register| mask | 1 << register);
+            search.extend(length, &states, readable);
+            if !search.result.sequences.is_empty() {
+                break;
+            }
+        }
+        search.result
+    }
+
+}
+
+impl<'a> SuperoptSearch<'a> {
+
+    fn extend(&mut self, remaining: usize, states: &[[u8; 16]], readable: u16) {
+        if self.result.sequences.len() >= self.spec.max_results {
+            return;
+        }
+        if remaining == 0 {
+            self.result.candidates += 1;
+            let output = self.spec.output as usize;
+            if states.iter().zip(&self.filter).all(|(state, index)| state[output] == self.expected[*index]) && self.verify() {
+                self.result.sequences.push(self.sequence.clone());
+            }
+            return;
+        }
+            // the last instruction has to produce the output, a shorter sequence would do otherwise
+        let targets: Vec<u8> = match remaining {
+            1 => vec![self.spec.output],
+            _ => std::iter::once(self.spec.output).chain(self.spec.scratch.iter().copied()).collect()
+        };
+        for instruction in Self::candidates(self.spec, readable, &targets) {
+            let next: Vec<[u8; 16]> = states.iter().map(|state| {
+                let mut state = *state;
+                instruction.apply(&mut state);
+                state
+            }).collect();
+            self.sequence.push(instruction);
+            self.extend(remaining - 1, &next, readable | 1 << instruction.result);
+            self.sequence.pop();
+        }
+    }
+
+    fn candidates(spec: &SuperoptSpec, readable: u16, targets: &[u8]) -> Vec<SuperoptInstruction> {
+        let registers: Vec<u8> = (0..16).filter(|register| readable & 1 << register != 0).collect();
+        let mut pairs: Vec<(u8, SuperoptOperand, SuperoptOperand)> = Vec::new();
+        for (i, a) in registers.iter().enumerate() {
+            let reg_a = SuperoptOperand::Reg(*a);
+            for b in &registers[i..] {
+                pairs.push((ADD, reg_a, SuperoptOperand::Reg(*b)));
+                if a != b {
+                        // AND, OR and XOR of a register with itself is nothing new
+                    for op in [2, 3, 5] {
+                        pairs.push((op, reg_a, SuperoptOperand::Reg(*b)));
+                    }
+                    pairs.push((SUB, reg_a, SuperoptOperand::Reg(*b)));
+                    pairs.push((SUB, SuperoptOperand::Reg(*b), reg_a));
+                }
+            }
+            for constant in &spec.constants {
+                let imm = SuperoptOperand::Imm(*constant);
+                for op in [ADD, SUB, 2, 3, 5] {
+                    pairs.push((op, reg_a, imm));
+                }
+                pairs.push((SUB, imm, reg_a));
+            }
+            pairs.push((NOT, reg_a, SuperoptOperand::Imm(0)));
+            pairs.push((SUB, SuperoptOperand::Imm(0), reg_a));     // negation
+            for amount in 1..8 {
+                pairs.push((SHL, reg_a, SuperoptOperand::Imm(amount)));
+                pairs.push((SHR, reg_a, SuperoptOperand::Imm(amount)));
+            }
+        }
+        for constant in &spec.constants {
+            pairs.push((ADD, SuperoptOperand::Imm(*constant), SuperoptOperand::Imm(0)));
+        }
+        targets.iter()
+            .flat_map(|result| pairs.iter().map(move |(op, a, b)| SuperoptInstruction { op: *op, a: *a, b: *b, result: *result }))
+            .collect()
+    }
+
+    fn verify(&self) -> bool {
+        let output = self.spec.output as usize;
+        let model_ok = self.vectors.iter().zip(&self.expected).all(|(vector, expected)| {
+            let mut state = RailSuperoptimizer::initial_state(self.spec, vector);
+            self.sequence.iter().for_each(|instruction| instruction.apply(&mut state));
+            state[output] == *expected
+        });
+        if !model_ok {
+            return false;
+        }
+        let code: Vec<u8> = self.sequence.iter().flat_map(|instruction| instruction.to_bytes()).collect();
+        self.vectors.iter().zip(&self.expected)
+            .all(|(vector, expected)| RailSuperoptimizer::run_on_system(self.spec, vector, &code) == *expected)
+    }
+
+}
+
+impl Default for RailSuperoptimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailSuperoptimizer {
+
+    pub fn new() -> Self {
+        Self { }
+    }
+
+        // every combination when there are few enough, a fixed pseudo random sample otherwise
+    fn get_vectors(input_count: usize) -> (Vec<Vec<u8>>, bool) {
+        let combinations = 256usize.checked_pow(input_count as u32).unwrap_or(usize::MAX);
+        if combinations <= EXHAUSTIVE_LIMIT {
+            let vectors = (0..combinations)
+                .map(|n| (0..input_count).map(|i| (n >> (8 * i)) as u8).collect())
+                .collect();
+            return (vectors, true);
+        }
+        let mut seed: u32 = 0x2545F491;
+        let vectors = (0..SAMPLED_VECTORS).map(|_| (0..input_count).map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        }).collect()).collect();
+        (vectors, false)
+    }
+
+    fn initial_state(spec: &SuperoptSpec, vector: &[u8]) -> [u8; 16] {
+        let mut state = [0; 16];
+        for (register, value) in spec.inputs.iter().zip(vector) {
+            state[*register as usize] = *value;
+        }
+        state
+    }
+
+    fn run_on_system(spec: &SuperoptSpec, vector: &[u8], code: &[u8]) -> u8 {
+        let mut program: Vec<u8> = spec.inputs.iter().zip(vector)
+            .flat_map(|(register, value)| [ADD | IM1 | IM2, *value, 0, *register])
+            .collect();
+        program.extend_from_slice(code);
+            // HALT writes 0 to its result, which must not be the output
+        program.extend_from_slice(&[HALT, 0, 0, if spec.output == 0 { 1 } else { 0 }]);
+        program.truncate(256);
+
+        let mut system = RailSystem::new_with_program(&program);
+        system.set_io_print(false);
+        for _i in 0..MAX_STEPS {
+            system.step();
+            if system.is_halted() { break }
+        }
+        system.get_register_value(spec.output)
+    }
+
+}
@@ -1,22 +1,40 @@
 use crate::rail_system::rail_instruction::RailInstruction;
 use crate::rail_system::rail_instruction_block::RailInstructionBlock;
+use crate::rail_system::rail_isa::{IsaExtension, FLAG_C, FLAG_V, FLAG_Z};
+use crate::rail_system::rail_peripheral::{RailPeripheralBus, RailPeripheralTrait};
 use crate::rail_system::rail_register::{BaseRailRegister, RailRegister};
 use crate::rail_system::rail_subsystem::RailSubSystem;
+use crate::rail_system::rail_timer::{RailTimer, TIMER_FIRST_PORT, TIMER_LINE};
 
 mod rail_register;
-mod rail_instruction;
-mod rail_subsystem;
-mod rail_instruction_block;
+pub mod rail_instruction;
+pub mod rail_subsystem;
+pub mod rail_instruction_block;
+pub mod rail_isa;
+pub mod rail_peripheral;
+pub mod rail_timer;
 
 pub struct RailSystem {
     registers: [RailRegister; 16],
-    ram: [u8; 256],
-    program: [u8; 256],
+    ram: Vec<[u8; 256]>,        // banks of RAM, only bank 0 without the banks extension
+    program: Vec<[u8; 256]>,    // banks of program memory, every 256 bytes of the image loaded is one
+    ram_bank: u8,
+    program_bank: u8,
     call_stack: [u8; 128],
+    call_banks: [u8; 128],      // the program bank to return to, for each call_stack entry
     call_stack_ptr: u8,
     gen_stack: [u8; 128],
     gen_stack_ptr: u8,
     ran_seed: u8,
+    flags: u8,      // C, Z and V, only kept up to date with the flags extension
+    shift_mask: bool,   // shift amounts wrap to their low 3 bits, like the reference architecture
+    unified: bool,      // RAM instructions use the program memory, von Neumann style, and ram is unused
+    interrupt_vectors: [u8; 8],     // the handler address of each line, in program bank 0
+    interrupt_mask: u8,             // lines let in, a bit per line
+    interrupt_pending: u8,          // lines raised and not yet handled
+    interrupt_flags: Option<u8>,    // the flags to restore on RETI while a handler runs
+    bus: RailPeripheralBus,
+    extensions: Vec<IsaExtension>,
 
     is_halted: bool
 }
@@ -31,15 +49,41 @@ pub trait RailSystemTrait {
     fn get_call_stack_ptr(&self) -> u8;
     fn get_gen_stack_slice(&self, start: u8, end: u8) -> &[u8];
     fn get_gen_stack_ptr(&self) -> u8;
+    fn get_flags(&self) -> u8;
+    fn get_program_bank(&self) -> u8;
+    fn get_ram_bank(&self) -> u8;
+    fn set_register_value(&mut self, reg: u8, value: u8);
+    fn set_ram_value(&mut self, address: u8, value: u8);
     fn is_halted(&self) -> bool;
     fn set_io_print(&mut self, print: bool);
+    fn set_shift_mask(&mut self, mask: bool);
+    fn set_unified_memory(&mut self, unified: bool);
+    fn is_unified_memory(&self) -> bool;
+    fn raise_interrupt(&mut self, line: u8);
+    fn attach_peripheral(&mut self, first_port: u8, line: u8, device: Box<dyn RailPeripheralTrait>);
+    fn read_port(&self, port: u8) -> u8;
+    fn get_pending_interrupts(&self) -> u8;
+    fn is_in_interrupt(&self) -> bool;
+    fn enable_extension(&mut self, extension: IsaExtension);
+    fn has_extension(&self, extension: IsaExtension) -> bool;
 
     fn load_program(&mut self, program_slice: &[u8]);
 }
 
 impl RailSystemTrait for RailSystem {
 
+        // the peripherals step first, so a line they raise is taken before the next instruction.
+        // Taking an interrupt uses up the step, the handler starts on the next one. Without the
+        // interrupts extension the devices still run, for programs that poll them.
     fn step(&mut self) {
+        let lines = self.bus.step();
+        if self.has_extension(IsaExtension::Interrupts) {
+            self.interrupt_pending |= lines;
+        }
+        if let Some(line) = self.get_interrupt_to_take() {
+            self.enter_interrupt(line);
+            return;
+        }
         let instruction = self.get_next_instruction_block();
         self.process_instruction(&instruction);
     }
@@ -49,15 +93,15 @@ impl RailSystemTrait for RailSystem {
     }
 
     fn get_cnt_register_value(&self) -> u8 {
-        return self.get_cnt_register().get_value()
+        self.get_cnt_register().get_value()
     }
 
     fn get_program_slice(&self, start: u8, end: u8) -> &[u8] {
-        &self.program[start as usize ..=end as usize]
+        &self.program[self.program_bank as usize][start as usize ..=end as usize]
     }
 
     fn get_ram_slice(&self, start: u8, end: u8) -> &[u8] {
-        &self.ram[start as usize ..=end as usize]
+        &self.get_memory()[start as usize ..=end as usize]
     }
 
     fn get_call_stack_slice(&self, start: u8, end: u8) -> &[u8] {
@@ -76,12 +120,79 @@ impl RailSystemTrait for RailSystem {
         self.gen_stack_ptr
     }
 
+    fn get_flags(&self) -> u8 {
+        self.flags
+    }
+
+    fn get_program_bank(&self) -> u8 {
+        self.program_bank
+    }
+
+    fn get_ram_bank(&self) -> u8 {
+        self.ram_bank
+    }
+
+    fn set_register_value(&mut self, reg: u8, value: u8) {
+        self.registers[reg as usize].set_value(value);
+    }
+
+    fn set_ram_value(&mut self, address: u8, value: u8) {
+        self.get_memory_mut()[address as usize] = value;
+    }
+
     fn set_io_print(&mut self, print: bool) {
         self.registers[15].set_is_io(print);
     }
 
+    fn set_shift_mask(&mut self, mask: bool) {
+        self.shift_mask = mask;
+    }
+
+    fn set_unified_memory(&mut self, unified: bool) {
+        self.unified = unified;
+    }
+
+    fn is_unified_memory(&self) -> bool {
+        self.unified
+    }
+
+        // lines 8 and up do not exist
+    fn raise_interrupt(&mut self, line: u8) {
+        self.interrupt_pending |= 1u8.checked_shl(line as u32).unwrap_or(0);
+    }
+
+    fn attach_peripheral(&mut self, first_port: u8, line: u8, device: Box<dyn RailPeripheralTrait>) {
+        self.bus.attach(first_port, line, device);
+    }
+
+    fn read_port(&self, port: u8) -> u8 {
+        self.bus.read(port)
+    }
+
+    fn get_pending_interrupts(&self) -> u8 {
+        self.interrupt_pending
+    }
+
+    fn is_in_interrupt(&self) -> bool {
+        self.interrupt_flags.is_some()
+    }
+
+    fn enable_extension(&mut self, extension: IsaExtension) {
+        if !self.extensions.contains(&extension) {
+            self.extensions.push(extension);
+        }
+    }
+
+    fn has_extension(&self, extension: IsaExtension) -> bool {
+        self.extensions.contains(&extension)
+    }
+
+        // an image of more than 256 bytes fills the following banks
     fn load_program(&mut self, program_slice: &[u8]) {
-        self.program[..program_slice.len()].copy_from_slice(program_slice);
+        for (bank, chunk) in program_slice.chunks(256).enumerate() {
+            Self::ensure_bank(&mut self.program, bank as u8);
+            self.program[bank][..chunk.len()].copy_from_slice(chunk);
+        }
     }
 
     fn is_halted(&self) -> bool {
@@ -90,21 +201,40 @@ impl RailSystemTrait for RailSystem {
 
 }
 
+impl Default for RailSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RailSystem {
 
     pub fn new() -> Self {
         let mut new_system = Self {
             registers: [RailRegister::new(); 16],
-            ram: [0; 256],
-            program: [0; 256],
+            ram: vec![[0; 256]],
+            program: vec![[0; 256]],
+            ram_bank: 0,
+            program_bank: 0,
             call_stack: [0; 128],
+            call_banks: [0; 128],
             call_stack_ptr: 0xFF,
             gen_stack: [0; 128],
             gen_stack_ptr: 0xFF,
             ran_seed: 0,
+            flags: 0,
+            shift_mask: false,
+            unified: false,
+            interrupt_vectors: [0; 8],
+            interrupt_mask: 0,
+            interrupt_pending: 0,
+            interrupt_flags: None,
+            bus: RailPeripheralBus::new(),
+            extensions: Vec::new(),
             is_halted: false
         };
         new_system.registers[15].set_is_io(true);
+        new_system.attach_peripheral(TIMER_FIRST_PORT, TIMER_LINE, Box::new(RailTimer::new()));
         new_system
     }
 
@@ -122,23 +252,73 @@ impl RailSystem {
         &mut self.registers[14]
     }
 
+        // what the RAM instructions address. Instructions are fetched from the program memory
+        // again on every step, so in unified mode a write to code shows the next time it runs.
+        // Unified memory is the program bank running, the RAM bank only selects real RAM.
+    fn get_memory(&self) -> &[u8; 256] {
+        if self.unified { &self.program[self.program_bank as usize] } else { &self.ram[self.ram_bank as usize] }
+    }
+
+    fn get_memory_mut(&mut self) -> &mut [u8; 256] {
+        if self.unified { &mut self.program[self.program_bank as usize] } else { &mut self.ram[self.ram_bank as usize] }
+    }
+
+        // banks are zeroed the first time they are switched to
+    fn ensure_bank(banks: &mut Vec<[u8; 256]>, bank: u8) {
+        if banks.len() <= bank as usize {
+            banks.resize(bank as usize + 1, [0; 256]);
+        }
+    }
+
+    fn set_program_bank(&mut self, bank: u8) {
+        Self::ensure_bank(&mut self.program, bank);
+        self.program_bank = bank;
+    }
+
     fn get_next_instruction_block(&mut self) -> RailInstructionBlock {
         let program_cnt_reg = &mut self.get_cnt_register_mut();
-        let cnt: usize = program_cnt_reg.get_value() as usize;
-        program_cnt_reg.set_value(program_cnt_reg.get_value() + 4);
+        let cnt = program_cnt_reg.get_value();
+        program_cnt_reg.set_value(cnt.wrapping_add(4));
+
+            // CNT can be any value written to it, like the target of JUMP_R, so the fetch wraps
+        let program = &self.program[self.program_bank as usize];
+        RailInstructionBlock::new(program[cnt as usize],
+                                 program[cnt.wrapping_add(1) as usize],
+                                 program[cnt.wrapping_add(2) as usize],
+                                 program[cnt.wrapping_add(3) as usize])
+    }
 
-        RailInstructionBlock::new(self.program[cnt],
-                                 self.program[cnt + 1],
-                                 self.program[cnt + 2],
-                                 self.program[cnt + 3])
+        // the lowest pending line that is let in, unless a handler is running already
+    fn get_interrupt_to_take(&self) -> Option<u8> {
+        let lines = self.interrupt_pending & self.interrupt_mask;
+        if lines == 0 || self.is_in_interrupt() || !self.has_extension(IsaExtension::Interrupts) {
+            return None;
+        }
+        Some(lines.trailing_zeros() as u8)
+    }
+
+        // like a CALL to the handler, which also keeps the flags for RETI
+    fn enter_interrupt(&mut self, line: u8) {
+        self.interrupt_pending &= !(1 << line);
+        self.interrupt_flags = Some(self.flags);
+        self.push_call_stack(self.get_cnt_register_value());
+        self.set_program_bank(0);
+        let vector = self.interrupt_vectors[line as usize];
+        self.get_cnt_register_mut().set_value(vector);
     }
 
     fn process_instruction(&mut self, instruction: &RailInstructionBlock) {
+            // instructions of an extension that is not enabled are unassigned, so do nothing
+        if let Some(extension) = rail_isa::get_by_opcode(instruction.op).and_then(|entry| entry.extension) {
+            if !self.extensions.contains(&extension) {
+                return;
+            }
+        }
         match instruction.get_subsystem() {
             RailSubSystem::Alu => self.process_alu(instruction),
             RailSubSystem::RamStack => self.process_ram_stack(instruction),
             RailSubSystem::CU => self.process_cu(instruction),
-            RailSubSystem::Peripheral => { }, // todo
+            RailSubSystem::Peripheral => self.process_peripheral(instruction),
             _ => { }// noop
         }
     }
@@ -147,23 +327,50 @@ impl RailSystem {
         let op = instruction.get_instruction();
         let arg1 = self.get_arg1_value(instruction);
         let arg2 = self.get_arg2_value(instruction);
+        let carry_in = self.flags & FLAG_C;
         let mut noop_flag = false;
+        let mut keeps_flags = false;
+        let mut carry_overflow = (false, false);
         let res = match op {
-            RailInstruction::Add => arg1.wrapping_add(arg2),
-            RailInstruction::Sub => arg1.wrapping_sub(arg2),
+            RailInstruction::Add | RailInstruction::AddCarry => {
+                let carry_in = if op == RailInstruction::AddCarry { carry_in } else { 0 };
+                let (res, carry, overflow) = Self::add_with_carry(arg1, arg2, carry_in);
+                carry_overflow = (carry, overflow);
+                res
+            }
+            RailInstruction::Sub | RailInstruction::SubBorrow => {
+                let borrow_in = if op == RailInstruction::SubBorrow { carry_in } else { 0 };
+                let (res, borrow, overflow) = Self::sub_with_borrow(arg1, arg2, borrow_in);
+                carry_overflow = (borrow, overflow);
+                res
+            }
             RailInstruction::And => arg1 & arg2,
             RailInstruction::Or => arg1 | arg2,
             RailInstruction::Not => !arg1,
             RailInstruction::Xor => arg1 ^ arg2,
-            RailInstruction::Shl => arg1 << arg2,
-            RailInstruction::Shr => arg1 >> arg2,
+            RailInstruction::Shl => self.get_shift(arg2).map_or(0, |amount| arg1 << amount),
+            RailInstruction::Shr => self.get_shift(arg2).map_or(0, |amount| arg1 >> amount),
+            RailInstruction::Asr => ((arg1 as i8) >> self.get_shift(arg2).unwrap_or(7)) as u8,
+            RailInstruction::Mul => arg1.wrapping_mul(arg2),
+            RailInstruction::MulHigh => ((arg1 as u16 * arg2 as u16) >> 8) as u8,
+            RailInstruction::Div => arg1.checked_div(arg2).unwrap_or(0xFF),
+            RailInstruction::Mod => arg1.checked_rem(arg2).unwrap_or(arg1),
+            RailInstruction::FlagsRead => {
+                keeps_flags = true;
+                self.flags
+            }
+            RailInstruction::FlagsWrite => {
+                self.flags = arg1 & (FLAG_C | FLAG_Z | FLAG_V);
+                noop_flag = true; 0
+            }
             RailInstruction::RANSetSeed => {
                 self.ran_set_seed(arg1);
                 noop_flag = true; 0 // should not update anything
             },
             RailInstruction::RANNext => self.ran_next(),
             RailInstruction::Halt => {
-                self.is_halted = true; 0
+                self.is_halted = true;
+                keeps_flags = true; 0
             }
             RailInstruction::Noop => {
                 noop_flag = true; 0 // noop
@@ -176,11 +383,39 @@ impl RailSystem {
         if noop_flag {
             return;
         }
+        if !keeps_flags && self.has_extension(IsaExtension::Flags) {
+                // only the additions and subtractions carry or overflow, everything else clears them
+            let (carry, overflow) = carry_overflow;
+            self.flags = (carry as u8 * FLAG_C) | ((res == 0) as u8 * FLAG_Z) | (overflow as u8 * FLAG_V);
+        }
 
         let res_reg = &mut self.registers[instruction.get_result() as usize];
         res_reg.set_value(res);
     }
 
+        // the amount to shift by, None when everything is shifted out
+    fn get_shift(&self, amount: u8) -> Option<u8> {
+        match amount {
+            _ if self.shift_mask => Some(amount & 7),
+            0..=7 => Some(amount),
+            _ => None
+        }
+    }
+
+        // result, carry out and signed overflow of A + B + carry
+    fn add_with_carry(arg1: u8, arg2: u8, carry: u8) -> (u8, bool, bool) {
+        let sum = arg1 as u16 + arg2 as u16 + carry as u16;
+        let res = sum as u8;
+        (res, sum > 0xFF, (arg1 ^ res) & (arg2 ^ res) & 0x80 != 0)
+    }
+
+        // result, borrow and signed overflow of A - B - borrow
+    fn sub_with_borrow(arg1: u8, arg2: u8, borrow: u8) -> (u8, bool, bool) {
+        let difference = arg1 as i16 - arg2 as i16 - borrow as i16;
+        let res = difference as u8;
+        (res, difference < 0, (arg1 ^ arg2) & (arg1 ^ res) & 0x80 != 0)
+    }
+
     fn ran_set_seed(&mut self, seed: u8) {
         self.ran_seed = seed;
     }
@@ -197,10 +432,9 @@ impl RailSystem {
         let source = self.get_arg1_value(instruction);
         let addr = self.get_arg2_value(instruction) as usize;
         let target = instruction.get_ram_target();
-        let target_reg = &mut self.registers[target as usize];
         match op {
-            RailInstruction::Read  => target_reg.set_value(self.ram[addr]),
-            RailInstruction::Write => self.ram[addr] = self.registers[source as usize].get_value(),
+            RailInstruction::Read  => self.registers[target as usize].set_value(self.get_memory()[addr]),
+            RailInstruction::Write => self.get_memory_mut()[addr] = self.registers[source as usize].get_value(),
             RailInstruction::SPop  => {
                 let value = self.pop_gen_stack();
                 self.registers[target as usize].set_value(value)
@@ -210,13 +444,57 @@ impl RailSystem {
                 self.push_gen_stack(value);
             },
             RailInstruction::Ret => {
-                let cnt = self.pop_call_stack();
+                let (cnt, bank) = self.pop_call_stack();
+                self.set_program_bank(bank);
                 self.get_cnt_register_mut().set_value(cnt)
             },
-            RailInstruction::Call => {
+            RailInstruction::InterruptReturn => {
+                let (cnt, bank) = self.pop_call_stack();
+                self.set_program_bank(bank);
+                self.get_cnt_register_mut().set_value(cnt);
+                if let Some(flags) = self.interrupt_flags.take() {
+                    self.flags = flags;
+                }
+            },
+            RailInstruction::Call | RailInstruction::CallIndirect => {
                 self.push_call_stack(self.get_cnt_register_value());  //already moved to next in step
                 self.get_cnt_register_mut().set_value(source)
             },
+            RailInstruction::JumpIndirect => self.get_cnt_register_mut().set_value(source),
+            RailInstruction::FarCall => {
+                self.push_call_stack(self.get_cnt_register_value());
+                self.set_program_bank(source);
+                self.get_cnt_register_mut().set_value(addr as u8)
+            },
+            RailInstruction::FarJump => {
+                self.set_program_bank(source);
+                self.get_cnt_register_mut().set_value(addr as u8)
+            },
+            RailInstruction::ProgramBankRead => self.registers[target as usize].set_value(self.program_bank),
+            RailInstruction::RamBankWrite => {
+                Self::ensure_bank(&mut self.ram, source);
+                self.ram_bank = source
+            },
+            RailInstruction::RamBankRead => self.registers[target as usize].set_value(self.ram_bank),
+            RailInstruction::SPeek => {
+                let value = self.peek_gen_stack(addr as u8);
+                self.registers[target as usize].set_value(value)
+            },
+            RailInstruction::SDrop => {
+                let remaining = self.gen_stack_ptr.wrapping_add(1).saturating_sub(addr as u8);
+                self.set_gen_stack_ptr(remaining.wrapping_sub(1))
+            },
+            RailInstruction::SPRead => self.registers[target as usize].set_value(self.gen_stack_ptr),
+            RailInstruction::SPWrite => self.set_gen_stack_ptr(source),
+            RailInstruction::ReadIndexed => {
+                let value = self.get_memory()[source.wrapping_add(addr as u8) as usize];
+                self.registers[target as usize].set_value(value)
+            },
+            RailInstruction::WriteIndexed => {
+                    // RES is the constant offset here, and A always names a register
+                let value = self.registers[instruction.arg1 as usize].get_value();
+                self.get_memory_mut()[(addr as u8).wrapping_add(instruction.result) as usize] = value
+            },
             RailInstruction::None => {} //noop
             _ => { }
         };
@@ -229,14 +507,23 @@ impl RailSystem {
         let jmp_addr = instruction.get_cu_addr();   // always immediate
 
         let do_jmp = match op {
-            RailInstruction::Equals => arg1 == arg2,
-            RailInstruction::NotEquals => arg1 != arg2,
-            RailInstruction::LessThan => arg1 < arg2,
-            RailInstruction::LessEqualThan => arg1 <= arg2,
-            RailInstruction::MoreThan => arg1 > arg2,
-            RailInstruction::MoreEqualThan => arg1 >= arg2,
+            _ if op.is_comparison() => op.compare(arg1, arg2) == Some(true),
             RailInstruction::True => true,
             RailInstruction::False => false,
+            RailInstruction::Carry => self.flags & FLAG_C != 0,
+            RailInstruction::NotCarry => self.flags & FLAG_C == 0,
+            RailInstruction::Zero => self.flags & FLAG_Z != 0,
+            RailInstruction::Overflow => self.flags & FLAG_V != 0,
+            RailInstruction::InterruptVector => {
+                if let Some(vector) = self.interrupt_vectors.get_mut(arg1 as usize) {
+                    *vector = jmp_addr;
+                }
+                false
+            }
+            RailInstruction::InterruptEnable => {
+                self.interrupt_mask = arg1;
+                false
+            }
             _ => false
         };
         if do_jmp {
@@ -244,6 +531,19 @@ impl RailSystem {
         }
     }
 
+    fn process_peripheral(&mut self, instruction: &RailInstructionBlock) {
+        let value = self.get_arg1_value(instruction);
+        let port = self.get_arg2_value(instruction);
+        match instruction.get_instruction() {
+            RailInstruction::PortRead => {
+                let value = self.bus.read(port);
+                self.registers[instruction.get_result() as usize].set_value(value)
+            },
+            RailInstruction::PortWrite => self.bus.write(port, value),
+            _ => { }
+        }
+    }
+
     fn get_arg1_value(&self, instruction: &RailInstructionBlock) -> u8 {
         if instruction.is_arg1_immediate() {
             instruction.arg1
@@ -262,22 +562,34 @@ impl RailSystem {
         }
     }
 
+        // the bank of the caller goes along, so a RET from another bank finds its way back
     fn push_call_stack(&mut self, value: u8) {
         self.call_stack_ptr = self.call_stack_ptr.wrapping_add(1);
         self.call_stack[self.call_stack_ptr as usize] = value;
+        self.call_banks[self.call_stack_ptr as usize] = self.program_bank;
     }
 
-    fn pop_call_stack(&mut self) -> u8 {
-        let res = self.call_stack[self.call_stack_ptr as usize];
+    fn pop_call_stack(&mut self) -> (u8, u8) {
+        let res = (self.call_stack[self.call_stack_ptr as usize], self.call_banks[self.call_stack_ptr as usize]);
         self.call_stack_ptr = self.call_stack_ptr.wrapping_sub(1);
         res
     }
 
+        // 0 to 127 points at the top entry and 0xFF is empty; anything else wraps into the stack
+    fn set_gen_stack_ptr(&mut self, ptr: u8) {
+        self.gen_stack_ptr = if ptr == 0xFF { ptr } else { ptr % self.gen_stack.len() as u8 };
+    }
+
     fn push_gen_stack(&mut self, value: u8) {
         self.gen_stack_ptr = self.gen_stack_ptr.wrapping_add(1);
         self.gen_stack[self.gen_stack_ptr as usize] = value;
     }
 
+        // the entry `depth` below the top, 0 for anything outside the stack
+    fn peek_gen_stack(&self, depth: u8) -> u8 {
+        self.gen_stack.get(self.gen_stack_ptr.wrapping_sub(depth) as usize).copied().unwrap_or(0)
+    }
+
     fn pop_gen_stack(&mut self) -> u8 {
         let res = self.gen_stack[self.gen_stack_ptr as usize];
         self.gen_stack_ptr = self.gen_stack_ptr.wrapping_sub(1);
@@ -1,13 +1,49 @@
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum RailInstruction {
     //ALU
     Add, Sub, And, Or, Not, Xor, Shl, Shr, RANSetSeed, RANNext, Halt, Noop,
+    Mul, MulHigh, Div, Mod, AddCarry, SubBorrow, FlagsRead, FlagsWrite, Asr,
     // CU
     Equals, NotEquals, LessThan, LessEqualThan, MoreThan, MoreEqualThan, True, False,
+    LessThanSigned, LessEqualThanSigned, MoreThanSigned, MoreEqualThanSigned, BitSet, BitClear,
+    Carry, NotCarry, Zero, Overflow, InterruptVector, InterruptEnable,
     // RAM
-    Read, Write, SPop, SPush, Ret, Call,
+    Read, Write, SPop, SPush, Ret, Call, ReadIndexed, WriteIndexed, JumpIndirect, CallIndirect,
+    SPeek, SDrop, SPRead, SPWrite, FarCall, FarJump, ProgramBankRead, RamBankWrite, RamBankRead,
+    InterruptReturn,
     // Peripheral
-
+    PortRead, PortWrite,
     // None
     None
 }
+
+impl RailInstruction {
+
+        // conditions that compare A with B, rather than look at the flags
+    pub fn is_comparison(&self) -> bool {
+        self.compare(0, 0).is_some()
+    }
+
+        // whether a condition on A and B holds, None for instructions that are no comparison
+    pub fn compare(&self, a: u8, b: u8) -> Option<bool> {
+        let res = match self {
+            RailInstruction::Equals => a == b,
+            RailInstruction::NotEquals => a != b,
+            RailInstruction::LessThan => a < b,
+            RailInstruction::LessEqualThan => a <= b,
+            RailInstruction::MoreThan => a > b,
+            RailInstruction::MoreEqualThan => a >= b,
+            RailInstruction::LessThanSigned => (a as i8) < (b as i8),
+            RailInstruction::LessEqualThanSigned => (a as i8) <= (b as i8),
+            RailInstruction::MoreThanSigned => (a as i8) > (b as i8),
+            RailInstruction::MoreEqualThanSigned => (a as i8) >= (b as i8),
+                // bits from 8 on are never set
+            RailInstruction::BitSet => a.checked_shr(b as u32).is_some_and(|value| value & 1 != 0),
+            RailInstruction::BitClear => a.checked_shr(b as u32).is_none_or(|value| value & 1 == 0),
+            _ => return None
+        };
+        Some(res)
+    }
+
+}
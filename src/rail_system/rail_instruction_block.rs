@@ -1,7 +1,9 @@
 
 use crate::rail_system::rail_instruction::RailInstruction;
+use crate::rail_system::rail_isa;
 use crate::rail_system::rail_subsystem::RailSubSystem;
 
+#[derive(Clone, Copy)]
 pub struct RailInstructionBlock {
     pub op: u8,
     pub arg1: u8,
@@ -15,7 +17,12 @@ impl RailInstructionBlock {
         Self { op, arg1, arg2, result }
     }
 
+        // the subsystem running the instruction; extension instructions in the peripheral group
+        // name theirs in the ISA table
     pub fn get_subsystem(&self) -> RailSubSystem {
+        if let Some(entry) = rail_isa::get_by_opcode(self.op) {
+            return entry.subsystem;
+        }
         match (&self.op & 48) >> 4 {
             0 => RailSubSystem::Alu,
             1 => RailSubSystem::RamStack,
@@ -26,14 +33,7 @@ impl RailInstructionBlock {
     }
 
     pub fn get_instruction(&self) -> RailInstruction {
-        let masked = self.op & 15;
-        match self.get_subsystem() {
-            RailSubSystem::Alu => self.get_alu_instruction(masked),
-            RailSubSystem::CU => self.get_cu_instruction(masked),
-            RailSubSystem::RamStack => self.get_ram_instruction(masked),
-            RailSubSystem::Peripheral => self.get_peripheral_instruction(masked),
-            _ => RailInstruction::None
-        }
+        rail_isa::decode(self.op)
     }
 
     pub fn is_arg1_immediate(&self) -> bool {
@@ -60,56 +60,4 @@ impl RailInstructionBlock {
         (value & flag) == flag
     }
 
-    pub fn get_alu_instruction(&self, masked: u8) -> RailInstruction {
-        match masked {
-            0 => RailInstruction::Add,
-            1 => RailInstruction::Sub,
-            2 => RailInstruction::And,
-            3 => RailInstruction::Or,
-            4 => RailInstruction::Not,
-            5 => RailInstruction::Xor,
-            6 => RailInstruction::Shl,
-            7 => RailInstruction::Shr,
-            // 8, 9, 10, 11
-            12 => RailInstruction::RANSetSeed,
-            13 => RailInstruction::RANNext,
-            14 => RailInstruction::Halt,
-            15 => RailInstruction::Noop,
-            _ => RailInstruction::None
-        }
-    }
-
-    pub fn get_cu_instruction(&self, masked: u8) -> RailInstruction {
-        match masked {
-            0 => RailInstruction::Equals,
-            1 => RailInstruction::NotEquals,
-            2 => RailInstruction::LessThan,
-            3 => RailInstruction::LessEqualThan,
-            4 => RailInstruction::MoreThan,
-            5 => RailInstruction::MoreEqualThan,
-            6 => RailInstruction::True,
-            7 => RailInstruction::False,
-            // 8 to 15
-            _ => RailInstruction::None
-        }
-    }
-
-    pub fn get_ram_instruction(&self, masked: u8) -> RailInstruction {
-        match masked {
-            0 => RailInstruction::Read,
-            1 => RailInstruction::Write,
-            // 2 to 7
-            8 => RailInstruction::SPop,
-            9 => RailInstruction::SPush,
-            10 => RailInstruction::Ret,
-            11 => RailInstruction::Call,
-            // 12 to 15
-            _ => RailInstruction::None
-        }
-    }
-
-    pub fn get_peripheral_instruction(&self, _masked: u8) -> RailInstruction {
-        RailInstruction::None
-    }
-
 }
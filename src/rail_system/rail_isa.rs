@@ -0,0 +1,231 @@
+use crate::rail_system::rail_instruction::RailInstruction;
+use crate::rail_system::rail_subsystem::RailSubSystem;
+use crate::rail_system::rail_subsystem::RailSubSystem::{Alu, RamStack, CU, Peripheral};
+use self::IsaExtension::{Banks, Flags, Interrupts, MulDiv, Shifts};
+use self::IsaOperand::{Address, Constant, Register, Target, Unused, Value};
+
+pub const IM1: u8 = 0x80;
+pub const IM2: u8 = 0x40;
+pub const CODE_MASK: u8 = 0x3F;     // subsystem and instruction bits, without the immediate flags
+pub const EXTENSION_GROUP: u8 = 0x30;   // the peripheral opcodes, where extensions of any subsystem go
+
+    // bits of the flags register of the flags extension
+pub const FLAG_C: u8 = 0x01;        // carry out of an addition, borrow of a subtraction
+pub const FLAG_Z: u8 = 0x02;        // the result is zero
+pub const FLAG_V: u8 = 0x04;        // signed overflow
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IsaOperand {
+    Value,      // a register, or a constant with the immediate flag set
+    Register,   // always a register index, even with the immediate flag set
+    Target,     // the register written
+    Address,    // a code address; a register holding it without the immediate flag
+    Constant,   // always a constant, for RES which has no immediate flag
+    Unused
+}
+
+impl IsaOperand {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            IsaOperand::Value => "value",
+            IsaOperand::Register => "register",
+            IsaOperand::Target => "target",
+            IsaOperand::Address => "address",
+            IsaOperand::Constant => "constant",
+            IsaOperand::Unused => "-"
+        }
+    }
+}
+
+    // opt-in groups of instructions, which a program and the machine running it have to enable
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IsaExtension {
+    MulDiv,     // MUL, MULH, DIV and MOD in the free ALU slots 8 to 11
+    Flags,      // carry, zero and overflow flags, ADC, SBB and the jumps on them
+    Shifts,     // ASR, the arithmetic shift right
+    Banks,      // switching between 256 byte banks of program memory and of RAM
+    Interrupts  // lines raised from outside that run a handler between two instructions
+}
+
+impl IsaExtension {
+
+    pub const ALL: [IsaExtension; 5] = [IsaExtension::MulDiv, IsaExtension::Flags, IsaExtension::Shifts,
+                                        IsaExtension::Banks, IsaExtension::Interrupts];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            IsaExtension::MulDiv => "muldiv",
+            IsaExtension::Flags => "flags",
+            IsaExtension::Shifts => "shifts",
+            IsaExtension::Banks => "banks",
+            IsaExtension::Interrupts => "interrupts"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|extension| extension.get_name().eq_ignore_ascii_case(name.trim()))
+    }
+
+}
+
+pub struct IsaEntry {
+    pub mnemonic: &'static str,
+    pub instruction: RailInstruction,
+    pub subsystem: RailSubSystem,
+    pub opcode: u8,                     // what the mnemonic assembles to, flags it always carries included
+    pub operands: [IsaOperand; 3],      // A, B and RES
+    pub semantics: &'static str,        // what RailSystem does with it
+    pub extension: Option<IsaExtension> // None for the base instruction set
+}
+
+impl IsaEntry {
+
+        // immediate flags baked into the mnemonic, an instruction without them has no mnemonic
+    pub fn get_fixed_flags(&self) -> u8 {
+        self.opcode & (IM1 | IM2)
+    }
+
+    pub const fn get_code(&self) -> u8 {
+        self.opcode & CODE_MASK
+    }
+
+}
+
+const fn entry(mnemonic: &'static str, instruction: RailInstruction, subsystem: RailSubSystem, opcode: u8,
+               operands: [IsaOperand; 3], semantics: &'static str) -> IsaEntry {
+    IsaEntry { mnemonic, instruction, subsystem, opcode, operands, semantics, extension: None }
+}
+
+const fn extension_entry(extension: IsaExtension, mnemonic: &'static str, instruction: RailInstruction, subsystem: RailSubSystem,
+                         opcode: u8, operands: [IsaOperand; 3], semantics: &'static str) -> IsaEntry {
+    IsaEntry { mnemonic, instruction, subsystem, opcode, operands, semantics, extension: Some(extension) }
+}
+
+    // The one description of the instruction set: the decoder, the assembler dictionary, the
+    // disassembler, the language server and the reference table in the readme all read it.
+pub const ISA: [IsaEntry; 63] = [
+    entry("ADD", RailInstruction::Add, Alu, 0x00, [Value, Value, Target], "ALU: RES = A + B, wrapping."),
+    entry("SUB", RailInstruction::Sub, Alu, 0x01, [Value, Value, Target], "ALU: RES = A - B, wrapping."),
+    entry("AND", RailInstruction::And, Alu, 0x02, [Value, Value, Target], "ALU: RES = A & B."),
+    entry("OR", RailInstruction::Or, Alu, 0x03, [Value, Value, Target], "ALU: RES = A | B."),
+    entry("NOT", RailInstruction::Not, Alu, 0x04, [Value, Unused, Target], "ALU: RES = !A. B is ignored."),
+    entry("XOR", RailInstruction::Xor, Alu, 0x05, [Value, Value, Target], "ALU: RES = A ^ B."),
+    entry("SHL", RailInstruction::Shl, Alu, 0x06, [Value, Value, Target], "ALU: RES = A << B. 0 when B is 8 or more, unless shifts are masked to B & 7."),
+    entry("SHR", RailInstruction::Shr, Alu, 0x07, [Value, Value, Target], "ALU: RES = A >> B, unsigned. 0 when B is 8 or more, unless shifts are masked to B & 7."),
+    extension_entry(MulDiv, "MUL", RailInstruction::Mul, Alu, 0x08, [Value, Value, Target], "ALU: RES = A * B, low byte."),
+    extension_entry(MulDiv, "MULH", RailInstruction::MulHigh, Alu, 0x09, [Value, Value, Target], "ALU: RES = A * B, high byte, unsigned."),
+    extension_entry(MulDiv, "DIV", RailInstruction::Div, Alu, 0x0A, [Value, Value, Target], "ALU: RES = A / B, unsigned. 0xFF when B is 0."),
+    extension_entry(MulDiv, "MOD", RailInstruction::Mod, Alu, 0x0B, [Value, Value, Target], "ALU: RES = A % B, unsigned. A when B is 0."),
+    entry("RAN_SS", RailInstruction::RANSetSeed, Alu, 0x0C, [Value, Unused, Unused], "ALU: sets the seed of the random generator to A. RES is not written."),
+    entry("RAN_NEXT", RailInstruction::RANNext, Alu, 0x0D, [Unused, Unused, Target], "ALU: RES = next xorshift random value."),
+    entry("HALT", RailInstruction::Halt, Alu, 0x0E, [Unused, Unused, Target], "ALU: stops the machine."),
+    entry("NOOP", RailInstruction::Noop, Alu, 0x0F, [Unused, Unused, Unused], "ALU: does nothing."),
+
+    entry("IF_EQ", RailInstruction::Equals, CU, 0x20, [Value, Value, Address], "CU: jumps to RES if A == B."),
+    entry("IF_N_EQ", RailInstruction::NotEquals, CU, 0x21, [Value, Value, Address], "CU: jumps to RES if A != B."),
+    entry("IF_LT", RailInstruction::LessThan, CU, 0x22, [Value, Value, Address], "CU: jumps to RES if A < B, unsigned."),
+    entry("IF_LTE", RailInstruction::LessEqualThan, CU, 0x23, [Value, Value, Address], "CU: jumps to RES if A <= B, unsigned."),
+    entry("IF_MT", RailInstruction::MoreThan, CU, 0x24, [Value, Value, Address], "CU: jumps to RES if A > B, unsigned."),
+    entry("IF_MTE", RailInstruction::MoreEqualThan, CU, 0x25, [Value, Value, Address], "CU: jumps to RES if A >= B, unsigned."),
+    entry("IF_T", RailInstruction::True, CU, 0x26, [Unused, Unused, Address], "CU: always jumps to RES."),
+    entry("IF_F", RailInstruction::False, CU, 0x27, [Unused, Unused, Unused], "CU: never jumps."),
+    entry("IF_LT_S", RailInstruction::LessThanSigned, CU, 0x28, [Value, Value, Address], "CU: jumps to RES if A < B, signed."),
+    entry("IF_LTE_S", RailInstruction::LessEqualThanSigned, CU, 0x29, [Value, Value, Address], "CU: jumps to RES if A <= B, signed."),
+    entry("IF_MT_S", RailInstruction::MoreThanSigned, CU, 0x2A, [Value, Value, Address], "CU: jumps to RES if A > B, signed."),
+    entry("IF_MTE_S", RailInstruction::MoreEqualThanSigned, CU, 0x2B, [Value, Value, Address], "CU: jumps to RES if A >= B, signed."),
+    entry("IF_BIT", RailInstruction::BitSet, CU, 0x2C, [Value, Value, Address], "CU: jumps to RES if bit B of A is set. Never for B of 8 or more."),
+    entry("IF_N_BIT", RailInstruction::BitClear, CU, 0x2D, [Value, Value, Address], "CU: jumps to RES if bit B of A is clear. Always for B of 8 or more."),
+    extension_entry(Interrupts, "INT_VEC", RailInstruction::InterruptVector, CU, 0x2E, [Value, Unused, Address], "CU: sets the handler of interrupt line A, 0 to 7, to RES in program bank 0."),
+    extension_entry(Interrupts, "INT_EN", RailInstruction::InterruptEnable, CU, 0x2F, [Value, Unused, Unused], "CU: lets in the interrupt lines whose bit is set in A, 0 masks them all."),
+
+    entry("RAM_R", RailInstruction::Read, RamStack, 0x10, [Unused, Value, Target], "RAM: RES = ram[B]."),
+    entry("RAM_W", RailInstruction::Write, RamStack, 0x11, [Register, Value, Unused], "RAM: ram[B] = register A."),
+    entry("RAM_RX", RailInstruction::ReadIndexed, RamStack, 0x12, [Value, Value, Target], "RAM: RES = ram[A + B], wrapping."),
+    entry("RAM_WX", RailInstruction::WriteIndexed, RamStack, 0x13, [Register, Value, Constant], "RAM: ram[B + RES] = register A, wrapping."),
+    entry("JUMP_R", RailInstruction::JumpIndirect, RamStack, 0x14, [Address, Unused, Unused], "Stack: jumps to A, usually a register holding the address."),
+    entry("CALL_R", RailInstruction::CallIndirect, RamStack, 0x15, [Address, Unused, Unused], "Stack: pushes the return address on the call stack and jumps to A, usually a register holding the address."),
+    extension_entry(Interrupts, "RETI", RailInstruction::InterruptReturn, RamStack, 0x16, [Unused, Unused, Unused], "Stack: returns from an interrupt handler, restoring the flags and letting the next interrupt in."),
+    entry("S_POP", RailInstruction::SPop, RamStack, 0x18, [Unused, Unused, Target], "Stack: pops the gen stack into RES."),
+    entry("S_PUSH", RailInstruction::SPush, RamStack, 0x19, [Register, Unused, Unused], "Stack: pushes register A onto the gen stack."),
+    entry("RET", RailInstruction::Ret, RamStack, 0x1A, [Unused, Unused, Unused], "Stack: pops the call stack into CNT."),
+    entry("CALL", RailInstruction::Call, RamStack, 0x9B, [Address, Unused, Unused], "Stack: pushes the return address on the call stack and jumps to A."),
+    entry("S_PEEK", RailInstruction::SPeek, RamStack, 0x1C, [Unused, Value, Target], "Stack: RES = the gen stack entry B below the top, 0 being the top. 0 past the bottom."),
+    entry("S_DROP", RailInstruction::SDrop, RamStack, 0x1D, [Unused, Value, Unused], "Stack: drops B entries off the gen stack, leaving it empty when it holds fewer."),
+    entry("S_SP_R", RailInstruction::SPRead, RamStack, 0x1E, [Unused, Unused, Target], "Stack: RES = the gen stack pointer, 0xFF when the stack is empty."),
+    entry("S_SP_W", RailInstruction::SPWrite, RamStack, 0x1F, [Value, Unused, Unused], "Stack: sets the gen stack pointer to A, 0xFF for empty. Values from 0x80 to 0xFE wrap to A & 0x7F."),
+
+    extension_entry(Flags, "ADC", RailInstruction::AddCarry, Alu, 0x30, [Value, Value, Target], "ALU: RES = A + B + C, wrapping."),
+    extension_entry(Flags, "SBB", RailInstruction::SubBorrow, Alu, 0x31, [Value, Value, Target], "ALU: RES = A - B - C, wrapping."),
+    extension_entry(Flags, "FLG_R", RailInstruction::FlagsRead, Alu, 0x32, [Unused, Unused, Target], "ALU: RES = flags, C in bit 0, Z in bit 1 and V in bit 2. Leaves the flags alone."),
+    extension_entry(Flags, "FLG_W", RailInstruction::FlagsWrite, Alu, 0x33, [Value, Unused, Unused], "ALU: flags = A. RES is not written."),
+    extension_entry(Flags, "IF_C", RailInstruction::Carry, CU, 0x34, [Unused, Unused, Address], "CU: jumps to RES if C is set."),
+    extension_entry(Flags, "IF_N_C", RailInstruction::NotCarry, CU, 0x35, [Unused, Unused, Address], "CU: jumps to RES if C is clear."),
+    extension_entry(Flags, "IF_Z", RailInstruction::Zero, CU, 0x36, [Unused, Unused, Address], "CU: jumps to RES if Z is set."),
+    extension_entry(Flags, "IF_V", RailInstruction::Overflow, CU, 0x37, [Unused, Unused, Address], "CU: jumps to RES if V is set."),
+
+    extension_entry(Shifts, "ASR", RailInstruction::Asr, Alu, 0x38, [Value, Value, Target], "ALU: RES = A >> B, copying the sign bit. 0 or 0xFF when B is 8 or more, unless shifts are masked to B & 7."),
+
+    extension_entry(Banks, "FAR_CALL", RailInstruction::FarCall, RamStack, 0x39, [Value, Value, Unused], "Stack: pushes the return address and bank on the call stack, switches to program bank A and jumps to B."),
+    extension_entry(Banks, "FAR_JMP", RailInstruction::FarJump, RamStack, 0x3A, [Value, Value, Unused], "Stack: switches to program bank A and jumps to B."),
+    extension_entry(Banks, "PBANK_R", RailInstruction::ProgramBankRead, RamStack, 0x3B, [Unused, Unused, Target], "Stack: RES = the program bank."),
+    extension_entry(Banks, "RBANK_W", RailInstruction::RamBankWrite, RamStack, 0x3C, [Value, Unused, Unused], "RAM: switches the RAM instructions to RAM bank A."),
+    extension_entry(Banks, "RBANK_R", RailInstruction::RamBankRead, RamStack, 0x3D, [Unused, Unused, Target], "RAM: RES = the RAM bank."),
+
+    entry("PER_R", RailInstruction::PortRead, Peripheral, 0x3E, [Unused, Value, Target], "Peripheral: RES = port B of the peripheral bus, 0 without a device there."),
+    entry("PER_W", RailInstruction::PortWrite, Peripheral, 0x3F, [Value, Value, Unused], "Peripheral: port B of the peripheral bus = A."),
+];
+
+    // index into ISA for each value of the code bits, built once. Building it checks that every
+    // entry sits in the opcode range of its subsystem, or in the extension group when it belongs
+    // to an extension, and that no two entries share a code.
+const NO_ENTRY: u8 = 0xFF;
+static DECODE: [u8; 64] = build_decode_table();
+
+const fn build_decode_table() -> [u8; 64] {
+    let mut table = [NO_ENTRY; 64];
+    let mut i = 0;
+    while i < ISA.len() {
+        let code = ISA[i].get_code() as usize;
+        let in_extension_group = ISA[i].extension.is_some() && code as u8 & EXTENSION_GROUP == EXTENSION_GROUP;
+        let in_range = in_extension_group || match ISA[i].subsystem {
+            Alu => code >> 4 == 0,
+            RamStack => code >> 4 == 1,
+            CU => code >> 4 == 2,
+            RailSubSystem::Peripheral => code >> 4 == 3,
+            RailSubSystem::None => false
+        };
+        assert!(in_range, "ISA entry outside the opcode range of its subsystem");
+        assert!(table[code] == NO_ENTRY, "Two ISA entries share an opcode");
+        table[code] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+pub fn decode(op: u8) -> RailInstruction {
+    match get_by_opcode(op) {
+        Some(entry) => entry.instruction,
+        None => RailInstruction::None
+    }
+}
+
+pub fn get_by_mnemonic(mnemonic: &str) -> Option<&'static IsaEntry> {
+    ISA.iter().find(|entry| entry.mnemonic == mnemonic)
+}
+
+    // the entry an opcode decodes to, whatever its immediate flags
+pub fn get_by_opcode(op: u8) -> Option<&'static IsaEntry> {
+    ISA.get(DECODE[(op & CODE_MASK) as usize] as usize)
+}
+
+    // reference table of every instruction, as markdown
+pub fn to_markdown() -> String {
+    let mut res = String::from("| Mnemonic | Opcode | A | B | RES | Semantics | Extension |\n|---|---|---|---|---|---|---|\n");
+    for entry in &ISA {
+        res.push_str(&format!("| `{}` | `0x{:02X}` | {} | {} | {} | {} | {} |\n", entry.mnemonic, entry.opcode,
+                              entry.operands[0].get_name(), entry.operands[1].get_name(),
+                              entry.operands[2].get_name(), entry.semantics.replace('|', "\\|"),
+                              entry.extension.map(|extension| extension.get_name()).unwrap_or("-")));
+    }
+    res
+}
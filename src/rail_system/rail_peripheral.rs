@@ -0,0 +1,69 @@
+    // a device on the peripheral bus. Ports are numbered from the first one the device is attached at.
+pub trait RailPeripheralTrait {
+    fn get_port_count(&self) -> u8;
+    fn read(&self, port: u8) -> u8;
+    fn write(&mut self, port: u8, value: u8);
+    fn step(&mut self) -> bool;     // runs once per machine step, true raises the interrupt line of the device
+}
+
+struct RailBusSlot {
+    first_port: u8,
+    line: u8,
+    device: Box<dyn RailPeripheralTrait>
+}
+
+    // PER_R and PER_W reach the devices through it. A port without a device reads 0 and ignores writes.
+pub struct RailPeripheralBus {
+    slots: Vec<RailBusSlot>
+}
+
+impl Default for RailPeripheralBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailPeripheralBus {
+
+    pub fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+        // a device attached later wins the ports it shares with an earlier one
+    pub fn attach(&mut self, first_port: u8, line: u8, device: Box<dyn RailPeripheralTrait>) {
+        self.slots.insert(0, RailBusSlot { first_port, line, device });
+    }
+
+    pub fn read(&self, port: u8) -> u8 {
+        match self.find(port) {
+            Some(index) => {
+                let slot = &self.slots[index];
+                slot.device.read(port - slot.first_port)
+            }
+            None => 0
+        }
+    }
+
+    pub fn write(&mut self, port: u8, value: u8) {
+        if let Some(index) = self.find(port) {
+            let slot = &mut self.slots[index];
+            slot.device.write(port - slot.first_port, value);
+        }
+    }
+
+        // steps every device, and returns the interrupt lines raised as a mask
+    pub fn step(&mut self) -> u8 {
+        let mut lines = 0;
+        for slot in &mut self.slots {
+            if slot.device.step() {
+                lines |= 1u8.checked_shl(slot.line as u32).unwrap_or(0);
+            }
+        }
+        lines
+    }
+
+    fn find(&self, port: u8) -> Option<usize> {
+        self.slots.iter().position(|slot| port >= slot.first_port && port - slot.first_port < slot.device.get_port_count())
+    }
+
+}
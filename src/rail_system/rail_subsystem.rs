@@ -1,4 +1,5 @@
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum RailSubSystem {
     Alu, RamStack, CU, Peripheral, None
 }
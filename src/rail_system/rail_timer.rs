@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+use crate::rail_system::rail_peripheral::RailPeripheralTrait;
+
+    // where a RailSystem attaches its timer on the peripheral bus
+pub const TIMER_FIRST_PORT: u8 = 0x00;
+pub const TIMER_LINE: u8 = 0;
+
+    // ports, from the first one
+pub const TIMER_CONTROL: u8 = 0;
+pub const TIMER_STATUS: u8 = 1;
+pub const TIMER_PRESCALER: u8 = 2;
+pub const TIMER_COMPARE: u8 = 3;
+pub const TIMER_COUNTER: u8 = 4;
+
+    // bits of the control port
+pub const TIMER_RUN: u8 = 0x01;         // the counter advances
+pub const TIMER_IRQ: u8 = 0x02;         // reaching the compare value raises the interrupt line
+pub const TIMER_CLEAR: u8 = 0x04;       // reaching the compare value starts the counter over at 0
+pub const TIMER_WALL_CLOCK: u8 = 0x08;  // the prescaler counts milliseconds instead of steps
+
+    // bits of the status port, writing a bit as 1 clears it
+pub const TIMER_EXPIRED: u8 = 0x01;
+
+    // An 8 bit counter that advances once every PRESCALER + 1 steps, or milliseconds with the wall
+    // clock. Reaching COMPARE sets the expired flag, and can raise an interrupt and clear the counter.
+pub struct RailTimer {
+    control: u8,
+    status: u8,
+    prescaler: u8,
+    compare: u8,
+    counter: u8,
+    prescale_count: u8,
+    last_tick: Option<Instant>      // when the wall clock was last read
+}
+
+impl Default for RailTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RailTimer {
+
+    pub fn new() -> Self {
+        Self { control: 0, status: 0, prescaler: 0, compare: 0, counter: 0, prescale_count: 0, last_tick: None }
+    }
+
+        // steps always tick once, the wall clock once per whole millisecond passed since the last tick
+    fn get_ticks(&mut self) -> u64 {
+        if self.control & TIMER_WALL_CLOCK == 0 {
+            return 1;
+        }
+        let now = Instant::now();
+        let last = *self.last_tick.get_or_insert(now);
+        let millis = now.duration_since(last).as_millis() as u64;
+        self.last_tick = Some(last + Duration::from_millis(millis));
+        millis
+    }
+
+        // true when the counter reaches the compare value. Works out all the ticks at once, as the
+        // wall clock can pass a lot of them between two steps.
+    fn advance(&mut self, ticks: u64) -> bool {
+        let period = self.prescaler as u64 + 1;
+        let prescaled = self.prescale_count as u64 + ticks;
+        self.prescale_count = (prescaled % period) as u8;
+        let counts = prescaled / period;
+
+            // counts until the counter equals compare, a whole round when it already does
+        let to_compare = match self.compare.wrapping_sub(self.counter) {
+            0 => 256,
+            distance => distance as u64
+        };
+        if counts < to_compare {
+            self.counter = self.counter.wrapping_add(counts as u8);
+            return false;
+        }
+        self.status |= TIMER_EXPIRED;
+        let counts_after = counts - to_compare;
+        self.counter = if self.control & TIMER_CLEAR != 0 {
+            let round = if self.compare == 0 { 256 } else { self.compare as u64 };
+            (counts_after % round) as u8        // from 0, starting over every time it reaches compare
+        } else {
+            self.compare.wrapping_add(counts_after as u8)
+        };
+        true
+    }
+
+}
+
+impl RailPeripheralTrait for RailTimer {
+
+    fn get_port_count(&self) -> u8 {
+        5
+    }
+
+    fn read(&self, port: u8) -> u8 {
+        match port {
+            TIMER_CONTROL => self.control,
+            TIMER_STATUS => self.status,
+            TIMER_PRESCALER => self.prescaler,
+            TIMER_COMPARE => self.compare,
+            TIMER_COUNTER => self.counter,
+            _ => 0
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port {
+            TIMER_CONTROL => {
+                self.control = value;
+                self.last_tick = None;      // the wall clock starts counting from now
+            }
+            TIMER_STATUS => self.status &= !value,
+            TIMER_PRESCALER => {
+                self.prescaler = value;
+                self.prescale_count = 0;
+            }
+            TIMER_COMPARE => self.compare = value,
+            TIMER_COUNTER => self.counter = value,
+            _ => { }
+        }
+    }
+
+    fn step(&mut self) -> bool {
+        if self.control & TIMER_RUN == 0 {
+            return false;
+        }
+        let ticks = self.get_ticks();
+        self.advance(ticks) && self.control & TIMER_IRQ != 0
+    }
+
+}
@@ -10,6 +10,10 @@ use tui::text::{Span, Spans};
 use tui::widgets::{Block, Borders, Paragraph};
 
 use crate::rail_system::{RailSystem, RailSystemTrait};
+use crate::rail_system::rail_instruction::RailInstruction;
+use crate::rail_system::rail_instruction_block::RailInstructionBlock;
+use crate::rail_system::rail_isa::{IsaExtension, FLAG_C, FLAG_V, FLAG_Z};
+use crate::rail_system::rail_timer::{TIMER_CONTROL, TIMER_COUNTER, TIMER_FIRST_PORT, TIMER_RUN};
 
 pub struct RailTerminalUI {
     pub(crate) rail_system: RailSystem
@@ -205,14 +209,21 @@ impl RailTerminalUI {
             .block(Block::default().borders(Borders::ALL).title("Registers"));
         frame.render_widget(block, main_columns[0]);
 
+        let has_banks = self.rail_system.has_extension(IsaExtension::Banks);
         let program = self.get_program();
+        let program_title = if has_banks { format!("Program {}", self.rail_system.get_program_bank()) } else { "Program".to_string() };
         let block = Paragraph::new(program)
-            .block(Block::default().borders(Borders::ALL).title("Program"));
+            .block(Block::default().borders(Borders::ALL).title(program_title));
         frame.render_widget(block, mem_columns[0]);
 
         let ram = self.get_ram();
+        let ram_title = match (self.rail_system.is_unified_memory(), has_banks) {
+            (true, _) => "RAM = Program".to_string(),
+            (false, true) => format!("RAM {}", self.rail_system.get_ram_bank()),
+            (false, false) => "RAM".to_string()
+        };
         let block = Paragraph::new(ram)
-            .block(Block::default().borders(Borders::ALL).title("RAM"));
+            .block(Block::default().borders(Borders::ALL).title(ram_title));
         frame.render_widget(block, mem_columns[1]);
 
         let call_stack = self.get_call_stack();
@@ -226,7 +237,7 @@ impl RailTerminalUI {
         frame.render_widget(block, mem_columns[3]);
     }
 
-    fn get_registers(&self) -> Vec<Spans> {
+    fn get_registers(&self) -> Vec<Spans<'_>> {
         let mut spans = Vec::new();
         spans.push(Spans::from(vec! [Span::raw("")]));
 
@@ -262,11 +273,38 @@ impl RailTerminalUI {
             };
             spans.push(Spans::from(span_vec));
         }
+        if self.rail_system.has_extension(IsaExtension::Flags) {
+            let flags = self.rail_system.get_flags();
+            let mut span_vec = vec![Span::raw(" FLG: ")];
+            for (flag, name) in [(FLAG_C, "C"), (FLAG_Z, "Z"), (FLAG_V, "V")] {
+                let color = if flags & flag != 0 { Color::LightGreen } else { Color::DarkGray };
+                span_vec.push(Span::styled(name, Style::default().fg(color)));
+            }
+            spans.push(Spans::from(span_vec));
+        }
+        if self.rail_system.has_extension(IsaExtension::Interrupts) {
+                // pending lines light up, the title while a handler runs
+            let title_color = if self.rail_system.is_in_interrupt() { Color::LightGreen } else { Color::Reset };
+            let pending = self.rail_system.get_pending_interrupts();
+            let mut span_vec = vec![Span::styled(" INT: ", Style::default().fg(title_color))];
+            for line in 0..8 {
+                let color = if pending & (1 << line) != 0 { Color::LightYellow } else { Color::DarkGray };
+                span_vec.push(Span::styled(line.to_string(), Style::default().fg(color)));
+            }
+            spans.push(Spans::from(span_vec));
+        }
+        if self.rail_system.read_port(TIMER_FIRST_PORT + TIMER_CONTROL) & TIMER_RUN != 0 {
+            let counter = self.rail_system.read_port(TIMER_FIRST_PORT + TIMER_COUNTER);
+            spans.push(Spans::from(vec![
+                Span::raw(" TMR: "),
+                Span::styled(Self::hex_str(counter), Style::default().fg(Color::Blue)),
+            ]));
+        }
 
         spans
     }
 
-    fn get_program(&self) -> Vec<Spans> {
+    fn get_program(&self) -> Vec<Spans<'_>> {
         let mut spans = Vec::new();
         let cnt = self.rail_system.get_cnt_register_value();
         for line in 0..32 {
@@ -288,7 +326,7 @@ impl RailTerminalUI {
         spans
     }
 
-    fn get_ram(&self) -> Vec<Spans> {
+    fn get_ram(&self) -> Vec<Spans<'_>> {
         let mut spans = Vec::new();
         for line in 0..32 {
             let slice_left = self.rail_system.get_ram_slice(line * 4, (line * 4) + 3);
@@ -305,7 +343,7 @@ impl RailTerminalUI {
         spans
     }
 
-    fn get_call_stack(&self) -> Vec<Spans> {
+    fn get_call_stack(&self) -> Vec<Spans<'_>> {
         let mut spans = Vec::new();
         let ptr = self.rail_system.get_call_stack_ptr();
         for line in 0..32 {
@@ -317,7 +355,7 @@ impl RailTerminalUI {
             let mut line_vec = Vec::new();
             line_vec.push(Span::raw(" ".repeat(2)));
             line_vec.push(Span::raw(" ".repeat(Self::MEM_LEFT_PAD)));
-            let mut val_spans = Self::make_stack_span(local_ptr, slice);
+            let mut val_spans = Self::make_stack_span(local_ptr, slice, &|_| false);
             line_vec.append(&mut val_spans);
 
             spans.push(Spans::from(line_vec));
@@ -326,9 +364,28 @@ impl RailTerminalUI {
         spans
     }
 
-    fn get_gen_stack(&self) -> Vec<Spans> {
+        // gen stack entries the next instruction reads with S_PEEK or removes with S_DROP
+    fn get_gen_stack_access(&self) -> Vec<u8> {
+        let cnt = self.rail_system.get_cnt_register_value();
+        let code = self.rail_system.get_program_slice(cnt, cnt.saturating_add(3));
+        if code.len() < 4 {
+            return Vec::new();
+        }
+        let block = RailInstructionBlock::new(code[0], code[1], code[2], code[3]);
+        let amount = if block.is_arg2_immediate() { block.arg2 }
+                     else { self.rail_system.get_register_value(block.arg2 & 0x0F) };
+        let ptr = self.rail_system.get_gen_stack_ptr();
+        match block.get_instruction() {
+            RailInstruction::SPeek => vec![ptr.wrapping_sub(amount)],
+            RailInstruction::SDrop => (0..amount).map(|depth| ptr.wrapping_sub(depth)).collect(),
+            _ => Vec::new()
+        }
+    }
+
+    fn get_gen_stack(&self) -> Vec<Spans<'_>> {
         let mut spans = Vec::new();
         let ptr = self.rail_system.get_gen_stack_ptr();
+        let accessed = self.get_gen_stack_access();
         for line in 0..32 {
             let slice = self.rail_system.get_gen_stack_slice(line * 4, (line * 4) + 3);
 
@@ -338,7 +395,7 @@ impl RailTerminalUI {
             let mut line_vec = Vec::new();
             line_vec.push(Span::raw(" ".repeat(2)));
             line_vec.push(Span::raw(" ".repeat(Self::MEM_LEFT_PAD)));
-            let mut val_spans = Self::make_stack_span(local_ptr, slice);
+            let mut val_spans = Self::make_stack_span(local_ptr, slice, &|i| accessed.contains(&(line * 4 + i)));
             line_vec.append(&mut val_spans);
 
             spans.push(Spans::from(line_vec));
@@ -347,7 +404,7 @@ impl RailTerminalUI {
         spans
     }
 
-    fn make_span(is_executing: bool, slice: &[u8]) -> Span {
+    fn make_span(is_executing: bool, slice: &[u8]) -> Span<'_> {
             // this feels so wrong. There has to be a better way!
         let values = format!("{} {} {} {}", Self::hex_str(slice[0]), Self::hex_str(slice[1]),
                              Self::hex_str(slice[2]), Self::hex_str(slice[3]));
@@ -359,12 +416,15 @@ impl RailTerminalUI {
         }
     }
 
-    fn make_stack_span(local_ptr: u8, slice: &[u8]) -> Vec<Span> {
+    fn make_stack_span<'a>(local_ptr: u8, slice: &'a [u8], is_accessed: &dyn Fn(u8) -> bool) -> Vec<Span<'a>> {
         let mut res = Vec::new();
         for i in 0..4 {
             if i == local_ptr {
                 res.push(Span::styled(format!("{} ", Self::hex_str(slice[i as usize])), Style::default().fg(Color::Green)));
             }
+            else if is_accessed(i) {
+                res.push(Span::styled(format!("{} ", Self::hex_str(slice[i as usize])), Style::default().fg(Color::Yellow)));
+            }
             else {
                 res.push(Span::raw(format!("{} ", Self::hex_str(slice[i as usize]))));
             }
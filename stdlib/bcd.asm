@@ -0,0 +1,30 @@
+# &rail-asm-v2
+# std/bcd: decimal digits for the D0-D3 displays.
+# BCD8: R1 as packed BCD, tens and ones in R1, hundreds in R2. Uses R3.
+# SHOW_DEC: shows R1 in decimal, hundreds on D0, tens on D1 and ones on D2. Uses R1 to R3.
+
+LABEL BCD8
+MOV *0 R2
+LABEL BCD8_HUNDREDS
+IF_LT R1 *100 BCD8_TENS
+SUB R1 *100 R1
+ADD R2 *1 R2
+JMP BCD8_HUNDREDS
+LABEL BCD8_TENS
+MOV *0 R3
+LABEL BCD8_TENS_LOOP
+IF_LT R1 *10 BCD8_PACK
+SUB R1 *10 R1
+ADD R3 *1 R3
+JMP BCD8_TENS_LOOP
+LABEL BCD8_PACK
+SHL R3 *4 R3
+OR R1 R3 R1
+RET
+
+LABEL SHOW_DEC
+CALL BCD8
+MOV R2 D0
+SHR R1 *4 D1
+AND R1 *15 D2
+RET
@@ -0,0 +1,21 @@
+# &rail-asm-v2
+# std/divmod: 8 bit unsigned division by repeated subtraction.
+# DIVMOD: R1 / R2, quotient in R1, remainder in R2. Uses R3.
+# Dividing by 0 gives a quotient of 255 and the dividend as remainder.
+
+LABEL DIVMOD
+MOV *0 R3
+IF_EQ R2 *0 DIVMOD_BY_ZERO
+LABEL DIVMOD_LOOP
+IF_LT R1 R2 DIVMOD_DONE
+SUB R1 R2 R1
+ADD R3 *1 R3
+JMP DIVMOD_LOOP
+LABEL DIVMOD_DONE
+MOV R1 R2
+MOV R3 R1
+RET
+LABEL DIVMOD_BY_ZERO
+MOV R1 R2
+MOV *255 R1
+RET
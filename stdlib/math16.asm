@@ -0,0 +1,20 @@
+# &rail-asm-v2
+# std/math16: 16 bit arithmetic on register pairs, low byte first.
+# ADD16: R2:R1 + R4:R3, result in R2:R1.
+# SUB16: R2:R1 - R4:R3, result in R2:R1.
+
+LABEL ADD16
+ADD R1 R3 R1
+ADD R2 R4 R2
+IF_MTE R1 R3 ADD16_DONE
+ADD R2 *1 R2            # carry from the low byte
+LABEL ADD16_DONE
+RET
+
+LABEL SUB16
+IF_MTE R1 R3 SUB16_NO_BORROW
+SUB R2 *1 R2            # borrow from the high byte
+LABEL SUB16_NO_BORROW
+SUB R1 R3 R1
+SUB R2 R4 R2
+RET
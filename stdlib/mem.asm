@@ -0,0 +1,24 @@
+# &rail-asm-v2
+# std/mem: block operations over ram.
+# MEMCPY: copies R3 bytes from R1 to R2, front to back. Uses R1 to R4.
+# MEMSET: sets R3 bytes from R1 on to the value of R2. Uses R1 and R3.
+
+LABEL MEMCPY
+IF_EQ R3 *0 MEMCPY_DONE
+RAM_R 0 R1 R4
+RAM_W+IM1 R4 R2 0
+ADD R1 *1 R1
+ADD R2 *1 R2
+SUB R3 *1 R3
+JMP MEMCPY
+LABEL MEMCPY_DONE
+RET
+
+LABEL MEMSET
+IF_EQ R3 *0 MEMSET_DONE
+RAM_W+IM1 R2 R1 0
+ADD R1 *1 R1
+SUB R3 *1 R3
+JMP MEMSET
+LABEL MEMSET_DONE
+RET
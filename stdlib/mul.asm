@@ -0,0 +1,30 @@
+# &rail-asm-v2
+# std/mul: 8x8 -> 16 bit unsigned multiply.
+# MUL8: R1 * R2, result low byte in R1, high byte in R2. Uses R3 and R4, keeps R5 and R6.
+
+LABEL MUL8
+!ST< R5 R6
+MOV R1 R3               # multiplicand, low byte
+MOV *0 R4               # multiplicand, high byte
+MOV *0 R1
+MOV *0 R5
+LABEL MUL8_LOOP
+IF_EQ R2 *0 MUL8_DONE
+AND R2 *1 R6
+IF_EQ R6 *0 MUL8_SHIFT
+ADD R1 R3 R1
+IF_MTE R1 R3 MUL8_NO_CARRY
+ADD R5 *1 R5
+LABEL MUL8_NO_CARRY
+ADD R5 R4 R5
+LABEL MUL8_SHIFT
+SHL R4 *1 R4            # multiplicand <<= 1, across both bytes
+SHR R3 *7 R6
+OR R4 R6 R4
+SHL R3 *1 R3
+SHR R2 *1 R2
+JMP MUL8_LOOP
+LABEL MUL8_DONE
+MOV R5 R2
+!ST> R5 R6
+RET
@@ -0,0 +1,130 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+#[path = "../src/rail_optimizer/mod.rs"]
+pub mod rail_optimizer;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_flow::{decode_program, FlowKind};
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_optimizer::{RailOptimizer, RailOptimizerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn run(asm: &str) -> RailSystem {
+        let mut system = RailSystem::new_with_program(&RailAssembler::new().assemble(asm));
+        system.set_io_print(false);
+        for _i in 0..1000 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+    #[test]
+    fn test_base_and_offset() {
+        let system = run(r#"
+                # &rail-asm-v2
+                MOV *0xF0 R1
+                MOV *0x21 R2
+                MOV *0x42 R3
+                RAM_WX R2 R1 0x12
+                RAM_WX R3 *0x10 3
+                RAM_RX R1 *0x12 R4
+                RAM_RX *0x0D *6 R5
+                HALT"#);
+            // 0xF0 + 0x12 wraps around to 0x02
+        assert_eq!(system.get_ram_slice(0x02, 0x02), &[0x21]);
+        assert_eq!(system.get_ram_slice(0x13, 0x13), &[0x42]);
+        assert_eq!((system.get_register_value(4), system.get_register_value(5)), (0x21, 0x42));
+    }
+
+    #[test]
+    fn test_jump_table() {
+        let asm = r#"
+                # &rail-asm-v2
+                CONST table 0x20
+                MOV *second R1
+                RAM_WX R1 *table 0
+                MOV *first R1
+                RAM_WX R1 *table 1
+                RAM_RX R2 *table R3
+                JUMP_R R3
+                LABEL first
+                MOV *1 R4
+                HALT
+                LABEL second
+                MOV *2 R4
+                HALT"#;
+        let program = RailAssembler::new().assemble(asm);
+        let instructions = decode_program(&program);
+        assert_eq!(instructions[5].kind, FlowKind::IndirectJump);
+
+        let mut system = RailSystem::new_with_program(&program);
+        system.set_io_print(false);
+        system.set_register_value(2, 1);
+        while !system.is_halted() {
+            system.step();
+        }
+        assert_eq!(system.get_register_value(4), 1);
+        assert_eq!(run(asm).get_register_value(4), 2);
+    }
+
+    #[test]
+    fn test_function_pointer() {
+        let system = run(r#"
+                # &rail-asm-v2
+                MOV *double R1
+                MOV *5 R2
+                CALL_R R1
+                CALL_R R1
+                HALT
+                LABEL double
+                ADD R2 R2 R2
+                RET"#);
+        assert_eq!(system.get_register_value(2), 20);
+        assert_eq!(system.get_call_stack_ptr(), 0xFF);
+    }
+
+    #[test]
+    fn test_unaligned_target() {
+            // the instruction at 0xFD takes its RES from the first byte of the program, 0x00 for R0
+        let mut binary = RailAssembler::new().assemble("# &rail-asm-v2\nADD R0 R0 R2\nJUMP_R *0xFD");
+        binary.resize(256, 0);
+        binary[0xFD..].copy_from_slice(&[0x40, 0x01, 7]);     // ADD+IM2 R1 7
+        let mut system = RailSystem::new_with_program(&binary);
+        system.set_register_value(1, 5);
+        for _i in 0..3 {
+            system.step();
+        }
+        assert_eq!(system.get_register_value(0), 12);
+        assert_eq!(system.get_cnt_register_value(), 0x01);
+    }
+
+    #[test]
+    fn test_immediate_targets() {
+        let program = RailAssembler::new().assemble_program(r#"
+                # &rail-asm-v2
+                JUMP_R *hop
+                LABEL hop
+                JMP end
+                LABEL end
+                CALL_R *end
+                HALT"#);
+        let instructions = decode_program(&program.binary);
+        assert_eq!(instructions[0].kind, FlowKind::Jump(4));
+        assert_eq!(instructions[2].kind, FlowKind::Call(8));
+
+            // the jump through hop is threaded, which rewrites A and not RES, and then the
+            // JMP to the next instruction goes, which moves end to 4
+        let (optimized, stats) = RailOptimizer::new().optimize(&program);
+        assert_eq!((stats.threaded_jumps, stats.removed_moves), (1, 1));
+        assert_eq!(optimized.binary, &[0x94, 4, 0, 0, 0x95, 4, 0, 0, 0x0E, 0, 0, 0]);
+    }
+
+}
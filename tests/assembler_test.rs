@@ -1,4 +1,6 @@
 
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
 #[path = "../src/rail_assembler/mod.rs"]
 pub mod rail_assembler;
 
@@ -7,7 +9,6 @@ pub use crate::rail_assembler::rasm_dictionary::RasmDictionary;
 #[cfg(test)]
 mod tests {
     pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
-    pub use crate::rail_assembler::rasm_dictionary::RasmDictionary;
 
     fn assemble(asm: &str) -> Vec<u8> {
         let rail_assembler = RailAssembler::new();
@@ -38,4 +39,27 @@ mod tests {
         assert_eq!(assembled, &[0x00, 0b0101, 0b1010, 0b1001]);
     }
 
+    #[test]
+    fn test_flag_given_twice() {
+        let rail_assembler = RailAssembler::new();
+        let error = rail_assembler.try_assemble_program("ADD+IM1+IM1 1 2 R1").err().unwrap();
+        assert!(error.message.contains("already set"));
+        let error = rail_assembler.try_assemble_program("CALL+IM1 0 0 0").err().unwrap();     // CALL carries IM1
+        assert!(error.message.contains("already set"));
+        let error = rail_assembler.try_assemble_program("MOV+IM2 1 R1").err().unwrap();
+        assert!(error.message.contains("already set"));
+        let error = rail_assembler.try_assemble_program("ADD 0xF0+0x20 2 R1").err().unwrap();
+        assert!(error.message.contains("does not fit in a byte"));
+    }
+
+    #[test]
+    fn test_const_cycle() {
+        let rail_assembler = RailAssembler::new();
+        let error = rail_assembler.try_assemble_program("CONST X X\nADD X 0 R1").err().unwrap();
+        assert_eq!(error.line_number, 2);
+        assert!(error.message.contains("in terms of itself"));
+        let error = rail_assembler.try_assemble_program("CONST A B\nCONST B A\nADD A 0 R1").err().unwrap();
+        assert!(error.message.contains("in terms of itself"));
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,171 @@
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_linker/mod.rs"]
+pub mod rail_linker;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_linker::{RailObjectLinker, RailObjectLinkerTrait};
+    pub use crate::rail_linker::rail_object::RailObject;
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+    pub use crate::rail_system::rail_isa::IsaExtension;
+
+    const MAIN_ASM: &str = r#"
+                # &rail-asm-v2
+                # &rail-ext-banks
+                EXTERN triple
+                MOV+IM1 5 R1
+                FAR_CALL+IM1+IM2 ^triple triple
+                PBANK_R R3
+                HALT"#;
+
+    const LIB_ASM: &str = r#"
+                # &rail-asm-v2
+                # &rail-ext-banks
+                BANK 1
+                GLOBAL triple
+                LABEL triple
+                MOV R1 R2
+                CALL twice
+                PBANK_R R4
+                RET
+                LABEL twice
+                ADD R1 R2 R1
+                ADD R1 R2 R1
+                RET"#;
+
+    fn build_object(name: &str, asm: &str) -> RailObject {
+        let rail_assembler = RailAssembler::new();
+        RailObject::from_program(name, &rail_assembler.try_assemble_object(asm).unwrap_or_else(|e| panic!("{}", e)))
+    }
+
+    fn run(binary: &[u8]) -> RailSystem {
+        let mut system = RailSystem::new_with_program(binary);
+        system.set_io_print(false);
+        system.enable_extension(IsaExtension::Banks);
+        for _i in 0..100 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+    #[test]
+    fn test_far_call_and_return() {
+        let result = RailObjectLinker::new().link(&[build_object("main", MAIN_ASM), build_object("lib", LIB_ASM)])
+            .unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(result.binary.len(), 256 + 28);
+        assert_eq!(&result.binary[4..8], &[0xF9, 1, 0, 0]);     // FAR_CALL+IM1+IM2 ^triple triple
+        assert!(result.map.contains("1:0x00  global  TRIPLE  (lib)"));
+
+            // the CALL inside bank 1 returns to bank 1, the RET after it back to bank 0
+        let system = run(&result.binary);
+        assert_eq!(system.get_register_value(1), 15);
+        assert_eq!(system.get_register_value(4), 1);
+        assert_eq!(system.get_register_value(3), 0);
+        assert_eq!(system.get_program_bank(), 0);
+    }
+
+    #[test]
+    fn test_far_jump_past_64_instructions() {
+            // 70 instructions, the last ones in bank 1, which loads without running out of space
+        let mut asm = String::from("# &rail-asm-v2\n# &rail-ext-banks\n");
+        for _i in 0..62 {
+            asm.push_str("ADD+IM2 R1 1 R1\n");
+        }
+        asm.push_str("FAR_JMP+IM1+IM2 1 0\n");
+        let mut binary = RailAssembler::new().assemble(&asm);
+        binary.resize(256, 0);
+        binary.extend(RailAssembler::new().assemble("# &rail-asm-v2\nADD+IM2 R1 100 R1\nHALT"));
+
+        let system = run(&binary);
+        assert_eq!(system.get_register_value(1), 162);
+        assert_eq!(system.get_program_bank(), 1);
+        assert_eq!(system.get_program_slice(0, 3), &[0x40, 1, 100, 1]);
+    }
+
+    #[test]
+    fn test_ram_banks() {
+        let system = run(&RailAssembler::new().assemble(r#"
+                # &rail-asm-v2
+                # &rail-ext-banks
+                MOV+IM1 7 R1
+                RAM_W+IM1+IM2 R1 0x10
+                RBANK_W+IM1 2
+                MOV+IM1 9 R1
+                RAM_W+IM1+IM2 R1 0x10
+                RBANK_R R4
+                RBANK_W+IM1 0
+                RAM_R+IM2 0x10 R2
+                RBANK_W+IM1 2
+                RAM_R+IM2 0x10 R3
+                HALT"#));
+        assert_eq!(system.get_register_value(2), 7);
+        assert_eq!(system.get_register_value(3), 9);
+        assert_eq!(system.get_register_value(4), 2);
+        assert_eq!(system.get_ram_bank(), 2);
+    }
+
+    #[test]
+    fn test_banks_need_the_extension() {
+        let binary = RailAssembler::new().assemble(r#"
+                # &rail-asm-v2
+                # &rail-ext-banks
+                FAR_JMP+IM1+IM2 1 0
+                MOV+IM1 1 R1
+                HALT"#);
+        let mut system = RailSystem::new_with_program(&binary);
+        for _i in 0..3 {
+            system.step();
+        }
+        assert!(system.is_halted());
+        assert_eq!(system.get_register_value(1), 1);
+
+        let error = RailAssembler::new().try_assemble_program("# &rail-asm-v2\nBANK 1\nHALT").err().unwrap();
+        assert!(error.message.contains("banks extension"));
+        let error = RailAssembler::new().try_assemble_program("# &rail-asm-v2\n# &rail-ext-banks\nHALT\nBANK 1").err().unwrap();
+        assert!(error.message.contains("before any code"));
+    }
+
+    #[test]
+    fn test_bank_directive_places_the_code() {
+        let program = RailAssembler::new().try_assemble_program(r#"
+                # &rail-asm-v2
+                # &rail-ext-banks
+                BANK 2
+                ORG 0x10
+                LABEL here
+                FAR_JMP+IM1+IM2 ^here here"#).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(program.binary.len(), 2 * 256 + 0x10 + 4);
+        assert_eq!(&program.binary[2 * 256 + 0x10..], &[0xFA, 2, 0x10, 0]);
+    }
+
+    #[test]
+    fn test_object_text_keeps_banks() {
+        let object = build_object("main", MAIN_ASM);
+        assert!(object.relocations.iter().any(|relocation| relocation.is_bank));
+        let parsed = RailObject::from_text(&object.to_text()).unwrap();
+        assert_eq!(parsed.relocations.iter().filter(|relocation| relocation.is_bank).count(), 1);
+
+        let lib = RailObject::from_text(&build_object("lib", LIB_ASM).to_text()).unwrap();
+        assert_eq!(lib.bank, Some(1));
+    }
+
+    #[test]
+    fn test_link_banks_separately() {
+            // two objects at the same ORG only overlap in the same bank
+        let other = build_object("other", &LIB_ASM.replace("BANK 1", "BANK 2").replace("triple", "other"));
+        let objects = [build_object("main", MAIN_ASM), build_object("lib", LIB_ASM), other];
+        let result = RailObjectLinker::new().link(&objects).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(result.binary.len(), 2 * 256 + 28);
+
+        let same = build_object("same", &LIB_ASM.replace("BANK 1", "BANK 1\nORG 0").replace("triple", "same"));
+        let overlap = RailObjectLinker::new().link(&[build_object("lib", LIB_ASM), same]);
+        assert!(overlap.err().unwrap().contains("overlaps lib at 1:0x00"));
+    }
+
+}
@@ -0,0 +1,67 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_compiler/mod.rs"]
+pub mod rail_compiler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_compiler::{RailCompiler, RailCompilerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn run(source: &str) -> RailSystem {
+        let program = RailCompiler::new().compile_brainfuck_program(source).unwrap_or_else(|e| panic!("{}", e));
+        let mut system = RailSystem::new_with_program(&program.binary);
+        system.set_io_print(false);
+        for _i in 0..20000 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+    #[test]
+    fn test_loops_and_output() {
+            // 6 * 8 into the second cell, printed as '0'
+        let system = run("++++++ [ > ++++++++ < - ] > .");
+        assert_eq!(system.get_register_value(15), b'0');
+        assert_eq!(system.get_ram_slice(0, 1), &[0, 48]);
+        assert_eq!(system.get_register_value(1), 1);
+    }
+
+    #[test]
+    fn test_nested_loops_and_wrapping() {
+            // 3 * 4 * 5 = 60, and the pointer wraps around to the last cell
+        let system = run("+++[>++++[>+++++<-]<-]>>. <<< ---");
+        assert_eq!(system.get_ram_slice(0, 2), &[0, 0, 60]);
+        assert_eq!(system.get_ram_slice(255, 255), &[253]);
+        assert_eq!(system.get_register_value(15), 60);
+    }
+
+    #[test]
+    fn test_runs_are_merged() {
+        let asm = RailCompiler::new().compile_brainfuck("+++++ comments are ignored >>> --").unwrap_or_else(|e| panic!("{}", e));
+        let code: Vec<&str> = asm.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(code, &["RAM_R 0 R1 R2", "ADD+IM2 R2 5 R2", "RAM_W+IM1 R2 R1 0",
+                           "ADD+IM2 R1 3 R1",
+                           "RAM_R 0 R1 R2", "SUB+IM2 R2 2 R2", "RAM_W+IM1 R2 R1 0",
+                           "HALT 0 0 R0"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let rail_compiler = RailCompiler::new();
+        let unmatched = rail_compiler.compile_brainfuck("+\n+]").err().unwrap();
+        assert_eq!(unmatched.line_number, 2);
+        assert!(unmatched.message.contains("without a matching ["));
+        let unclosed = rail_compiler.compile_brainfuck("[\n[]").err().unwrap();
+        assert_eq!(unclosed.line_number, 1);
+        assert!(unclosed.message.contains("never closed"));
+        let too_long = rail_compiler.compile_brainfuck_program(&">.".repeat(40)).err().unwrap();
+        assert!(too_long.message.contains("needs 121 instructions"), "{}", too_long.message);
+    }
+
+}
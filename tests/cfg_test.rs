@@ -0,0 +1,124 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_cfg::{CfgEdgeKind, RailCfg};
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+
+    fn build_cfg(asm: &str) -> RailCfg {
+        let rail_assembler = RailAssembler::new();
+        RailCfg::from_program(&rail_assembler.assemble_program(asm))
+    }
+
+    #[test]
+    fn test_basic_blocks() {
+        let cfg = build_cfg(r#"
+                ADD+IM1+IM2 0 5 R1
+                LABEL loop
+                SUB+IM2 R1 1 R1
+                IF_N_EQ+IM2 R1 0 loop
+                HALT 0 0 R2"#);
+        let starts: Vec<u8> = cfg.blocks.iter().map(|block| block.start).collect();
+        assert_eq!(starts, &[0x00, 0x04, 0x0C]);
+        assert_eq!(cfg.get_block_at(0x08).unwrap().start, 0x04);
+
+        let taken = cfg.edges.iter().find(|edge| edge.kind == CfgEdgeKind::Taken).unwrap();
+        assert_eq!((taken.from, taken.to), (0x04, Some(0x04)));
+        assert_eq!(taken.label, "R1 != 0");
+        let not_taken = cfg.edges.iter().find(|edge| edge.kind == CfgEdgeKind::NotTaken).unwrap();
+        assert_eq!(not_taken.to, Some(0x0C));
+    }
+
+    #[test]
+    fn test_call_and_return_edges() {
+        let cfg = build_cfg(r#"
+                # &rail-asm-v2
+                CALL func
+                HALT
+                LABEL func
+                RET"#);
+        let call = cfg.edges.iter().find(|edge| edge.kind == CfgEdgeKind::Call).unwrap();
+        assert_eq!(call.to, Some(0x08));
+        let ret = cfg.edges.iter().find(|edge| edge.kind == CfgEdgeKind::Return).unwrap();
+        assert_eq!(ret.to, Some(0x04));
+        assert!(!cfg.edges.iter().any(|edge| edge.from == 0x08));
+    }
+
+    #[test]
+    fn test_computed_jump() {
+        let cfg = build_cfg(r#"
+                ADD+IM1+IM2 8 0 CNT
+                HALT 0 0 R1
+                HALT 0 0 R1"#);
+        assert_eq!(cfg.computed_jumps, &[0x00]);
+        assert!(cfg.to_dot().contains("b_00 -> computed"));
+
+            // the gen stack instructions that write a register
+        let cfg = build_cfg(r#"
+                S_PEEK+IM2 0 0 CNT
+                S_SP_R 0 0 CNT
+                HALT 0 0 R1"#);
+        assert_eq!(cfg.computed_jumps, &[0x00, 0x04]);
+    }
+
+    #[test]
+    fn test_far_call_and_jump() {
+        let cfg = build_cfg(r#"
+                # &rail-ext-banks
+                FAR_CALL+IM1+IM2 1 0 0
+                FAR_JMP+IM1+IM2 1 8 0
+                HALT 0 0 R1"#);
+        let starts: Vec<u8> = cfg.blocks.iter().map(|block| block.start).collect();
+        assert_eq!(starts, &[0x00, 0x04, 0x08]);
+        let edges: Vec<(u8, Option<u8>, CfgEdgeKind, &str)> = cfg.edges.iter()
+            .map(|edge| (edge.from, edge.to, edge.kind, edge.label.as_str()))
+            .collect();
+        assert_eq!(edges, &[(0x00, None, CfgEdgeKind::Bank, "call bank 1 at 0x00"),
+                            (0x00, Some(0x04), CfgEdgeKind::Return, "return"),
+                            (0x04, None, CfgEdgeKind::Bank, "bank 1 at 0x08")]);
+        assert!(cfg.computed_jumps.is_empty());
+        assert!(cfg.to_dot().contains("b_04 -> bank"));
+
+            // a bank in a register is not known
+        let cfg = build_cfg(r#"
+                # &rail-ext-banks
+                FAR_CALL R1 R2 0
+                HALT 0 0 R1"#);
+        assert_eq!(cfg.computed_jumps, &[0x00]);
+        assert_eq!(cfg.edges[0].label, "call bank R1 at R2");
+
+        let cfg = build_cfg(r#"
+                # &rail-ext-banks
+                PBANK_R 0 0 CNT
+                RBANK_R 0 0 CNT
+                HALT 0 0 R1"#);
+        assert_eq!(cfg.computed_jumps, &[0x00, 0x04]);
+    }
+
+    #[test]
+    fn test_dot_output() {
+        let cfg = build_cfg(r#"
+                LABEL loop
+                ADD+IM2 R1 1 R1
+                JMP 0 0 loop"#);
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph rail {"));
+        assert!(dot.contains("LOOP:\\l0x00  ADD+IM2 R1 1 R1\\l0x04  IF_T R0 R0 LOOP\\l"));
+        assert!(dot.contains("b_00 -> b_00"));
+    }
+
+    #[test]
+    fn test_from_binary() {
+        let cfg = RailCfg::from_binary(&[0x40, 0x00, 0x01, 0x01,
+                                         0x26, 0x00, 0x00, 0x00]);
+        assert_eq!(cfg.blocks.len(), 1);
+        assert!(cfg.to_dot().contains("IF_T R0 R0 0x00"));
+    }
+
+}
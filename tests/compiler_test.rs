@@ -0,0 +1,129 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_compiler/mod.rs"]
+pub mod rail_compiler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_compiler::{RailCompiler, RailCompilerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn run(source: &str) -> RailSystem {
+        let program = RailCompiler::new().compile_program(source).unwrap_or_else(|e| panic!("{}", e));
+        assert!(program.binary.len() <= 256, "{} bytes", program.binary.len());
+        let mut system = RailSystem::new_with_program(&program.binary);
+        system.set_io_print(false);
+        for _i in 0..20000 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        assert_eq!(system.get_gen_stack_ptr(), 0xFF);
+        system
+    }
+
+    fn error(source: &str) -> String {
+        RailCompiler::new().compile(source).err().unwrap().message
+    }
+
+    #[test]
+    fn test_loops_and_globals() {
+        let system = run(r#"
+                u8 total;
+                u8 limit = 10;
+
+                void main() {
+                    u8 i = 1;
+                    while (i <= limit) {
+                        if (i == 7) {
+                            i = i + 1;
+                            continue;       // leaves out 7
+                        }
+                        total = total + i;
+                        i = i + 1;
+                    }
+                    out(total);
+                }"#);
+        assert_eq!(system.get_ram_slice(0, 1), &[48, 10]);
+        assert_eq!(system.get_register_value(15), 48);
+    }
+
+    #[test]
+    fn test_recursion() {
+        let system = run(r#"
+                u8 fib(u8 n) {
+                    if (n < 2) {
+                        return n;
+                    }
+                    return fib(n - 1) + fib(n - 2);
+                }
+
+                void main() {
+                    out(fib(10));
+                }"#);
+        assert_eq!(system.get_register_value(15), 55);
+    }
+
+    #[test]
+    fn test_arrays_and_libcalls() {
+        let system = run(r#"
+                u8 squares[6];
+
+                void main() {
+                    u8 i = 0;
+                    while (i < 6) {
+                        squares[i] = i * i + 1;
+                        i = i + 1;
+                    }
+                    out(squares[5] % 7);
+                }"#);
+        assert_eq!(system.get_ram_slice(0, 5), &[1, 2, 5, 10, 17, 26]);
+        assert_eq!(system.get_register_value(15), 5);
+    }
+
+    #[test]
+    fn test_register_spilling() {
+        let system = run(r#"
+                u8 spill;
+
+                void main() {
+                    u8 a = 1;
+                    u8 b = 2;
+                    u8 c = 3;
+                    u8 d = 4;
+                    u8 e = 5;
+                    u8 f = 6;
+                    u8 g = 7;       // past R6, lives in ram
+                    spill = (a + b) - ((c + d) - ((e + f) - (g - a)));
+                    out(!(a > b) && (g == 7 || f));
+                }"#);
+            // 3 - (7 - (11 - 6))
+        assert_eq!(system.get_ram_slice(0, 0), &[1]);
+        assert_eq!(system.get_register_value(15), 1);
+    }
+
+    #[test]
+    fn test_generated_assembly() {
+        let asm = RailCompiler::new().compile("void main() { u8 x = 3; x = x + 4; }").unwrap_or_else(|e| panic!("{}", e));
+        assert!(asm.contains("CALL FN_MAIN 0 0\nHALT 0 0 R0"), "{}", asm);
+        assert!(asm.contains("ADD+IM1+IM2 3 0 R1\nADD+IM2 R1 4 R1\nRET 0 0 0"), "{}", asm);
+        assert!(!asm.contains("INCLUDE"));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert!(error("void f() {}").contains("No main function"));
+        assert!(error("void main() { x = 1; }").contains("x is not defined"));
+        assert!(error("u8 a[4];\nvoid main() {\n a[4] = 1; }").contains("out of bounds"));
+        assert!(error("void f() {}\nvoid main() { u8 x = f(); }").contains("f is void"));
+        assert!(error("u8 f(u8 a) { return a; }\nvoid main() { f(); }").contains("takes 1 argument(s)"));
+        assert!(error("void main() { break; }").contains("outside of a loop"));
+        let missing = RailCompiler::new().compile("void main() {\n u8 x = 1\n}").err().unwrap();
+        assert_eq!(missing.line_number, 3);
+        assert!(missing.message.contains("Expected ;"));
+    }
+
+}
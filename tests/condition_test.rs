@@ -0,0 +1,91 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_cfg::{CfgEdgeKind, RailCfg};
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+        // whether the condition jumps for A and B in registers
+    fn jumps(op: &str, a: u8, b: u8) -> bool {
+        let binary = RailAssembler::new().assemble(&format!(r#"
+                {} R1 R2 taken
+                HALT 0 0 R0
+                LABEL taken
+                ADD+IM1+IM2 1 0 R3
+                HALT 0 0 R0"#, op));
+        let mut system = RailSystem::new_with_program(&binary);
+        system.set_io_print(false);
+        system.set_register_value(1, a);
+        system.set_register_value(2, b);
+        while !system.is_halted() {
+            system.step();
+        }
+        system.get_register_value(3) == 1
+    }
+
+    #[test]
+    fn test_signed_comparisons() {
+            // -1 against 1, 1 against -1, -128 against 127 and equal values
+        let pairs = [(0xFF, 1), (1, 0xFF), (0x80, 0x7F), (0x90, 0x90)];
+        let results = |op: &str| -> Vec<bool> { pairs.iter().map(|(a, b)| jumps(op, *a, *b)).collect() };
+        assert_eq!(results("IF_LT_S"), &[true, false, true, false]);
+        assert_eq!(results("IF_LTE_S"), &[true, false, true, true]);
+        assert_eq!(results("IF_MT_S"), &[false, true, false, false]);
+        assert_eq!(results("IF_MTE_S"), &[false, true, false, true]);
+            // the unsigned ones see it the other way around
+        assert_eq!(results("IF_LT"), &[false, true, false, false]);
+    }
+
+    #[test]
+    fn test_bit_conditions() {
+        let bits = [0, 3, 7, 8, 0xFF];
+        let results = |op: &str| -> Vec<bool> { bits.iter().map(|bit| jumps(op, 0x89, *bit)).collect() };
+        assert_eq!(results("IF_BIT"), &[true, true, true, false, false]);
+        assert_eq!(results("IF_N_BIT"), &[false, false, false, true, true]);
+        assert!(!jumps("IF_BIT", 0x89, 1));
+        assert!(jumps("IF_N_BIT", 0x89, 1));
+    }
+
+    #[test]
+    fn test_structured_signed_condition() {
+        let mut system = RailSystem::new_with_program(&RailAssembler::new().assemble(r#"
+                # &rail-asm-v2
+                MOV *0xF6 R1
+                WHILE R1 <s 5
+                    ADD R1 *1 R1
+                    ADD R2 *1 R2
+                ENDWHILE
+                HALT"#));
+        system.set_io_print(false);
+        while !system.is_halted() {
+            system.step();
+        }
+            // from -10 up to 5
+        assert_eq!(system.get_register_value(1), 5);
+        assert_eq!(system.get_register_value(2), 15);
+    }
+
+    #[test]
+    fn test_analysis() {
+        let cfg = RailCfg::from_program(&RailAssembler::new().assemble_program(r#"
+                IF_LT_S+IM2 R1 0 negative
+                IF_BIT+IM2 R1 0 negative
+                IF_MT_S+IM1+IM2 0xFF 1 negative
+                HALT 0 0 R0
+                LABEL negative
+                HALT 0 0 R0"#));
+        let labels: Vec<&str> = cfg.edges.iter().filter(|edge| edge.kind == CfgEdgeKind::Taken)
+            .map(|edge| edge.label.as_str()).collect();
+        assert_eq!(labels, &["R1 <s 0", "bit 0 of R1"]);
+            // -1 > 1 never holds, so the third one always falls through
+        assert_eq!(cfg.get_block_at(0x08).unwrap().end, 0x0C);
+    }
+
+}
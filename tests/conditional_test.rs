@@ -0,0 +1,98 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+
+    const TRACE_ASM: &str = r#"
+                # &rail-asm-v2
+                CONST level 2
+                IFDEF debug
+                    MOV *1 IO
+                ELSE
+                    MOV *2 IO
+                ENDIF
+                IF level >= 2
+                    MOV *level R1
+                ENDIF
+                HALT"#;
+
+    #[test]
+    fn test_ifdef() {
+        let rail_assembler = RailAssembler::new();
+        assert_eq!(rail_assembler.assemble(TRACE_ASM), &[0xC0, 0x02, 0x00, 0x0F,
+                                                         0xC0, 0x02, 0x00, 0x01,
+                                                         0x0E, 0x00, 0x00, 0x00]);
+        let program = rail_assembler.assemble_program("CONST debug 0\nIFNDEF debug\nHALT 0 0 R0\nENDIF");
+        assert!(program.binary.is_empty());
+        assert_eq!(program.consts[0].references, 1);
+    }
+
+    #[test]
+    fn test_defines() {
+        let mut rail_assembler = RailAssembler::new();
+        rail_assembler.set_define("debug", "1");
+        rail_assembler.set_define("level", "1");    // overrides the CONST in the source
+        assert_eq!(rail_assembler.assemble(TRACE_ASM), &[0xC0, 0x01, 0x00, 0x0F,
+                                                         0x0E, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_expressions() {
+        let rail_assembler = RailAssembler::new();
+        let binary = rail_assembler.assemble(r#"
+                CONST width 8
+                CONST size width
+                IF size
+                    HALT 0 0 R1
+                ENDIF
+                IF 0x10 != size
+                    HALT 0 0 R2
+                ELSE
+                    HALT 0 0 R3
+                ENDIF"#);
+        assert_eq!(binary, &[0x0E, 0x00, 0x00, 0x01,
+                             0x0E, 0x00, 0x00, 0x02]);
+    }
+
+    #[test]
+    fn test_nesting_with_structured_if() {
+        let rail_assembler = RailAssembler::new();
+        let asm = r#"
+                IFDEF debug
+                    IF R1 < R2
+                        HALT 0 0 R1
+                    ELSE
+                        HALT 0 0 R2
+                    ENDIF
+                ENDIF
+                IF R1 == R2
+                    IFNDEF debug
+                        HALT 0 0 R3
+                    ENDIF
+                ENDIF"#;
+        assert_eq!(rail_assembler.assemble(asm), &[0x21, 0x01, 0x02, 0x08,    // IF_N_EQ R1 R2 past the HALT
+                                                   0x0E, 0x00, 0x00, 0x03]);
+
+        let mut debug_assembler = RailAssembler::new();
+        debug_assembler.set_define("debug", "1");
+        assert_eq!(debug_assembler.assemble(asm).len(), 4 * 4 + 4);
+    }
+
+    #[test]
+    fn test_conditional_errors() {
+        let rail_assembler = RailAssembler::new();
+        let error = |asm: &str| rail_assembler.try_assemble_program(asm).err().unwrap();
+        let unclosed = error("HALT 0 0 R0\nIFDEF debug\nHALT 0 0 R0");
+        assert_eq!(unclosed.line_number, 2);
+        assert!(unclosed.message.contains("no ENDIF"));
+        assert!(error("IF missing\nENDIF").message.contains("MISSING is not defined"));
+        assert!(error("IFDEF x\nELSE\nELSE\nENDIF").message.contains("ELSE without a matching IF"));
+        assert!(error("IFDEF\nENDIF").message.contains("IFDEF has no name"));
+    }
+
+}
@@ -0,0 +1,99 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+#[path = "../src/rail_optimizer/mod.rs"]
+pub mod rail_optimizer;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_equivalence::{EquivDifference, EquivLocation, EquivSpec, RailEquivalence, RailEquivalenceTrait};
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_optimizer::{RailOptimizer, RailOptimizerTrait};
+    pub use crate::rail_system::rail_isa::IsaExtension;
+
+    fn assemble(asm: &str) -> Vec<u8> {
+        RailAssembler::new().assemble(asm)
+    }
+
+    #[test]
+    fn test_hand_refactor() {
+        let first = assemble("ADD R1 R1 R2\nADD R2 R1 R1\nADD+IM1+IM2 0 0 R2\nHALT 0 0 R0");
+        let second = assemble("SHL+IM2 R1 1 R2\nADD R1 R2 R1\nSUB R2 R2 R2\nHALT 0 0 R0");
+        let report = RailEquivalence::new().check(&first, &second, &EquivSpec::new(&[EquivLocation::Register(1)]));
+        assert!(report.is_equivalent());
+        assert!(report.is_exhaustive);
+        assert_eq!(report.cases, 256);
+    }
+
+    #[test]
+    fn test_optimizer_output() {
+        let program = RailAssembler::new().assemble_program(r#"
+                # &rail-asm-v2
+                MOV R1 R1
+                ADD R1 R2 R3
+                JMP skip
+                LABEL skip
+                MOV *4 R4
+                ADD R4 *1 R4
+                SUB R3 R4 R3
+                HALT"#);
+        let (optimized, _) = RailOptimizer::new().optimize(&program);
+        assert!(optimized.binary.len() < program.binary.len());
+        let spec = EquivSpec::new(&[EquivLocation::Register(1), EquivLocation::Register(2)]);
+        let report = RailEquivalence::new().check(&program.binary, &optimized.binary, &spec);
+        assert!(report.is_equivalent());
+        assert_eq!(report.cases, 65536);
+    }
+
+    #[test]
+    fn test_counterexample() {
+        let first = assemble("ADD+IM2 R1 1 R2\nHALT 0 0 R0");
+        let second = assemble("OR+IM2 R1 1 R2\nHALT 0 0 R0");
+        let report = RailEquivalence::new().check(&first, &second, &EquivSpec::new(&[EquivLocation::Register(1)]));
+        let counterexample = report.counterexample.unwrap();
+        assert_eq!(counterexample.inputs, &[(EquivLocation::Register(1), 1)]);
+        assert_eq!(counterexample.difference, EquivDifference::Value { location: EquivLocation::Register(2), first: 2, second: 1 });
+        assert_eq!(counterexample.to_string(), "With R1 = 1: R2 is 2 after the first program and 1 after the second.");
+        assert_eq!(report.cases, 2);
+    }
+
+    #[test]
+    fn test_halting_and_ram() {
+            // spins forever when ram[0x10] is 5
+        let first = assemble("RAM_R+IM2 0 0x10 R1\nLABEL spin\nIF_EQ+IM2 R1 5 spin\nHALT 0 0 R0");
+        let second = assemble("RAM_R+IM2 0 0x10 R1\nHALT 0 0 R0");
+        let mut spec = EquivSpec::new(&[EquivLocation::Ram(0x10)]);
+        spec.max_steps = 64;
+        let counterexample = RailEquivalence::new().check(&first, &second, &spec).counterexample.unwrap();
+        assert_eq!(counterexample.difference, EquivDifference::Halting { first: false, second: true });
+        assert_eq!(counterexample.to_string(), "With ram[0x10] = 5: the first program does not halt, the second halts.");
+    }
+
+    #[test]
+    fn test_sampled_inputs() {
+        let first = assemble("XOR R1 R2 R4\nXOR R4 R3 R4\nHALT 0 0 R0");
+        let second = assemble("XOR R3 R2 R4\nXOR R1 R4 R4\nHALT 0 0 R0");
+        let mut spec = EquivSpec::new(&[EquivLocation::Register(1), EquivLocation::Register(2), EquivLocation::Register(3)]);
+        spec.max_cases = 1000;
+        let report = RailEquivalence::new().check(&first, &second, &spec);
+        assert!(report.is_equivalent());
+        assert!(!report.is_exhaustive);
+        assert_eq!(report.cases, 1000);
+    }
+
+    #[test]
+    fn test_extensions_run() {
+            // without the extension both MULs are no-ops, and look equivalent
+        let first = assemble("# &rail-ext-muldiv\nMUL+IM2 R1 3 R2\nHALT 0 0 R0");
+        let second = assemble("# &rail-ext-muldiv\nMUL+IM2 R1 5 R2\nHALT 0 0 R0");
+        let mut spec = EquivSpec::new(&[EquivLocation::Register(1)]);
+        spec.extensions = vec![IsaExtension::MulDiv];
+        let counterexample = RailEquivalence::new().check(&first, &second, &spec).counterexample.unwrap();
+        assert_eq!(counterexample.difference, EquivDifference::Value { location: EquivLocation::Register(2), first: 3, second: 5 });
+    }
+
+}
@@ -0,0 +1,134 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+#[path = "../src/rail_optimizer/mod.rs"]
+pub mod rail_optimizer;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_optimizer::{RailOptimizer, RailOptimizerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+    pub use crate::rail_system::rail_isa::{IsaExtension, FLAG_C, FLAG_V, FLAG_Z};
+
+    fn run(asm: &str, enabled: bool) -> RailSystem {
+        let mut system = RailSystem::new_with_program(&RailAssembler::new().assemble(asm));
+        system.set_io_print(false);
+        if enabled {
+            system.enable_extension(IsaExtension::Flags);
+        }
+        for _i in 0..1000 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+        // flags after A op B, and the result
+    fn flags_after(op: &str, a: u8, b: u8) -> (u8, u8) {
+        let system = run(&format!("# &rail-ext-flags\n{}+IM1+IM2 {} {} R1\nHALT 0 0 R0", op, a, b), true);
+        (system.get_flags(), system.get_register_value(1))
+    }
+
+    #[test]
+    fn test_arithmetic_flags() {
+        assert_eq!(flags_after("ADD", 0x7F, 1), (FLAG_V, 0x80));
+        assert_eq!(flags_after("ADD", 0xFF, 1), (FLAG_C | FLAG_Z, 0));
+        assert_eq!(flags_after("ADD", 0x80, 0x80), (FLAG_C | FLAG_Z | FLAG_V, 0));
+        assert_eq!(flags_after("ADD", 2, 3), (0, 5));
+        assert_eq!(flags_after("SUB", 1, 2), (FLAG_C, 0xFF));
+        assert_eq!(flags_after("SUB", 0x80, 1), (FLAG_V, 0x7F));
+        assert_eq!(flags_after("SUB", 7, 7), (FLAG_Z, 0));
+        assert_eq!(flags_after("AND", 0xF0, 0x0F), (FLAG_Z, 0));
+        assert_eq!(flags_after("XOR", 0xF0, 0x0F), (0, 0xFF));
+    }
+
+    #[test]
+    fn test_multi_byte_arithmetic() {
+            // 0x12F0 + 0x0320 = 0x1610, then 0x1610 - 0x0620 = 0x0FF0
+        let system = run(r#"
+                # &rail-asm-v2
+                # &rail-ext-flags
+                MOV *0xF0 R1
+                MOV *0x12 R2
+                ADD R1 *0x20 R1
+                ADC R2 *0x03 R2
+                MOV R1 R3
+                MOV R2 R4
+                SUB R3 *0x20 R3
+                SBB R4 *0x06 R4
+                HALT"#, true);
+        let registers: Vec<u8> = (1..=4).map(|reg| system.get_register_value(reg)).collect();
+        assert_eq!(registers, &[0x10, 0x16, 0xF0, 0x0F]);
+    }
+
+    #[test]
+    fn test_flag_jumps() {
+            // counts the additions of 0x40 until the carry
+        let system = run(r#"
+                # &rail-asm-v2
+                # &rail-ext-flags
+                LABEL loop
+                ADD R2 *1 R2
+                ADD R1 *0x40 R1
+                IF_N_C loop
+                IF_Z zero
+                HALT
+                LABEL zero
+                MOV *1 R3
+                FLG_W *0x07
+                FLG_R R4
+                HALT"#, true);
+        assert_eq!(system.get_register_value(2), 4);
+        assert_eq!(system.get_register_value(3), 1);
+        assert_eq!(system.get_register_value(4), FLAG_C | FLAG_Z | FLAG_V);
+        assert_eq!(system.get_flags(), FLAG_C | FLAG_Z | FLAG_V);
+    }
+
+    #[test]
+    fn test_not_enabled() {
+        let error = RailAssembler::new().try_assemble_program("ADC R1 R2 R3").err().unwrap();
+        assert!(error.message.contains("ADC is part of the flags extension"), "{}", error.message);
+
+            // without the extension the machine keeps no flags and ADC does nothing
+        let system = run("# &rail-ext-flags\nADD+IM1+IM2 0xFF 1 R1\nADC+IM1+IM2 1 1 R2\nHALT 0 0 R0", false);
+        assert_eq!(system.get_flags(), 0);
+        assert_eq!(system.get_register_value(2), 0);
+    }
+
+    #[test]
+    fn test_optimizer_keeps_flag_writes() {
+            // ADD R1 0 R1 is no move here, it clears the carry
+        let program = RailAssembler::new().assemble_program(r#"
+                # &rail-asm-v2
+                # &rail-ext-flags
+                ADD R1 *0xFF R2
+                ADD R1 *0 R1
+                ADC R3 *0 R3
+                HALT"#);
+        let (optimized, stats) = RailOptimizer::new().optimize(&program);
+        assert_eq!(optimized.binary, program.binary);
+        assert_eq!(stats.get_removed_instructions(), 0);
+    }
+
+    #[test]
+    fn test_flag_write_is_no_jump() {
+            // FLG_W writes no register, whatever its RES byte holds
+        let program = RailAssembler::new().assemble_program(r#"
+                # &rail-ext-flags
+                FLG_W+IM1 1 0 14
+                JMP 0 0 end
+                HALT 0 0 R1
+                LABEL end
+                HALT 0 0 R2"#);
+        let (optimized, stats) = RailOptimizer::new().optimize(&program);
+        assert_eq!(stats.removed_dead_code, 1);
+        assert_eq!(optimized.binary.len(), 8);      // the JMP then lands on the next instruction too
+    }
+
+}
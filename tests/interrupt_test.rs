@@ -0,0 +1,157 @@
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_cfg::{CfgEdgeKind, RailCfg};
+    pub use crate::rail_analysis::rail_linter::{LintKind, RailLinter, RailLinterTrait};
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+    pub use crate::rail_system::rail_isa::{IsaExtension, FLAG_C};
+
+        // counts R1 up to 20 in the main loop, and R2 once per interrupt on line 0
+    const COUNTER_ASM: &str = r#"
+                # &rail-asm-v2
+                # &rail-ext-interrupts
+                INT_VEC+IM1 0 tick
+                INT_EN+IM1 1
+                LABEL loop
+                ADD+IM2 R1 1 R1
+                IF_LT+IM2 R1 20 loop
+                HALT
+                LABEL tick
+                ADD+IM2 R2 1 R2
+                RETI"#;
+
+    fn new_system(asm: &str, enabled: bool) -> RailSystem {
+        let mut system = RailSystem::new_with_program(&RailAssembler::new().assemble(asm));
+        system.set_io_print(false);
+        if enabled {
+            system.enable_extension(IsaExtension::Interrupts);
+        }
+        system
+    }
+
+    fn run(system: &mut RailSystem, steps: u32) {
+        for _i in 0..steps {
+            system.step();
+            if system.is_halted() { break }
+        }
+    }
+
+    #[test]
+    fn test_handler_runs_between_instructions() {
+        let mut system = new_system(COUNTER_ASM, true);
+        run(&mut system, 6);
+        let cnt = system.get_cnt_register_value();
+        let r1 = system.get_register_value(1);
+
+            // entering takes a step, then the handler runs and returns where the loop was
+        system.raise_interrupt(0);
+        assert_eq!(system.get_pending_interrupts(), 1);
+        system.step();
+        assert!(system.is_in_interrupt());
+        assert_eq!(system.get_pending_interrupts(), 0);
+        assert_eq!(system.get_cnt_register_value(), 0x14);
+        assert_eq!(system.get_call_stack_slice(0, 0), &[cnt]);
+        run(&mut system, 2);
+        assert!(!system.is_in_interrupt());
+        assert_eq!(system.get_cnt_register_value(), cnt);
+        assert_eq!(system.get_call_stack_ptr(), 0xFF);
+        assert_eq!(system.get_register_value(1), r1);
+
+        system.raise_interrupt(0);
+        run(&mut system, 1000);
+        assert!(system.is_halted());
+        assert_eq!(system.get_register_value(1), 20);
+        assert_eq!(system.get_register_value(2), 2);
+    }
+
+    #[test]
+    fn test_masked_lines_wait() {
+        let mut system = new_system(&COUNTER_ASM.replace("INT_EN+IM1 1", "INT_EN+IM1 0"), true);
+        run(&mut system, 4);
+        system.raise_interrupt(0);
+        system.raise_interrupt(9);      // no such line
+        run(&mut system, 1000);
+        assert_eq!(system.get_register_value(2), 0);
+        assert_eq!(system.get_pending_interrupts(), 1);
+    }
+
+    #[test]
+    fn test_lowest_line_first_and_no_nesting() {
+        let mut system = new_system(r#"
+                # &rail-asm-v2
+                # &rail-ext-interrupts
+                INT_VEC+IM1 1 one
+                INT_VEC+IM1 3 three
+                INT_EN+IM1 0xFF
+                LABEL loop
+                JMP 0 0 loop
+                LABEL one
+                ADD+IM2 R1 1 R1
+                MOV R1 R3
+                RETI
+                LABEL three
+                ADD+IM2 R1 1 R1
+                MOV R1 R4
+                RETI"#, true);
+        run(&mut system, 4);
+        system.raise_interrupt(3);
+        system.raise_interrupt(1);
+        run(&mut system, 2);
+        system.raise_interrupt(1);      // while the handler of line 1 runs
+        run(&mut system, 20);
+        assert_eq!(system.get_register_value(4), 3);
+        assert_eq!(system.get_register_value(3), 2);
+        assert_eq!(system.get_pending_interrupts(), 0);
+    }
+
+    #[test]
+    fn test_reti_restores_the_flags() {
+        let mut system = new_system(r#"
+                # &rail-asm-v2
+                # &rail-ext-interrupts
+                # &rail-ext-flags
+                INT_VEC+IM1 0 handler
+                INT_EN+IM1 1
+                ADD+IM1+IM2 0xFF 1 R1
+                HALT
+                LABEL handler
+                ADD R0 R0 R0
+                RETI"#, true);
+        system.enable_extension(IsaExtension::Flags);
+        run(&mut system, 3);
+        system.raise_interrupt(0);
+        run(&mut system, 3);
+        assert_eq!(system.get_flags() & FLAG_C, FLAG_C);     // the handler's ADD cleared it
+    }
+
+    #[test]
+    fn test_without_the_extension() {
+        let mut system = new_system(COUNTER_ASM, false);
+        run(&mut system, 6);
+        system.raise_interrupt(0);
+        run(&mut system, 1000);
+        assert_eq!(system.get_register_value(2), 0);
+        assert!(!system.is_in_interrupt());
+    }
+
+    #[test]
+    fn test_analyses_see_the_handler() {
+        let kinds: Vec<LintKind> = RailLinter::new().lint(COUNTER_ASM).into_iter().map(|warning| warning.kind).collect();
+        assert!(!kinds.contains(&LintKind::UnreachableCode));
+        assert!(!kinds.contains(&LintKind::UnmatchedRet));
+
+        let program = RailAssembler::new().assemble_program(COUNTER_ASM);
+        let cfg = RailCfg::from_program(&program);
+        let edge = cfg.edges.iter().find(|edge| edge.kind == CfgEdgeKind::Call).unwrap();
+        assert_eq!(edge.to, Some(0x14));
+        assert_eq!(edge.label, "interrupt");
+    }
+
+}
@@ -0,0 +1,102 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_flow::FlowInstruction;
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_assembler::rasm_dictionary::RasmDictionary;
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+    pub use crate::rail_system::rail_instruction::RailInstruction;
+    pub use crate::rail_system::rail_instruction_block::RailInstructionBlock;
+    pub use crate::rail_system::rail_isa::{self, IsaExtension, IsaOperand, ISA};
+
+    fn assembler() -> RailAssembler {
+        let mut assembler = RailAssembler::new();
+        for extension in IsaExtension::ALL {
+            assembler.enable_extension(extension);
+        }
+        assembler
+    }
+
+    fn disassemble(bytes: [u8; 4]) -> String {
+        FlowInstruction::new(0, RailInstructionBlock::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+            .to_asm(&|address| address.to_string())
+    }
+
+    #[test]
+    fn test_encoder_and_decoder_agree() {
+        for entry in &ISA {
+            assert_eq!(RasmDictionary::translate(entry.mnemonic), Ok(entry.opcode), "{}", entry.mnemonic);
+            assert_eq!(assembler().assemble(&format!("{} 1 2 3", entry.mnemonic)), &[entry.opcode, 1, 2, 3]);
+            let block = RailInstructionBlock::new(entry.opcode, 1, 2, 3);
+            assert_eq!(block.get_instruction(), entry.instruction, "{}", entry.mnemonic);
+            assert_eq!(block.get_subsystem(), entry.subsystem, "{}", entry.mnemonic);
+            assert!(RasmDictionary::MNEMONICS.contains(&entry.mnemonic));
+            assert_eq!(RasmDictionary::describe(entry.mnemonic), Some(entry.semantics));
+        }
+    }
+
+    #[test]
+    fn test_disassembler_round_trip() {
+        for entry in &ISA {
+            for flags in [0, rail_isa::IM1, rail_isa::IM2, rail_isa::IM1 | rail_isa::IM2] {
+                let bytes = [entry.opcode | flags, 1, 2, 3];
+                let asm = disassemble(bytes);
+                assert_eq!(assembler().assemble(&asm), &bytes, "{}", asm);
+            }
+        }
+            // CALL without its immediate flag has no mnemonic
+        assert_eq!(disassemble([0x1B, 1, 0, 0]), "0x1B R1 R0 0");
+    }
+
+    #[test]
+    fn test_unassigned_opcodes() {
+        let assigned: Vec<u8> = ISA.iter().map(|entry| entry.get_code()).collect();
+        for code in (0..64u8).filter(|code| !assigned.contains(code)) {
+            assert_eq!(RailInstructionBlock::new(code, 0, 0, 0).get_instruction(), RailInstruction::None);
+            assert_eq!(disassemble([code, 1, 2, 3]), format!("0x{:02X} 0x01 0x02 0x03", code));
+        }
+    }
+
+        // no ALU result of R1 and R2 lands on the value R3 already holds
+    const VALUES: [u8; 8] = [0, 0x3A, 0x05, 0x17, 0x99, 0x64, 0x2E, 0xD3];
+
+    #[test]
+    fn test_simulator_writes_only_the_target() {
+            // control flow instructions move CNT, and RET, RETI and S_POP need something on the stacks
+        let entries = ISA.iter().filter(|entry| !entry.operands.contains(&IsaOperand::Address)
+            && !matches!(entry.instruction, RailInstruction::Ret | RailInstruction::InterruptReturn));
+        for entry in entries {
+                // register operands are read through their index only with IM1 set
+            let op = if entry.operands[0] == IsaOperand::Register { entry.opcode | rail_isa::IM1 } else { entry.opcode };
+            let mut system = RailSystem::new_with_program(&[0x99, 6, 0, 0, op, 1, 2, 3]);
+            system.set_io_print(false);
+            for extension in IsaExtension::ALL {
+                system.enable_extension(extension);
+            }
+            for (reg, value) in VALUES.iter().enumerate() {
+                system.set_register_value(reg as u8, *value);
+            }
+            system.step();
+            system.step();
+            for reg in (0..16).filter(|reg| *reg != 14) {
+                let expected = VALUES.get(reg as usize).copied().unwrap_or(0);
+                let changed = system.get_register_value(reg) != expected;
+                let is_target = reg == 3 && entry.operands[2] == IsaOperand::Target;
+                assert_eq!(changed, is_target, "{} and {}", entry.mnemonic, reg);
+            }
+        }
+    }
+
+    #[test]
+    fn test_readme_reference() {
+        assert!(include_str!("../readme.md").contains(&rail_isa::to_markdown()));
+    }
+
+}
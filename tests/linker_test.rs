@@ -0,0 +1,111 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_linker/mod.rs"]
+pub mod rail_linker;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_linker::{RailObjectLinker, RailObjectLinkerTrait};
+    pub use crate::rail_linker::rail_object::RailObject;
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn build_object(name: &str, asm: &str) -> RailObject {
+        let rail_assembler = RailAssembler::new();
+        RailObject::from_program(name, &rail_assembler.try_assemble_object(asm).unwrap_or_else(|e| panic!("{}", e)))
+    }
+
+    const MAIN_ASM: &str = r#"
+                # &rail-asm-v2
+                EXTERN double
+                MOV+IM1 3 R1
+                CALL double
+                HALT"#;
+
+    const LIB_ASM: &str = r#"
+                # &rail-asm-v2
+                GLOBAL double
+                LABEL double
+                ADD R1 R1 R1
+                JMP 0 0 done
+                LABEL done
+                RET"#;
+
+    #[test]
+    fn test_object_relocations() {
+        let object = build_object("lib", LIB_ASM);
+        assert_eq!(object.relocations.len(), 1);
+        assert_eq!(object.relocations[0].offset, 7);
+        assert_eq!(object.relocations[0].symbol, None);
+        assert!(object.symbols.iter().any(|symbol| symbol.name == "DOUBLE" && symbol.is_global));
+
+        let main = build_object("main", MAIN_ASM);
+        assert_eq!(main.externs, &["DOUBLE"]);
+        assert_eq!(main.relocations[0].symbol.as_deref(), Some("DOUBLE"));
+    }
+
+    #[test]
+    fn test_object_text_roundtrip() {
+        let object = build_object("lib", LIB_ASM);
+        let parsed = RailObject::from_text(&object.to_text()).unwrap();
+        assert_eq!(parsed.name, "lib");
+        assert_eq!(parsed.code, object.code);
+        assert_eq!(parsed.symbols.len(), object.symbols.len());
+        assert_eq!(parsed.relocations.len(), object.relocations.len());
+        assert!(RailObject::from_text("CODE 00").is_err());
+    }
+
+    #[test]
+    fn test_link_and_run() {
+        let objects = [build_object("main", MAIN_ASM), build_object("lib", LIB_ASM)];
+        let result = RailObjectLinker::new().link(&objects).unwrap();
+        assert_eq!(result.binary[5], 12);           // CALL double
+        assert_eq!(result.binary[12 + 7], 20);      // JMP done, moved by 12
+        assert!(result.map.contains("0x0C  global  DOUBLE  (lib)"));
+
+        let mut system = RailSystem::new_with_program(&result.binary);
+        for _i in 0..16 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert_eq!(system.get_register_value(1), 6);
+    }
+
+    #[test]
+    fn test_link_org() {
+        let lib = build_object("lib", &format!("ORG 0x40\n{}", LIB_ASM));
+        let result = RailObjectLinker::new().link(&[build_object("main", MAIN_ASM), lib]).unwrap();
+        assert_eq!(result.binary.len(), 0x40 + 12);
+        assert_eq!(result.binary[5], 0x40);
+        assert_eq!(result.binary[0x40 + 7], 0x48);
+    }
+
+    #[test]
+    fn test_link_errors() {
+        let linker = RailObjectLinker::new();
+        let undefined = linker.link(&[build_object("main", MAIN_ASM)]);
+        assert!(undefined.err().unwrap().contains("Undefined external DOUBLE"));
+
+        let duplicate = linker.link(&[build_object("a", LIB_ASM), build_object("b", LIB_ASM)]);
+        assert!(duplicate.err().unwrap().contains("defined in both"));
+
+        let overlap = linker.link(&[build_object("main", MAIN_ASM), build_object("lib", &format!("ORG 4\n{}", LIB_ASM))]);
+        assert!(overlap.err().unwrap().contains("overlaps"));
+    }
+
+    #[test]
+    fn test_assemble_with_org() {
+        let rail_assembler = RailAssembler::new();
+        let program = rail_assembler.assemble_program(r#"
+                ORG 8
+                LABEL start
+                JMP 0 0 start"#);
+        assert_eq!(program.binary, &[0, 0, 0, 0, 0, 0, 0, 0, 0x26, 0, 0, 8]);
+        assert!(rail_assembler.try_assemble_program("EXTERN x\nJMP 0 0 x").is_err());
+        assert!(rail_assembler.try_assemble_program("HALT 0 0 R1\nORG 8").is_err());
+    }
+
+}
@@ -0,0 +1,123 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_linter::{LintKind, RailLinter, RailLinterTrait};
+
+    fn lint_kinds(asm: &str) -> Vec<LintKind> {
+        let linter = RailLinter::new();
+        linter.lint(asm).iter().map(|warning| warning.kind).collect()
+    }
+
+    fn count(kinds: &[LintKind], kind: LintKind) -> usize {
+        kinds.iter().filter(|k| **k == kind).count()
+    }
+
+    #[test]
+    fn test_clean_program() {
+        let kinds = lint_kinds(r#"
+                ADD+IM1+IM2 1 2 R1
+                ADD+IM2 R1 1 R2
+                HALT 0 0 R3"#);
+        assert!(kinds.is_empty(), "{:?}", kinds);
+    }
+
+    #[test]
+    fn test_included_module_routines() {
+        let kinds = lint_kinds(r#"
+                # &rail-asm-v2
+                MOV *200 R1
+                CALL bcd8
+                HALT 0 0 R3
+                INCLUDE std/bcd"#);
+        assert!(kinds.is_empty(), "{:?}", kinds);
+    }
+
+    #[test]
+    fn test_missing_halt() {
+        let kinds = lint_kinds("ADD+IM1+IM2 1 2 R1");
+        assert_eq!(kinds, &[LintKind::MissingHalt]);
+    }
+
+    #[test]
+    fn test_unreachable_code_and_unused_symbols() {
+        let kinds = lint_kinds(r#"
+                CONST unused 4
+                LABEL start
+                LABEL end
+                JMP 0 0 end
+                ADD+IM1+IM2 1 2 R1
+                ADD+IM1+IM2 1 2 R1"#);
+        assert_eq!(count(&kinds, LintKind::UnusedConst), 1);
+        assert_eq!(count(&kinds, LintKind::UnusedLabel), 1);
+        assert_eq!(count(&kinds, LintKind::UnreachableCode), 1);
+    }
+
+    #[test]
+    fn test_far_jump_ends_the_code() {
+        let kinds = lint_kinds(r#"
+                # &rail-ext-banks
+                FAR_CALL+IM1+IM2 1 0 0
+                FAR_JMP+IM1+IM2 1 8 0
+                HALT 0 0 R1"#);
+        assert_eq!(count(&kinds, LintKind::UnreachableCode), 1, "{:?}", kinds);
+    }
+
+    #[test]
+    fn test_cnt_write() {
+        let kinds = lint_kinds(r#"
+                ADD+IM1+IM2 8 0 CNT
+                HALT 0 0 R1
+                HALT 0 0 R1"#);
+        assert_eq!(count(&kinds, LintKind::CntWrite), 1);
+    }
+
+    #[test]
+    fn test_uninitialized_read() {
+        let kinds = lint_kinds(r#"
+                ADD R3 R3 R4
+                ADD R4 R4 R4
+                HALT 0 0 R4"#);
+        assert_eq!(kinds, &[LintKind::UninitializedRead]);
+    }
+
+    #[test]
+    fn test_unmatched_ret() {
+        let kinds = lint_kinds(r#"
+                # &rail-asm-v2
+                CALL func
+                RET
+                LABEL func
+                RET"#);
+        assert_eq!(count(&kinds, LintKind::UnmatchedRet), 1);
+    }
+
+    #[test]
+    fn test_unbalanced_stack() {
+        let kinds = lint_kinds(r#"
+                # &rail-asm-v2
+                LABEL loop
+                !ST< R1
+                JMP loop"#);
+        assert_eq!(count(&kinds, LintKind::UnbalancedStack), 1);
+    }
+
+    #[test]
+    fn test_unbalanced_routine() {
+        let kinds = lint_kinds(r#"
+                # &rail-asm-v2
+                CALL func
+                HALT
+                LABEL func
+                !ST< R1
+                RET"#);
+        assert_eq!(count(&kinds, LintKind::UnbalancedStack), 1);
+    }
+
+}
@@ -0,0 +1,209 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+#[path = "../src/rail_lsp/mod.rs"]
+pub mod rail_lsp;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::{json, Value};
+
+    pub use crate::rail_lsp::{RailLanguageServer, RailLanguageServerTrait};
+    pub use crate::rail_lsp::lsp_transport::{read_message, write_message};
+    pub use crate::rail_system::rail_isa::IsaExtension;
+
+    const URI: &str = "file:///test.asm";
+    const SOURCE: &str = "CONST step 1\nLABEL loop\nADD+IM2 R1 step R1\nJMP 0 0 loop\n";
+
+        // runs the whole stdio loop over a scripted session and returns everything the server sent
+    fn run_session(requests: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        let mut script = vec![
+            json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": { "uri": URI, "languageId": "rail", "version": 1, "text": SOURCE }
+            }}),
+        ];
+        script.extend_from_slice(requests);
+        script.push(json!({ "jsonrpc": "2.0", "id": 999, "method": "shutdown" }));
+        script.push(json!({ "jsonrpc": "2.0", "method": "exit" }));
+        for message in &script {
+            write_message(&mut input, message).unwrap();
+        }
+
+        let mut output = Vec::new();
+        let mut server = RailLanguageServer::new();
+        server.run(&mut Cursor::new(input), &mut output).unwrap();
+        assert!(server.is_shutdown());
+
+        let mut reader = Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            messages.push(message.unwrap());
+        }
+        messages
+    }
+
+    fn get_response(messages: &[Value], id: u64) -> Value {
+        messages.iter().find(|message| message["id"] == id).unwrap()["result"].clone()
+    }
+
+    fn position_request(id: u64, method: &str, line: u32, character: u32) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": {
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+            "context": { "includeDeclaration": true },
+            "newName": "top"
+        }})
+    }
+
+    #[test]
+    fn test_initialize_capabilities() {
+        let messages = run_session(&[]);
+        let capabilities = &get_response(&messages, 0)["capabilities"];
+        assert_eq!(capabilities["hoverProvider"], true);
+        assert_eq!(capabilities["renameProvider"], true);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let messages = run_session(&[
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "text": "ADD R1 R2\nADD R1 WHAT R3" }]
+            }}),
+        ]);
+        let diagnostics: Vec<&Value> = messages.iter()
+            .filter(|message| message["method"] == "textDocument/publishDiagnostics")
+            .collect();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0]["params"]["diagnostics"].as_array().unwrap().len(), 0);
+        let error = &diagnostics[1]["params"]["diagnostics"][0];
+        assert_eq!(error["severity"], 1);
+        assert_eq!(error["range"]["start"]["line"], 1);
+    }
+
+    #[test]
+    fn test_const_cycle_diagnostic() {
+        let messages = run_session(&[
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "text": "CONST X X\nADD X 0 R1" }]
+            }}),
+        ]);
+        let last = messages.iter()
+            .rfind(|message| message["method"] == "textDocument/publishDiagnostics")
+            .unwrap();
+        let error = &last["params"]["diagnostics"][0];
+        assert_eq!(error["severity"], 1);
+        assert_eq!(error["range"]["start"]["line"], 1);
+    }
+
+    #[test]
+    fn test_lint_diagnostics() {
+        let messages = run_session(&[
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "text": "LABEL unused\nADD+IM1+IM2 1 2 R1\nHALT 0 0 R2" }]
+            }}),
+        ]);
+        let last = messages.iter()
+            .rfind(|message| message["method"] == "textDocument/publishDiagnostics")
+            .unwrap();
+        let warning = &last["params"]["diagnostics"][0];
+        assert_eq!(warning["severity"], 2);
+        assert_eq!(warning["range"]["start"]["line"], 0);
+    }
+
+    #[test]
+    fn test_hover() {
+        let messages = run_session(&[
+            position_request(1, "textDocument/hover", 2, 1),
+            position_request(2, "textDocument/hover", 3, 10),
+            position_request(3, "textDocument/hover", 2, 12),
+        ]);
+        let opcode = get_response(&messages, 1)["contents"]["value"].as_str().unwrap().to_string();
+        assert!(opcode.contains("`0x00`"), "{}", opcode);
+        let label = get_response(&messages, 2)["contents"]["value"].as_str().unwrap().to_string();
+        assert!(label.contains("0x00"), "{}", label);
+        let constant = get_response(&messages, 3)["contents"]["value"].as_str().unwrap().to_string();
+        assert_eq!(constant, "**CONST STEP** = `1`");
+    }
+
+    #[test]
+    fn test_definition_and_references() {
+        let messages = run_session(&[
+            position_request(1, "textDocument/definition", 3, 9),
+            position_request(2, "textDocument/references", 1, 7),
+        ]);
+        let definition = get_response(&messages, 1);
+        assert_eq!(definition["range"]["start"], json!({ "line": 1, "character": 6 }));
+        let references = get_response(&messages, 2);
+        assert_eq!(references.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_completion() {
+        let messages = run_session(&[position_request(1, "textDocument/completion", 2, 0)]);
+        let completion = get_response(&messages, 1);
+        let labels: Vec<&str> = completion.as_array().unwrap().iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"IF_LT"));
+        assert!(labels.contains(&"BZ0"));
+        assert!(labels.contains(&"LOOP"));
+        assert!(labels.contains(&"STEP"));
+    }
+
+    #[test]
+    fn test_rename() {
+        let messages = run_session(&[position_request(1, "textDocument/rename", 3, 9)]);
+        let edits = get_response(&messages, 1)["changes"][URI].clone();
+        let edits = edits.as_array().unwrap();
+        assert_eq!(edits.len(), 2);
+        assert!(edits.iter().all(|edit| edit["newText"] == "TOP"));
+    }
+
+    #[test]
+    fn test_bad_message_keeps_the_server_running() {
+        let mut input = b"Content-Length: 9\r\n\r\n{\"id\": 1,".to_vec();
+        write_message(&mut input, &json!({ "jsonrpc": "2.0", "id": 7, "result": {} })).unwrap();     // a client response
+        write_message(&mut input, &json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" })).unwrap();
+        write_message(&mut input, &json!({ "jsonrpc": "2.0", "method": "exit" })).unwrap();
+
+        let mut output = Vec::new();
+        let mut server = RailLanguageServer::new();
+        server.run(&mut Cursor::new(input), &mut output).unwrap();
+        assert!(server.is_shutdown());
+
+        let mut reader = Cursor::new(output);
+        let parse_error = read_message(&mut reader).unwrap().unwrap().unwrap();
+        assert_eq!(parse_error["id"], Value::Null);
+        assert_eq!(parse_error["error"]["code"], -32700);
+        let shutdown = read_message(&mut reader).unwrap().unwrap().unwrap();
+        assert_eq!(shutdown["id"], 1);
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_enabled_extension() {
+        let open = json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": { "uri": URI, "languageId": "rail", "version": 1, "text": "MUL+IM1+IM2 2 3 R2\nHALT" }
+        }});
+        let mut server = RailLanguageServer::new();
+        let diagnostics = server.handle_message(&open)[0]["params"]["diagnostics"].clone();
+        assert!(diagnostics[0]["message"].as_str().unwrap().contains("muldiv extension"));
+
+        server.enable_extension(IsaExtension::MulDiv);
+        let diagnostics = server.handle_message(&open)[0]["params"]["diagnostics"].clone();
+        assert_eq!(diagnostics, json!([]));
+    }
+
+}
@@ -0,0 +1,92 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_compiler/mod.rs"]
+pub mod rail_compiler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_compiler::{RailCompiler, RailCompilerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+    pub use crate::rail_system::rail_isa::IsaExtension;
+
+    fn run(binary: &[u8], enabled: bool) -> (RailSystem, u32) {
+        let mut system = RailSystem::new_with_program(binary);
+        system.set_io_print(false);
+        if enabled {
+            system.enable_extension(IsaExtension::MulDiv);
+        }
+        let mut steps = 0;
+        while !system.is_halted() && steps < 20000 {
+            system.step();
+            steps += 1;
+        }
+        assert!(system.is_halted());
+        (system, steps)
+    }
+
+    #[test]
+    fn test_semantics() {
+        let binary = RailAssembler::new().assemble(r#"
+                # &rail-asm-v2
+                # &rail-ext-muldiv
+                MOV *200 R1
+                MOV *3 R2
+                MUL R1 R2 R3
+                MULH R1 R2 R4
+                DIV R1 R2 R5
+                MOD R1 R2 R6
+                DIV R1 *0 R7
+                MOD R1 *0 R1
+                HALT"#);
+        let (system, _) = run(&binary, true);
+        let results: Vec<u8> = [3, 4, 5, 6, 7, 1].iter().map(|reg| system.get_register_value(*reg)).collect();
+        assert_eq!(results, &[88, 2, 66, 2, 0xFF, 200]);
+
+            // a machine without the extension skips them
+        let (system, _) = run(&binary, false);
+        let results: Vec<u8> = [3, 4, 5, 6, 7, 1].iter().map(|reg| system.get_register_value(*reg)).collect();
+        assert_eq!(results, &[0, 0, 0, 0, 0, 200]);
+    }
+
+    #[test]
+    fn test_not_enabled() {
+        let error = RailAssembler::new().try_assemble_program("MUL R1 R2 R3").err().unwrap();
+        assert!(error.message.contains("MUL is part of the muldiv extension"), "{}", error.message);
+        let error = RailAssembler::new().try_assemble_program("# &rail-ext-fpu\nHALT 0 0 R0").err().unwrap();
+        assert!(error.message.contains("Unknown ISA extension fpu"), "{}", error.message);
+
+        let mut assembler = RailAssembler::new();
+        assembler.enable_extension(IsaExtension::MulDiv);
+        assert_eq!(assembler.assemble("MUL R1 R2 R3"), &[0x08, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_compiler() {
+        let source = r#"
+                u8 a = 23;
+                u8 b = 7;
+
+                void main() {
+                    a = a * b;
+                    b = a / 5;
+                }"#;
+        let mut compiler = RailCompiler::new();
+        compiler.enable_extension(IsaExtension::MulDiv);
+        let asm = compiler.compile(source).unwrap_or_else(|e| panic!("{}", e));
+        assert!(asm.contains("# &rail-ext-muldiv") && !asm.contains("INCLUDE"), "{}", asm);
+
+        let plain = RailCompiler::new().compile_program(source).unwrap_or_else(|e| panic!("{}", e));
+        let extended = compiler.compile_program(source).unwrap_or_else(|e| panic!("{}", e));
+        assert!(extended.binary.len() < plain.binary.len());
+        let (plain_system, plain_steps) = run(&plain.binary, false);
+        let (extended_system, extended_steps) = run(&extended.binary, true);
+        assert!(extended_steps < plain_steps);
+        assert_eq!(plain_system.get_ram_slice(0, 1), &[161, 32]);
+        assert_eq!(extended_system.get_ram_slice(0, 1), &[161, 32]);
+    }
+
+}
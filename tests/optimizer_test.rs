@@ -0,0 +1,139 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+#[path = "../src/rail_optimizer/mod.rs"]
+pub mod rail_optimizer;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_optimizer::{OptimizerStats, RailOptimizer, RailOptimizerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn optimize(asm: &str) -> (Vec<u8>, OptimizerStats) {
+        let program = RailAssembler::new().assemble_program(asm);
+        let (optimized, stats) = RailOptimizer::new().optimize(&program);
+        (optimized.binary, stats)
+    }
+
+    fn run(binary: &[u8], steps: u32) -> RailSystem {
+        let mut system = RailSystem::new_with_program(binary);
+        system.set_io_print(false);
+        for _ in 0..steps {
+            if system.is_halted() { break }
+            system.step();
+        }
+        system
+    }
+
+    #[test]
+    fn test_remove_noop_moves() {
+        let (binary, stats) = optimize(r#"
+                MOV R1 0 R1
+                ADD+IM1+IM2 1 0 R2
+                AND+IM2 R2 255 R2
+                HALT 0 0 R3"#);
+        assert_eq!(binary, &[0xC0, 1, 0, 2,
+                             0x0E, 0, 0, 3]);
+        assert_eq!(stats.removed_moves, 2);
+    }
+
+    #[test]
+    fn test_keep_io_move() {
+        let (binary, _) = optimize(r#"
+                MOV IO 0 IO
+                HALT 0 0 R3"#);
+        assert_eq!(binary.len(), 8);
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let (binary, stats) = optimize(r#"
+                ADD+IM1+IM2 3 4 R1
+                SHL+IM2 R1 1 R2
+                SUB R2 R1 R3
+                HALT 0 0 R4"#);
+        assert_eq!(binary, &[0xC0, 7, 0, 1,
+                             0xC0, 14, 0, 2,
+                             0xC0, 7, 0, 3,
+                             0x0E, 0, 0, 4]);
+        assert_eq!(stats.folded_constants, 3);
+    }
+
+    #[test]
+    fn test_no_fold_across_labels() {
+        let (binary, _) = optimize(r#"
+                MOV+IM1 3 0 R1
+                LABEL loop
+                ADD+IM2 R1 1 R1
+                JMP 0 0 loop"#);
+        assert_eq!(&binary[4..8], &[0x40, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_no_fold_across_far_call() {
+        let (binary, _) = optimize(r#"
+                # &rail-ext-banks
+                MOV+IM1 3 0 R1
+                FAR_CALL+IM1+IM2 1 0 0
+                ADD+IM2 R1 1 R2
+                HALT 0 0 R3"#);
+        assert_eq!(&binary[8..12], &[0x40, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_thread_jumps_and_dead_code() {
+        let (binary, stats) = optimize(r#"
+                IF_EQ+IM2 R1 0 first
+                HALT 0 0 R2
+                LABEL first
+                JMP 0 0 second
+                ADD+IM1+IM2 1 1 R1
+                LABEL second
+                JMP 0 0 end
+                LABEL end
+                HALT 0 0 R3"#);
+        assert_eq!(binary, &[0x60, 1, 0, 8,
+                             0x0E, 0, 0, 2,
+                             0x0E, 0, 0, 3]);
+        assert!(stats.threaded_jumps > 0);
+        assert!(stats.removed_dead_code > 0);
+    }
+
+    #[test]
+    fn test_same_result_after_optimizing() {
+        let asm = r#"
+                # &rail-asm-v2
+                MOV *10 R1
+                MOV R2 R2
+                LABEL loop
+                CALL step
+                IF_N_EQ R1 *0 loop
+                HALT
+                MOV *1 R5
+                LABEL step
+                SUB R1 *1 R1
+                ADD R2 *3 R2
+                MOV *2 R3
+                ADD R3 *2 R3
+                JMP back
+                LABEL back
+                RET"#;
+        let original = RailAssembler::new().assemble(asm);
+        let (binary, stats) = optimize(asm);
+        assert!(binary.len() < original.len());
+        assert!(stats.get_removed_instructions() >= 3);
+
+        let before = run(&original, 500);
+        let after = run(&binary, 500);
+        assert!(before.is_halted() && after.is_halted());
+        for reg in 0..8 {
+            assert_eq!(before.get_register_value(reg), after.get_register_value(reg));
+        }
+    }
+
+}
@@ -0,0 +1,153 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_linter::{LintKind, RailLinter, RailLinterTrait};
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn run(asm: &str) -> RailSystem {
+        let rail_assembler = RailAssembler::new();
+        let mut system = RailSystem::new_with_program(&rail_assembler.assemble(asm));
+        system.set_io_print(false);
+        for _i in 0..1024 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+    fn lint_kinds(asm: &str) -> Vec<LintKind> {
+        RailLinter::new().lint(asm).iter().map(|warning| warning.kind).collect()
+    }
+
+    const SUM_ASM: &str = r#"
+                # &rail-asm-v2
+                MOV *5 R3
+                MOV *9 R4
+                MOV *3 R1
+                MOV *4 R2
+                CALL sum
+                HALT 0 0 R0
+
+                PROC sum ARGS a=R1 b=R2 USES R3 R4
+                MOV a R3
+                MOV b R4
+                ADD R3 R4 a
+                ENDP"#;
+
+    #[test]
+    fn test_prologue_and_epilogue() {
+        let program = RailAssembler::new().assemble_program(SUM_ASM);
+        let proc = &program.procs[0];
+        assert_eq!((proc.name.as_str(), proc.start, proc.end), ("SUM", 0x18, 0x38));
+        assert_eq!(proc.uses, &[3, 4]);
+        assert_eq!(&program.binary[0x18..0x20], &[0x99, 0x03, 0x00, 0x00,      // S_PUSH+IM1 R3
+                                                 0x99, 0x04, 0x00, 0x00]);     // S_PUSH+IM1 R4
+        assert_eq!(&program.binary[0x2C..0x38], &[0x18, 0x00, 0x00, 0x04,      // S_POP R4
+                                                 0x18, 0x00, 0x00, 0x03,      // S_POP R3
+                                                 0x1A, 0x00, 0x00, 0x00]);    // RET
+    }
+
+    #[test]
+    fn test_saved_registers() {
+        let system = run(SUM_ASM);
+        assert_eq!(system.get_register_value(1), 7);
+        assert_eq!(system.get_register_value(3), 5);
+        assert_eq!(system.get_register_value(4), 9);
+        assert_eq!(system.get_gen_stack_ptr(), 0xFF);
+        assert!(lint_kinds(SUM_ASM).is_empty(), "{:?}", lint_kinds(SUM_ASM));
+    }
+
+    #[test]
+    fn test_early_ret_and_stack_args() {
+        let asm = r#"
+                # &rail-asm-v2
+                MOV *8 R1
+                MOV *20 R2
+                !ST< R1 R2
+                CALL max
+                HALT 0 0 R0
+
+                PROC max ARGS a=STACK:R1 b=STACK:R2 USES R3
+                MOV *1 R3
+                IF a >= b
+                    RET
+                ENDIF
+                MOV b a
+                ENDP"#;
+        let system = run(asm);
+        assert_eq!(system.get_register_value(1), 20);
+        assert_eq!(system.get_register_value(3), 0);
+        assert_eq!(system.get_gen_stack_ptr(), 0xFF);
+        assert!(lint_kinds(asm).is_empty(), "{:?}", lint_kinds(asm));
+    }
+
+    #[test]
+    fn test_conditional_ret_before_endp() {
+            // the RET only runs when the IF is taken, so ENDP still restores R3 and returns
+        let asm = r#"
+                # &rail-asm-v2
+                MOV *1 R3
+                MOV *5 R1
+                CALL f
+                HALT 0 0 R0
+
+                PROC f USES R3
+                MOV *7 R3
+                IF R1 == R0
+                    RET
+                ENDIF
+                ENDP
+                MOV *9 R4
+                HALT 0 0 R0"#;
+        let system = run(asm);
+        assert_eq!(system.get_register_value(3), 1);
+        assert_eq!(system.get_register_value(4), 0);
+        assert_eq!(system.get_gen_stack_ptr(), 0xFF);
+    }
+
+    #[test]
+    fn test_convention_lint() {
+        let kinds = lint_kinds(r#"
+                # &rail-asm-v2
+                MOV *1 R1
+                CALL inc
+                HALT 0 0 R0
+
+                PROC inc ARGS x=R1
+                ADD x *1 R5
+                MOV R5 x
+                ENDP"#);
+        assert_eq!(kinds, &[LintKind::ProcConvention]);
+
+        let kinds = lint_kinds(r#"
+                # &rail-asm-v2
+                CALL leaky
+                HALT 0 0 R0
+
+                PROC leaky USES R3
+                S_PUSH+IM1 R3 0 0
+                ENDP"#);
+        assert_eq!(kinds, &[LintKind::UnbalancedStack]);
+    }
+
+    #[test]
+    fn test_proc_errors() {
+        let rail_assembler = RailAssembler::new();
+        let error = |asm: &str| rail_assembler.try_assemble_program(asm).err().unwrap().message;
+        assert!(error("PROC a\nHALT 0 0 R0").contains("PROC has no ENDP"));
+        assert!(error("ENDP").contains("ENDP without a matching PROC"));
+        assert!(error("PROC a\nPROC b\nENDP\nENDP").contains("PROC inside PROC"));
+        assert!(error("PROC a USES R9X\nENDP").contains("R9X is not a register"));
+        assert!(error("PROC a ARGS x=STACK:R3 USES R3\nENDP").contains("popped into a register the PROC saves"));
+    }
+
+}
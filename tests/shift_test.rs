@@ -0,0 +1,70 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+    pub use crate::rail_system::rail_isa::IsaExtension;
+
+    const AMOUNTS: [u8; 8] = [0, 1, 3, 7, 8, 9, 15, 0xFF];
+
+        // value shifted by every amount, with the amount in a register so nothing folds it
+    fn shift(op: &str, value: u8, mask: bool) -> Vec<u8> {
+        let mut assembler = RailAssembler::new();
+        assembler.enable_extension(IsaExtension::Shifts);
+        let program = assembler.assemble(&format!("{} R1 R2 R3\nHALT 0 0 R0", op));
+        let mut system = RailSystem::new_with_program(&program);
+        system.set_io_print(false);
+        system.enable_extension(IsaExtension::Shifts);
+        system.set_shift_mask(mask);
+        AMOUNTS.iter().map(|amount| {
+            system.set_register_value(14, 0);       // back to the shift
+            system.set_register_value(1, value);
+            system.set_register_value(2, *amount);
+            system.step();
+            system.get_register_value(3)
+        }).collect()
+    }
+
+    #[test]
+    fn test_logical_shifts() {
+        assert_eq!(shift("SHL", 0x81, false), &[0x81, 0x02, 0x08, 0x80, 0, 0, 0, 0]);
+        assert_eq!(shift("SHR", 0x81, false), &[0x81, 0x40, 0x10, 0x01, 0, 0, 0, 0]);
+        assert_eq!(shift("SHL", 0xFF, false), &[0xFF, 0xFE, 0xF8, 0x80, 0, 0, 0, 0]);
+        assert_eq!(shift("SHR", 0xFF, false), &[0xFF, 0x7F, 0x1F, 0x01, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_arithmetic_shift() {
+        assert_eq!(shift("ASR", 0x81, false), &[0x81, 0xC0, 0xF0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(shift("ASR", 0x7E, false), &[0x7E, 0x3F, 0x0F, 0, 0, 0, 0, 0]);
+        assert_eq!(shift("ASR", 0x80, false), &[0x80, 0xC0, 0xF0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_masked_shifts() {
+            // 8 shifts by 0, 9 by 1, 15 and 0xFF by 7
+        assert_eq!(shift("SHL", 0x81, true), &[0x81, 0x02, 0x08, 0x80, 0x81, 0x02, 0x80, 0x80]);
+        assert_eq!(shift("SHR", 0x81, true), &[0x81, 0x40, 0x10, 0x01, 0x81, 0x40, 0x01, 0x01]);
+        assert_eq!(shift("ASR", 0x81, true), &[0x81, 0xC0, 0xF0, 0xFF, 0x81, 0xC0, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_asr_needs_the_extension() {
+        let error = RailAssembler::new().try_assemble_program("ASR R1 R2 R3").err().unwrap();
+        assert!(error.message.contains("ASR is part of the shifts extension"), "{}", error.message);
+
+        let mut system = RailSystem::new_with_program(&RailAssembler::new()
+            .assemble("# &rail-ext-shifts\nADD+IM1+IM2 0x80 0 R1\nASR+IM2 R1 1 R1\nHALT 0 0 R0"));
+        system.set_io_print(false);
+        while !system.is_halted() {
+            system.step();
+        }
+        assert_eq!(system.get_register_value(1), 0x80);
+    }
+
+}
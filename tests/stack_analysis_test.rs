@@ -0,0 +1,146 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_stack_analysis::{RailStackAnalysis, RailStackAnalysisTrait, StackReport};
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn analyze(asm: &str) -> StackReport {
+        RailStackAnalysis::new().analyze(&RailAssembler::new().assemble_program(asm))
+    }
+
+    fn count_steps(asm: &str) -> u64 {
+        let mut system = RailSystem::new_with_program(&RailAssembler::new().assemble(asm));
+        system.set_io_print(false);
+        let mut steps = 0;
+        while !system.is_halted() {
+            system.step();
+            steps += 1;
+        }
+        steps
+    }
+
+    #[test]
+    fn test_call_and_gen_depth() {
+        let report = analyze(r#"
+                S_PUSH+IM1 R1 0 0
+                S_PUSH+IM1 R1 0 0
+                CALL first 0 0
+                S_POP 0 0 R1
+                S_POP 0 0 R1
+                HALT 0 0 R0
+                LABEL first
+                S_PUSH+IM1 R1 0 0
+                CALL second 0 0
+                S_POP 0 0 R1
+                RET 0 0 0
+                LABEL second
+                S_PUSH+IM1 R1 0 0
+                S_PUSH+IM1 R1 0 0
+                S_POP 0 0 R1
+                S_POP 0 0 R1
+                RET 0 0 0"#);
+        let depths: Vec<(&str, Option<u32>, Option<i32>)> = report.procs.iter()
+            .map(|proc| (proc.name.as_str(), proc.call_depth, proc.gen_depth))
+            .collect();
+        assert_eq!(depths, &[("0x00", Some(2), Some(5)), ("FIRST", Some(1), Some(3)), ("SECOND", Some(0), Some(2))]);
+        assert!(report.fits());
+        assert!(report.get_recursive().is_empty());
+    }
+
+    #[test]
+    fn test_far_call() {
+            // the routine in the other bank is not part of the program
+        let report = analyze(r#"
+                # &rail-ext-banks
+                FAR_CALL+IM1+IM2 1 0 0
+                HALT 0 0 R0"#);
+        assert_eq!(report.get_entry().call_depth, None);
+        assert_eq!(report.get_entry().steps, None);
+    }
+
+    #[test]
+    fn test_recursion() {
+        let report = analyze(r#"
+                # &rail-asm-v2
+                CALL countdown
+                HALT
+                LABEL countdown
+                IF_EQ+IM2 R1 0 done
+                SUB R1 *1 R1
+                CALL countdown
+                LABEL done
+                RET"#);
+        let recursive: Vec<&str> = report.get_recursive().iter().map(|proc| proc.name.as_str()).collect();
+        assert_eq!(recursive, &["COUNTDOWN"]);
+        assert_eq!(report.get_entry().call_depth, None);
+        assert_eq!(report.get_entry().steps, None);
+        assert!(!report.fits());
+    }
+
+    #[test]
+    fn test_counted_loop_steps() {
+        let asm = r#"
+                ADD+IM1+IM2 0 0 R1
+                LABEL loop
+                ADD+IM2 R1 1 R1
+                IF_LT+IM2 R1 10 loop
+                HALT 0 0 R0"#;
+        assert_eq!(analyze(asm).get_entry().steps, Some(22));
+        assert_eq!(count_steps(asm), 22);
+    }
+
+    #[test]
+    fn test_nested_loops_with_calls() {
+        let asm = r#"
+                ADD+IM1+IM2 5 0 R1
+                LABEL outer
+                ADD+IM1+IM2 0 0 R2
+                LABEL inner
+                CALL bump 0 0
+                ADD+IM2 R2 1 R2
+                IF_N_EQ+IM2 R2 3 inner
+                SUB+IM2 R1 1 R1
+                IF_N_EQ+IM2 R1 0 outer
+                HALT 0 0 R0
+                LABEL bump
+                ADD+IM2 R3 1 R3
+                RET 0 0 0"#;
+        let report = analyze(asm);
+        let steps: Vec<Option<u64>> = report.procs.iter().map(|proc| proc.steps).collect();
+        assert_eq!(steps, &[Some(92), Some(2)]);
+        assert_eq!(count_steps(asm), 92);
+    }
+
+    #[test]
+    fn test_unbounded() {
+            // the loop count comes from ram
+        let report = analyze(r#"
+                RAM_R+IM2 0 0x10 R1
+                LABEL loop
+                SUB+IM2 R1 1 R1
+                IF_N_EQ+IM2 R1 0 loop
+                HALT 0 0 R0"#);
+        assert_eq!(report.get_entry().steps, None);
+        assert!(report.fits());
+
+            // pushes on every iteration
+        let report = analyze(r#"
+                LABEL loop
+                S_PUSH+IM1 R1 0 0
+                ADD+IM2 R1 1 R1
+                IF_LT+IM2 R1 200 loop
+                HALT 0 0 R0"#);
+        assert_eq!(report.get_entry().gen_depth, None);
+        assert_eq!(report.get_entry().steps, Some(601));
+        assert!(!report.fits());
+    }
+
+}
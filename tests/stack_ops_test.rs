@@ -0,0 +1,117 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_analysis::rail_linter::{RailLinter, RailLinterTrait};
+    pub use crate::rail_analysis::rail_stack_analysis::{RailStackAnalysis, RailStackAnalysisTrait};
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn run(asm: &str) -> RailSystem {
+        let mut system = RailSystem::new_with_program(&RailAssembler::new().assemble(asm));
+        system.set_io_print(false);
+        for _i in 0..1000 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+    #[test]
+    fn test_peek_and_drop() {
+        let system = run(r#"
+                # &rail-asm-v2
+                MOV *10 R1
+                S_PUSH+IM1 R1
+                MOV *20 R1
+                S_PUSH+IM1 R1
+                MOV *30 R1
+                S_PUSH+IM1 R1
+                S_PEEK *0 R2
+                S_PEEK *2 R3
+                S_PEEK *3 R4
+                S_DROP *2
+                S_PEEK *0 R5
+                S_SP_R R6
+                HALT"#);
+        let registers: Vec<u8> = (2..=6).map(|reg| system.get_register_value(reg)).collect();
+            // peeking past the bottom gives 0
+        assert_eq!(registers, &[30, 10, 0, 10, 0]);
+        assert_eq!(system.get_gen_stack_ptr(), 0);
+    }
+
+    #[test]
+    fn test_frame() {
+            // a routine with two locals on the stack, dropped in one go before returning
+        let system = run(r#"
+                # &rail-asm-v2
+                MOV *7 R1
+                CALL square_plus
+                HALT
+                LABEL square_plus
+                S_SP_R R7
+                S_PUSH+IM1 R1
+                S_PUSH+IM1 R1
+                S_PEEK *0 R2
+                S_PEEK *1 R3
+                ADD R2 R3 R1
+                S_PEEK *1 R2
+                ADD R1 R2 R1
+                S_SP_W R7
+                RET"#);
+        assert_eq!(system.get_register_value(1), 21);
+        assert_eq!(system.get_gen_stack_ptr(), 0xFF);
+    }
+
+    #[test]
+    fn test_pointer_stays_in_the_stack() {
+            // past the top it wraps into the stack, dropping past the bottom leaves it empty
+        let system = run(r#"
+                # &rail-asm-v2
+                S_SP_W *200
+                S_SP_R R1
+                MOV *9 R3
+                S_PUSH+IM1 R3
+                S_POP R2
+                S_SP_W *1
+                S_DROP *5
+                S_SP_R R4
+                S_DROP *1
+                S_SP_R R5
+                HALT"#);
+        assert_eq!(system.get_register_value(1), 200 & 0x7F);
+        assert_eq!(system.get_register_value(2), 9);
+        assert_eq!(system.get_register_value(4), 0xFF);
+        assert_eq!(system.get_register_value(5), 0xFF);
+    }
+
+    #[test]
+    fn test_analysis() {
+        let program = RailAssembler::new().assemble_program(r#"
+                # &rail-asm-v2
+                S_PUSH+IM1 R1
+                S_PUSH+IM1 R1
+                S_PUSH+IM1 R1
+                S_DROP *3
+                S_PUSH+IM1 R1
+                HALT"#);
+        let report = RailStackAnalysis::new().analyze(&program);
+        assert_eq!(report.get_entry().gen_depth, Some(3));
+
+            // the depth after moving the stack pointer is not known
+        let program = RailAssembler::new().assemble_program("# &rail-asm-v2\nS_SP_W R1\nS_PUSH+IM1 R1\nHALT");
+        assert_eq!(RailStackAnalysis::new().analyze(&program).get_entry().gen_depth, None);
+
+        let warnings = RailLinter::new().lint("# &rail-asm-v2\nMOV *1 R1\nS_PUSH+IM1 R1\nS_DROP *2\nHALT");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("S_DROP on an empty gen stack"), "{}", warnings[0].message);
+    }
+
+}
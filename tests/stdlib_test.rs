@@ -0,0 +1,116 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_assembler::rasm_stdlib::RasmStdlib;
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn run(asm: &str) -> RailSystem {
+        let rail_assembler = RailAssembler::new();
+        let mut system = RailSystem::new_with_program(&rail_assembler.assemble(asm));
+        system.set_io_print(false);
+        for _i in 0..4096 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+        // loads R1 to R4, calls the routine and halts, with the module after the HALT
+    fn call(module: &str, routine: &str, args: [u8; 4]) -> RailSystem {
+        run(&format!(r#"
+                # &rail-asm-v2
+                MOV *{} R1
+                MOV *{} R2
+                MOV *{} R3
+                MOV *{} R4
+                CALL {}
+                HALT 0 0 R0
+                INCLUDE {}"#, args[0], args[1], args[2], args[3], routine, module))
+    }
+
+    #[test]
+    fn test_every_module_assembles() {
+        let rail_assembler = RailAssembler::new();
+        for module in RasmStdlib::MODULES {
+            let program = rail_assembler.try_assemble_program(&format!("INCLUDE {}", module));
+            assert!(program.is_ok(), "{} does not assemble", module);
+        }
+    }
+
+    #[test]
+    fn test_mul8() {
+        for (a, b) in [(0u8, 7u8), (3, 5), (15, 17), (200, 3), (255, 255)] {
+            let system = call("std/mul", "mul8", [a, b, 0, 0]);
+            let product = a as u16 * b as u16;
+            assert_eq!(system.get_register_value(1), product as u8, "{} * {}", a, b);
+            assert_eq!(system.get_register_value(2), (product >> 8) as u8, "{} * {}", a, b);
+            assert_eq!(system.get_gen_stack_ptr(), 0xFF);
+        }
+    }
+
+    #[test]
+    fn test_divmod() {
+        for (a, b) in [(17u8, 5u8), (4, 9), (255, 1), (200, 200)] {
+            let system = call("std/divmod", "divmod", [a, b, 0, 0]);
+            assert_eq!(system.get_register_value(1), a / b, "{} / {}", a, b);
+            assert_eq!(system.get_register_value(2), a % b, "{} % {}", a, b);
+        }
+        let system = call("std/divmod", "divmod", [42, 0, 0, 0]);
+        assert_eq!((system.get_register_value(1), system.get_register_value(2)), (255, 42));
+    }
+
+    #[test]
+    fn test_math16() {
+        let system = call("std/math16", "add16", [0xF0, 0x01, 0x20, 0x02]);     // 0x01F0 + 0x0220
+        assert_eq!((system.get_register_value(2), system.get_register_value(1)), (0x04, 0x10));
+        let system = call("std/math16", "sub16", [0x10, 0x04, 0x20, 0x02]);     // 0x0410 - 0x0220
+        assert_eq!((system.get_register_value(2), system.get_register_value(1)), (0x01, 0xF0));
+    }
+
+    #[test]
+    fn test_bcd() {
+        let system = call("std/bcd", "bcd8", [249, 0, 0, 0]);
+        assert_eq!((system.get_register_value(2), system.get_register_value(1)), (0x02, 0x49));
+        let system = call("std/bcd", "show_dec", [107, 0, 0, 0]);
+        assert_eq!([system.get_register_value(10), system.get_register_value(11), system.get_register_value(12)],
+                   [1, 0, 7]);
+    }
+
+    #[test]
+    fn test_mem() {
+        let system = run(r#"
+                # &rail-asm-v2
+                MOV *16 R1
+                MOV *7 R2
+                MOV *4 R3
+                CALL memset
+                MOV *16 R1
+                MOV *32 R2
+                MOV *3 R3
+                CALL memcpy
+                HALT 0 0 R0
+                INCLUDE std/mem"#);
+        assert_eq!(system.get_ram_slice(16, 20), &[7, 7, 7, 7, 0]);
+        assert_eq!(system.get_ram_slice(32, 35), &[7, 7, 7, 0]);
+    }
+
+    #[test]
+    fn test_include_errors_and_guard() {
+        let rail_assembler = RailAssembler::new();
+        let unknown = rail_assembler.try_assemble_program("INCLUDE std/nothing");
+        assert!(unknown.err().unwrap().message.contains("Unknown module STD/NOTHING"));
+
+        let once = rail_assembler.assemble_program("INCLUDE std/divmod");
+        let twice = rail_assembler.assemble_program("INCLUDE std/divmod\nINCLUDE std/divmod");
+        assert_eq!(once.binary, twice.binary);
+        assert!(twice.code_lines.iter().all(|line| line.line_number == 1 && line.is_included));
+    }
+
+}
@@ -0,0 +1,131 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn run(asm: &str) -> RailSystem {
+        let rail_assembler = RailAssembler::new();
+        let mut system = RailSystem::new_with_program(&rail_assembler.assemble(asm));
+        system.set_io_print(false);
+        for _i in 0..4096 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+    #[test]
+    fn test_if_lowering() {
+        let rail_assembler = RailAssembler::new();
+        let binary = rail_assembler.assemble(r#"
+                IF R1 < R2
+                ADD+IM1+IM2 1 0 R3
+                ENDIF
+                HALT 0 0 R0"#);
+        assert_eq!(binary, &[0x25, 0x01, 0x02, 0x08,        // IF_MTE R1 R2 past the body
+                             0xC0, 0x01, 0x00, 0x03,
+                             0x0E, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_if_else() {
+        let program = |a: u8, b: u8| format!(r#"
+                ADD+IM1+IM2 {} 0 R1
+                ADD+IM1+IM2 {} 0 R2
+                IF R1 >= R2
+                    SUB R1 R2 R3
+                ELSE
+                    SUB R2 R1 R3
+                ENDIF
+                HALT 0 0 R0"#, a, b);
+        assert_eq!(run(&program(9, 4)).get_register_value(3), 5);
+        assert_eq!(run(&program(4, 9)).get_register_value(3), 5);
+        assert_eq!(run(&program(7, 7)).get_register_value(3), 0);
+    }
+
+    #[test]
+    fn test_immediate_operands() {
+        let system = run(r#"
+                CONST limit 3
+                ADD+IM1+IM2 3 0 R1
+                IF R1 == *limit
+                    IF 2 != R1
+                        ADD+IM1+IM2 1 0 R2
+                    ENDIF
+                ENDIF
+                HALT 0 0 R0"#);
+        assert_eq!(system.get_register_value(2), 1);
+    }
+
+    #[test]
+    fn test_while() {
+        let system = run(r#"
+                ADD+IM1+IM2 1 0 R1
+                WHILE R1 < 100
+                    ADD R1 R1 R1
+                    ADD+IM2 R2 1 R2
+                ENDWHILE
+                HALT 0 0 R0"#);
+        assert_eq!(system.get_register_value(1), 128);
+        assert_eq!(system.get_register_value(2), 7);
+    }
+
+    #[test]
+    fn test_for() {
+        let system = run(r#"
+                FOR R1 = 1 TO 10
+                    ADD R2 R1 R2
+                ENDFOR
+                FOR R3 = 250 TO 255
+                    ADD+IM2 R4 1 R4
+                ENDFOR
+                FOR R5 = 5 TO 4
+                    ADD+IM2 R6 1 R6
+                ENDFOR
+                HALT 0 0 R0"#);
+        assert_eq!(system.get_register_value(2), 55);
+        assert_eq!(system.get_register_value(4), 6);
+        assert_eq!(system.get_register_value(6), 0);
+    }
+
+    #[test]
+    fn test_const_operands() {
+        let system = run(r#"
+                CONST limit 5
+                WHILE R1 < limit
+                    ADD+IM2 R1 1 R1
+                ENDWHILE
+                FOR R2 = 1 TO limit
+                    ADD R3 R2 R3
+                ENDFOR
+                HALT 0 0 R0"#);
+        assert_eq!(system.get_register_value(1), 5);
+        assert_eq!(system.get_register_value(3), 15);
+
+        let error = RailAssembler::new().try_assemble_program("WHILE R1 < limit\nENDWHILE\nCONST limit 5").err().unwrap();
+        assert_eq!(error.line_number, 1);
+        assert!(error.message.contains("LIMIT is neither a register nor a CONST"));
+    }
+
+    #[test]
+    fn test_structure_errors() {
+        let rail_assembler = RailAssembler::new();
+        let error = |asm: &str| rail_assembler.try_assemble_program(asm).err().unwrap();
+
+        let unclosed = error("HALT 0 0 R0\nWHILE R1 < R2\nHALT 0 0 R0");
+        assert_eq!(unclosed.line_number, 2);
+        assert!(unclosed.message.contains("never closed"));
+        assert!(error("IF R1 < R2\nENDWHILE").message.contains("ENDWHILE without a matching WHILE"));
+        assert!(error("IF R1 < R2\nELSE\nELSE\nENDIF").message.contains("ELSE without a matching IF"));
+        assert!(error("IF R1 =< R2\nENDIF").message.contains("Unknown comparison"));
+        assert!(error("FOR R1 1 10\nENDFOR").message.contains("Expected FOR"));
+    }
+
+}
@@ -0,0 +1,65 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+#[path = "../src/rail_analysis/mod.rs"]
+pub mod rail_analysis;
+#[path = "../src/rail_optimizer/mod.rs"]
+pub mod rail_optimizer;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_optimizer::rail_superoptimizer::{RailSuperoptimizer, RailSuperoptimizerTrait, SuperoptResult, SuperoptSpec};
+
+    fn asm(result: &SuperoptResult) -> Vec<Vec<String>> {
+        result.sequences.iter()
+            .map(|sequence| sequence.iter().map(|instruction| instruction.to_asm()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_single_instruction() {
+        let result = RailSuperoptimizer::new().search(&SuperoptSpec::new_function(&[1], 1, |x| x[0].wrapping_neg()));
+        assert_eq!(asm(&result), &[&["SUB+IM1 0 R1 R1"]]);
+        assert_eq!(result.sequences[0][0].to_bytes(), [0x81, 0x00, 0x01, 0x01]);
+        assert!(result.is_exhaustive);
+    }
+
+    #[test]
+    fn test_reference_sequence() {
+            // times 3 the long way round
+        let reference = RailAssembler::new().assemble(r#"
+                ADD+IM2 R1 0 R2
+                ADD R1 R2 R3
+                ADD R3 R2 R1"#);
+        let result = RailSuperoptimizer::new().search(&SuperoptSpec::new_reference(&[1], 1, &reference));
+        assert_eq!(result.get_length(), Some(2));
+        assert!(asm(&result).contains(&vec!["SHL+IM2 R1 1 R2".to_string(), "ADD R1 R2 R1".to_string()]));
+    }
+
+    #[test]
+    fn test_bit_trick() {
+            // isolates the lowest set bit
+        let result = RailSuperoptimizer::new().search(&SuperoptSpec::new_function(&[1], 2, |x| x[0] & x[0].wrapping_neg()));
+        assert_eq!(asm(&result), &[&["SUB+IM1 0 R1 R2", "AND R1 R2 R2"], &["SUB+IM1 0 R1 R3", "AND R1 R3 R2"]]);
+    }
+
+    #[test]
+    fn test_sampled_inputs() {
+        let result = RailSuperoptimizer::new().search(&SuperoptSpec::new_function(&[1, 2, 3], 4, |x| x[0] ^ x[1] ^ x[2]));
+        assert_eq!(result.get_length(), Some(2));
+        assert!(!result.is_exhaustive);
+    }
+
+    #[test]
+    fn test_not_found() {
+        let mut spec = SuperoptSpec::new_function(&[1], 1, |x| x[0].wrapping_mul(10));
+        spec.max_length = 1;
+        let result = RailSuperoptimizer::new().search(&spec);
+        assert_eq!(result.get_length(), None);
+        assert!(result.candidates > 0);
+    }
+
+}
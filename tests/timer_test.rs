@@ -0,0 +1,176 @@
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+    pub use crate::rail_system::rail_isa::IsaExtension;
+    pub use crate::rail_system::rail_peripheral::RailPeripheralTrait;
+    pub use crate::rail_system::rail_timer::*;
+
+    fn new_system(asm: &str) -> RailSystem {
+        let mut system = RailSystem::new_with_program(&RailAssembler::new().assemble(asm));
+        system.set_io_print(false);
+        system.enable_extension(IsaExtension::Interrupts);
+        system
+    }
+
+    fn run(system: &mut RailSystem, steps: u32) {
+        for _i in 0..steps {
+            system.step();
+            if system.is_halted() { break }
+        }
+    }
+
+    #[test]
+    fn test_counter_follows_the_steps() {
+            // the counter advances once every 3 steps, from the step after the one that starts it
+        let mut system = new_system(r#"
+                # &rail-asm-v2
+                PER_W+IM1+IM2 2 2
+                PER_W+IM1+IM2 1 0
+                LABEL loop
+                JMP 0 0 loop"#);
+        run(&mut system, 2);
+        assert_eq!(system.read_port(TIMER_COUNTER), 0);
+        run(&mut system, 2);
+        assert_eq!(system.read_port(TIMER_COUNTER), 0);
+        run(&mut system, 1);
+        assert_eq!(system.read_port(TIMER_COUNTER), 1);
+        run(&mut system, 3 * 255);
+        assert_eq!(system.read_port(TIMER_COUNTER), 0);     // free running, it wraps
+        assert_eq!(system.read_port(TIMER_STATUS), TIMER_EXPIRED);      // passing compare 0 on the way
+    }
+
+    #[test]
+    fn test_expired_flag() {
+        let mut system = new_system(r#"
+                # &rail-asm-v2
+                PER_W+IM1+IM2 5 3
+                PER_W+IM1+IM2 5 0
+                LABEL wait
+                PER_R+IM2 1 R1
+                IF_EQ+IM2 R1 0 wait
+                PER_W+IM1+IM2 1 1
+                PER_R+IM2 1 R2
+                PER_R+IM2 4 R3
+                HALT"#);
+        run(&mut system, 100);
+        assert!(system.is_halted());
+        assert_eq!(system.get_register_value(1), TIMER_EXPIRED);
+        assert_eq!(system.get_register_value(2), 0);
+        assert!(system.get_register_value(3) < 5);      // TIMER_CLEAR started it over
+        assert_eq!(system.get_pending_interrupts(), 0);     // no TIMER_IRQ
+    }
+
+    #[test]
+    fn test_periodic_interrupt_blinks_lv0() {
+            // every 4 * 5 = 20 steps the handler toggles LV0, instead of a busy loop per delay. The
+            // first time the counter reaches 5 is step 25, and the handler is done 2 steps later.
+        let mut system = new_system(r#"
+                # &rail-asm-v2
+                # &rail-ext-interrupts
+                INT_VEC+IM1 0 blink
+                INT_EN+IM1 1
+                PER_W+IM1+IM2 3 2
+                PER_W+IM1+IM2 5 3
+                PER_W+IM1+IM2 7 0
+                LABEL idle
+                JMP 0 0 idle
+                LABEL blink
+                XOR+IM2 LV0 1 LV0
+                ADD+IM2 R1 1 R1
+                RETI"#);
+        run(&mut system, 7);
+        for blinks in 1..=4 {
+            run(&mut system, 20);
+            assert_eq!(system.get_register_value(1), blinks);
+        }
+        assert_eq!(system.get_register_value(9), 0);
+        assert_eq!(system.read_port(TIMER_COUNTER), 0);
+        run(&mut system, 20);
+        assert_eq!(system.get_register_value(9), 1);
+    }
+
+    #[test]
+    fn test_polling_without_interrupts() {
+            // TIMER_IRQ is set, but a machine without the extension only gets the expired flag
+        let mut system = RailSystem::new_with_program(&RailAssembler::new().assemble(r#"
+                # &rail-asm-v2
+                PER_W+IM1+IM2 4 3
+                PER_W+IM1+IM2 7 0
+                LABEL wait
+                PER_R+IM2 1 R1
+                IF_EQ+IM2 R1 0 wait
+                HALT"#));
+        system.set_io_print(false);
+        run(&mut system, 100);
+        assert!(system.is_halted());
+        assert_eq!(system.get_register_value(1), TIMER_EXPIRED);
+        assert_eq!(system.get_pending_interrupts(), 0);
+    }
+
+    #[test]
+    fn test_wall_clock_catches_up() {
+            // every millisecond passed between two steps counts, however many there are
+        let mut timer = RailTimer::new();
+        timer.write(TIMER_COMPARE, 10);
+        timer.write(TIMER_CONTROL, TIMER_RUN | TIMER_CLEAR | TIMER_WALL_CLOCK);
+        assert!(!timer.step());
+        std::thread::sleep(std::time::Duration::from_millis(25));
+        assert!(!timer.step());     // no TIMER_IRQ
+        assert_eq!(timer.read(TIMER_STATUS), TIMER_EXPIRED);
+        assert!(timer.read(TIMER_COUNTER) < 10);
+    }
+
+    struct Doorbell {
+        value: u8,
+        ring_at: u32,
+        steps: u32
+    }
+
+    impl RailPeripheralTrait for Doorbell {
+        fn get_port_count(&self) -> u8 {
+            1
+        }
+
+        fn read(&self, _port: u8) -> u8 {
+            self.value
+        }
+
+        fn write(&mut self, _port: u8, value: u8) {
+            self.value = value;
+        }
+
+        fn step(&mut self) -> bool {
+            self.steps += 1;
+            self.steps == self.ring_at
+        }
+    }
+
+    #[test]
+    fn test_attached_device() {
+        let mut system = new_system(r#"
+                # &rail-asm-v2
+                # &rail-ext-interrupts
+                INT_VEC+IM1 3 ring
+                INT_EN+IM1 8
+                PER_W+IM1+IM2 42 0x20
+                LABEL idle
+                JMP 0 0 idle
+                LABEL ring
+                PER_R+IM2 0x20 R1
+                PER_R+IM2 0x21 R2
+                HALT"#);
+        system.attach_peripheral(0x20, 3, Box::new(Doorbell { value: 0, ring_at: 6, steps: 0 }));
+        system.set_register_value(2, 9);
+        run(&mut system, 100);
+        assert!(system.is_halted());
+        assert_eq!(system.get_register_value(1), 42);
+        assert_eq!(system.get_register_value(2), 0);    // nothing at 0x21
+    }
+
+}
@@ -0,0 +1,80 @@
+
+#[path = "../src/rail_system/mod.rs"]
+pub mod rail_system;
+#[path = "../src/rail_assembler/mod.rs"]
+pub mod rail_assembler;
+
+#[cfg(test)]
+mod tests {
+    pub use crate::rail_assembler::{RailAssembler, RailAssemblerTrait};
+    pub use crate::rail_system::{RailSystem, RailSystemTrait};
+
+    fn run(asm: &str, unified: bool) -> RailSystem {
+        let binary = RailAssembler::new().assemble(asm);
+        let mut system = RailSystem::new_with_program(&binary);
+        system.set_io_print(false);
+        system.set_unified_memory(unified);
+        for _i in 0..1000 {
+            system.step();
+            if system.is_halted() { break }
+        }
+        assert!(system.is_halted());
+        system
+    }
+
+    #[test]
+    fn test_patching_an_operand() {
+            // raises the constant of the first ADD on every iteration
+        let asm = r#"
+                LABEL loop
+                ADD+IM2 R2 1 R2
+                RAM_R+IM2 0 2 R3
+                ADD+IM2 R3 1 R3
+                RAM_W+IM1+IM2 R3 2 0
+                IF_LT+IM2 R2 4 loop
+                HALT 0 0 R0"#;
+        let system = run(asm, true);
+        assert_eq!(system.get_register_value(2), 1 + 2 + 3);
+        assert_eq!(system.get_program_slice(0, 3), &[0x40, 2, 4, 2]);
+
+            // separate memories leave the code alone
+        let system = run(asm, false);
+        assert_eq!(system.get_register_value(2), 4);
+        assert_eq!(system.get_program_slice(0, 3), &[0x40, 2, 1, 2]);
+        assert_eq!(system.get_ram_slice(2, 2), &[4]);
+    }
+
+    #[test]
+    fn test_instruction_overwriting_itself() {
+            // the write finishes as decoded, and the HALT it leaves behind runs the next time around
+        let system = run(r#"
+                ADD+IM1+IM2 0x0E 0 R1
+                LABEL again
+                ADD+IM2 R2 1 R2
+                RAM_W+IM1+IM2 R1 8 0
+                JMP 0 0 again"#, true);
+        assert_eq!(system.get_register_value(2), 2);
+        assert_eq!(system.get_cnt_register_value(), 12);
+    }
+
+    #[test]
+    fn test_loader() {
+            // copies the routine to 0xC0 and calls the copy
+        let system = run(r#"
+                # &rail-asm-v2
+                MOV *0 R1
+                LABEL copy
+                RAM_RX R1 *routine R2
+                RAM_WX R2 R1 0xC0
+                ADD R1 *1 R1
+                IF_LT R1 *8 copy
+                CALL 0xC0
+                HALT
+                LABEL routine
+                ADD R3 *5 R3
+                RET"#, true);
+        assert_eq!(system.get_register_value(3), 5);
+        assert_eq!(system.get_ram_slice(0xC0, 0xC7), system.get_program_slice(0x1C, 0x23));
+    }
+
+}